@@ -0,0 +1,204 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! `#[derive(S7Struct)]` for the `s7` crate, enable its `derive` feature instead of using this directly
+//!
+//! The members are placed with `s7::layout::Layout` in declaration order,
+//! `String` fields are `STRING`s and need their max length `#[s7(string = 20)]`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt};
+
+#[proc_macro_derive(S7Struct, attributes(s7))]
+pub fn derive_s7_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// how a field is placed, decoded and encoded
+enum Kind {
+    Member(Box<syn::Type>),
+    String(u8),
+}
+
+struct Member {
+    ident: syn::Ident,
+    kind: Kind,
+}
+
+impl Member {
+    fn place(&self) -> TokenStream2 {
+        match &self.kind {
+            Kind::Member(ty) => quote! {
+                <#ty as ::s7::layout::Member>::place(&mut layout)
+            },
+            Kind::String(max_length) => quote! {
+                ::s7::layout::place_string(&mut layout, #max_length)
+            },
+        }
+    }
+
+    fn decode(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let place = self.place();
+
+        match &self.kind {
+            Kind::Member(ty) => quote! {
+                #ident: <#ty as ::s7::layout::Member>::decode(bytes, #place)?
+            },
+            Kind::String(max_length) => quote! {
+                #ident: ::s7::layout::decode_string(bytes, #place, #max_length)?
+            },
+        }
+    }
+
+    fn encode(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        let place = self.place();
+
+        match &self.kind {
+            Kind::Member(ty) => quote! {
+                <#ty as ::s7::layout::Member>::encode(&self.#ident, &mut bytes, #place)?;
+            },
+            Kind::String(max_length) => quote! {
+                ::s7::layout::encode_string(&self.#ident, &mut bytes, #place, #max_length)?;
+            },
+        }
+    }
+}
+
+fn members(input: &DeriveInput) -> syn::Result<Vec<Member>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "S7Struct needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "S7Struct can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut members = Vec::with_capacity(fields.len());
+
+    for field in fields.iter() {
+        let mut string = None;
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("s7")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("string") {
+                    let max_length: LitInt = meta.value()?.parse()?;
+                    string = Some(max_length.base10_parse::<u8>()?);
+                    return Ok(());
+                }
+                Err(meta.error("unknown s7 attribute, expected `string = <max length>`"))
+            })?;
+        }
+
+        let kind = match string {
+            Some(max_length) => Kind::String(max_length),
+            None if is_string(&field.ty) => {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "STRING fields need a max length, ie `#[s7(string = 20)]`",
+                ))
+            }
+            None => Kind::Member(Box::new(field.ty.clone())),
+        };
+
+        members.push(Member {
+            ident: field.ident.clone().expect("named field"),
+            kind,
+        });
+    }
+
+    Ok(members)
+}
+
+fn is_string(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident == "String")
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let members = members(&input)?;
+
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let places = members.iter().map(Member::place);
+    let decodes = members.iter().map(Member::decode);
+    let encodes = members.iter().map(Member::encode);
+
+    Ok(quote! {
+        impl #impl_generics ::s7::layout::S7Struct for #name #ty_generics #where_clause {
+            fn size() -> usize {
+                let mut layout = ::s7::layout::Layout::new();
+                #(#places;)*
+                layout.size()
+            }
+
+            fn from_bytes(bytes: &[u8]) -> ::std::result::Result<Self, ::s7::error::Error> {
+                ::s7::layout::check_size(
+                    #name_str,
+                    bytes,
+                    <Self as ::s7::layout::S7Struct>::size(),
+                )?;
+
+                let mut layout = ::s7::layout::Layout::new();
+                Ok(#name {
+                    #(#decodes,)*
+                })
+            }
+
+            fn to_bytes(&self) -> ::std::result::Result<Vec<u8>, ::s7::error::Error> {
+                let mut bytes = vec![0u8; <Self as ::s7::layout::S7Struct>::size()];
+                let mut layout = ::s7::layout::Layout::new();
+                #(#encodes)*
+                Ok(bytes)
+            }
+        }
+
+        // nested structs start at an even byte and take up an even number of bytes
+        impl #impl_generics ::s7::layout::Member for #name #ty_generics #where_clause {
+            fn place(layout: &mut ::s7::layout::Layout) -> usize {
+                layout.words(<Self as ::s7::layout::S7Struct>::size())
+            }
+
+            fn decode(bytes: &[u8], offset: usize) -> ::std::result::Result<Self, ::s7::error::Error> {
+                let start = offset / 8;
+                let end = start + <Self as ::s7::layout::S7Struct>::size();
+                <Self as ::s7::layout::S7Struct>::from_bytes(&bytes[start..end])
+            }
+
+            fn encode(&self, bytes: &mut [u8], offset: usize) -> ::std::result::Result<(), ::s7::error::Error> {
+                let encoded = <Self as ::s7::layout::S7Struct>::to_bytes(self)?;
+                let start = offset / 8;
+                bytes[start..start + encoded.len()].copy_from_slice(&encoded);
+                Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,78 @@
+use s7::layout::S7Struct;
+
+#[derive(Debug, PartialEq, S7Struct)]
+struct Motor {
+    running: bool,
+    fault: bool,
+    mode: u8,
+    speed: i16,
+    #[s7(string = 10)]
+    name: String,
+    setpoints: [f32; 2],
+    id: u8,
+    alarms: [bool; 3],
+}
+
+#[derive(Debug, PartialEq, S7Struct)]
+struct Line {
+    count: u8,
+    motor: Motor,
+    enabled: bool,
+}
+
+fn motor() -> Motor {
+    Motor {
+        running: true,
+        fault: false,
+        mode: 3,
+        speed: -2,
+        name: "M1".to_string(),
+        setpoints: [53.5, -1.5],
+        id: 7,
+        alarms: [false, true, true],
+    }
+}
+
+#[test]
+fn test_layout() {
+    assert_eq!(28, Motor::size());
+    assert_eq!(32, Line::size());
+
+    let bytes = motor().to_bytes().unwrap();
+    assert_eq!(
+        vec![
+            1, 3, // running, fault, mode
+            0xFF, 0xFE, // speed
+            10, 2, b'M', b'1', 0, 0, 0, 0, 0, 0, 0, 0, // name
+            66, 86, 0, 0, 191, 192, 0, 0, // setpoints
+            7, 0, // id
+            0b110, 0, // alarms
+        ],
+        bytes
+    );
+    assert_eq!(motor(), Motor::from_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn test_nested() {
+    let line = Line {
+        count: 2,
+        motor: motor(),
+        enabled: true,
+    };
+
+    let bytes = line.to_bytes().unwrap();
+    assert_eq!(2, bytes[0]);
+    assert_eq!(motor().to_bytes().unwrap(), bytes[2..30].to_vec());
+    assert_eq!(1, bytes[30]);
+    assert_eq!(line, Line::from_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn test_errors() {
+    assert!(Motor::from_bytes(&[0; 27]).is_err());
+
+    let mut motor = motor();
+    motor.name = "longer than ten".to_string();
+    assert!(motor.to_bytes().is_err());
+}
@@ -0,0 +1,454 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Symbolic S7 addresses like `DB10.DBX8.4`, `MW20`, `I0.1`, `QD4`, `C5` or `T3`
+//!
+//! Both the English (I/Q) and the German (E/A) mnemonics are accepted.
+
+use super::client::DataItem;
+use super::constant::{self, Area};
+use super::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// the address is sent as a 3 bytes bit offset
+pub(crate) const MAX_BIT_OFFSET: u32 = 0x00FF_FFFF;
+
+/// a typed S7 address
+///
+/// # Examples
+///
+/// ```
+/// use s7::address::Address;
+/// use s7::constant::{self, Area};
+///
+/// let address: Address = "DB10.DBX8.4".parse().unwrap();
+///
+/// assert_eq!(Area::DataBausteine, address.area());
+/// assert_eq!(10, address.db_number());
+/// assert_eq!(8, address.byte());
+/// assert_eq!(4, address.bit());
+/// assert_eq!(constant::WL_BIT, address.word_len());
+///
+/// let address: Address = "EW20".parse().unwrap();
+/// assert_eq!(Area::ProcessInput, address.area());
+/// assert_eq!(constant::WL_WORD, address.word_len());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    area: Area,
+    db_number: u16,
+    byte: u32,
+    bit: u8,
+    word_len: i32,
+}
+
+impl Address {
+    /// `byte` is the counter or timer number for `Area::Counter` and `Area::Timer`
+    /// `word_len` is one of `WL_BIT`, `WL_BYTE`, `WL_WORD` and `WL_DWORD` for the other areas
+    /// `bit` must be zero for every `word_len` but `WL_BIT`
+    pub fn new(
+        area: Area,
+        db_number: u16,
+        byte: u32,
+        bit: u8,
+        word_len: i32,
+    ) -> Result<Address, Error> {
+        let address = Address {
+            area,
+            db_number,
+            byte,
+            bit,
+            word_len,
+        };
+        address.validate().map_err(|reason| Error::InvalidAddress {
+            address: address.to_string(),
+            reason,
+        })?;
+        Ok(address)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self.area {
+            Area::Counter if self.word_len != constant::WL_COUNTER => {
+                return Err("counters must have a counter word length".to_string())
+            }
+            Area::Timer if self.word_len != constant::WL_TIMER => {
+                return Err("timers must have a timer word length".to_string())
+            }
+            Area::Unknown => return Err("unknown area".to_string()),
+            _ => {}
+        }
+
+        // the widths of the notation, any other word length couldn't be parsed back
+        match self.word_len {
+            constant::WL_BIT | constant::WL_BYTE | constant::WL_WORD | constant::WL_DWORD => {}
+            constant::WL_COUNTER | constant::WL_TIMER
+                if self.area == Area::Counter || self.area == Area::Timer => {}
+            _ => return Err(format!("invalid word length {}", self.word_len)),
+        }
+
+        if self.word_len == constant::WL_BIT {
+            if self.bit > 7 {
+                return Err(format!("bit offset must be 0..7 got {}", self.bit));
+            }
+        } else if self.bit != 0 {
+            return Err(format!("only bits can have a bit offset got {}", self.bit));
+        }
+
+        if self.area != Area::DataBausteine && self.db_number != 0 {
+            return Err(format!(
+                "only data blocks have a number got {}",
+                self.db_number
+            ));
+        }
+
+        if self.byte > MAX_BIT_OFFSET >> 3 {
+            return Err(format!(
+                "byte offset must be 0..{} got {}",
+                MAX_BIT_OFFSET >> 3,
+                self.byte
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn area(&self) -> Area {
+        self.area
+    }
+
+    pub fn db_number(&self) -> u16 {
+        self.db_number
+    }
+
+    /// byte offset, or the counter/timer number
+    pub fn byte(&self) -> u32 {
+        self.byte
+    }
+
+    pub fn bit(&self) -> u8 {
+        self.bit
+    }
+
+    pub fn word_len(&self) -> i32 {
+        self.word_len
+    }
+
+    /// size of the addressed value in bytes
+    pub fn size(&self) -> i32 {
+        constant::data_size_byte(self.word_len)
+    }
+
+    /// start of the address as expected by the client,
+    /// the bit offset `byte * 8 + bit` for bits, the byte offset for everything else
+    pub fn start(&self) -> i32 {
+        match self.word_len {
+            constant::WL_BIT => (self.byte * 8 + self.bit as u32) as i32,
+            _ => self.byte as i32,
+        }
+    }
+}
+
+impl From<Address> for DataItem {
+    fn from(address: Address) -> Self {
+        DataItem::new(
+            address.area,
+            address.db_number as i32,
+            address.start(),
+            address.word_len,
+            1,
+        )
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = match self.word_len {
+            constant::WL_BIT => "X",
+            constant::WL_BYTE => "B",
+            constant::WL_WORD => "W",
+            constant::WL_DWORD => "D",
+            _ => "",
+        };
+
+        match self.area {
+            Area::DataBausteine if self.word_len == constant::WL_BIT => {
+                write!(f, "DB{}.DBX{}.{}", self.db_number, self.byte, self.bit)
+            }
+            Area::DataBausteine => write!(f, "DB{}.DB{}{}", self.db_number, width, self.byte),
+            Area::Counter => write!(f, "C{}", self.byte),
+            Area::Timer => write!(f, "T{}", self.byte),
+            Area::Unknown => write!(f, "?{}", self.byte),
+            _ => {
+                let area = match self.area {
+                    Area::ProcessInput => "I",
+                    Area::ProcessOutput => "Q",
+                    _ => "M",
+                };
+
+                if self.word_len == constant::WL_BIT {
+                    write!(f, "{}{}.{}", area, self.byte, self.bit)
+                } else {
+                    write!(f, "{}{}{}", area, width, self.byte)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: String| Error::InvalidAddress {
+            address: s.to_string(),
+            reason,
+        };
+
+        let upper = s.trim().to_ascii_uppercase();
+
+        let (area, db_number, rest) = if upper.starts_with("DB") {
+            let dot = upper
+                .find('.')
+                .ok_or_else(|| err("missing '.' after the data block number".to_string()))?;
+            let db_number = parse_number(&upper[2..dot], "data block number").map_err(err)?;
+
+            if db_number > u16::MAX as u32 {
+                return Err(err(format!(
+                    "data block number must be 0..{} got {}",
+                    u16::MAX,
+                    db_number
+                )));
+            }
+
+            let rest = upper[dot + 1..]
+                .strip_prefix("DB")
+                .ok_or_else(|| err("expected 'DB' before the width".to_string()))?;
+
+            (Area::DataBausteine, db_number as u16, rest)
+        } else {
+            let mut chars = upper.chars();
+            let area = match chars.next() {
+                Some('I') | Some('E') => Area::ProcessInput,
+                Some('Q') | Some('A') => Area::ProcessOutput,
+                Some('M') => Area::Merker,
+                Some('C') | Some('Z') => Area::Counter,
+                Some('T') => Area::Timer,
+                Some(c) => return Err(err(format!("unknown area '{}'", c))),
+                None => return Err(err("empty address".to_string())),
+            };
+            (area, 0, chars.as_str())
+        };
+
+        let address = match area {
+            Area::Counter | Area::Timer => {
+                let word_len = match area {
+                    Area::Counter => constant::WL_COUNTER,
+                    _ => constant::WL_TIMER,
+                };
+                let number = parse_number(rest, "number").map_err(err)?;
+                Address {
+                    area,
+                    db_number,
+                    byte: number,
+                    bit: 0,
+                    word_len,
+                }
+            }
+            _ => {
+                let (word_len, rest) = match rest.chars().next() {
+                    Some('X') => (constant::WL_BIT, &rest[1..]),
+                    Some('B') => (constant::WL_BYTE, &rest[1..]),
+                    Some('W') => (constant::WL_WORD, &rest[1..]),
+                    Some('D') => (constant::WL_DWORD, &rest[1..]),
+                    // `DB` addresses need an explicit width, `X` is implied everywhere else
+                    Some(c) if c.is_ascii_digit() && area != Area::DataBausteine => {
+                        (constant::WL_BIT, rest)
+                    }
+                    Some(c) => return Err(err(format!("unknown width '{}'", c))),
+                    None => return Err(err("missing width and offset".to_string())),
+                };
+
+                let (byte, bit) = match rest.find('.') {
+                    Some(dot) if word_len == constant::WL_BIT => (
+                        parse_number(&rest[..dot], "byte offset").map_err(err)?,
+                        parse_number(&rest[dot + 1..], "bit offset").map_err(err)?,
+                    ),
+                    Some(_) => {
+                        return Err(err("only bits can have a bit offset".to_string()));
+                    }
+                    None if word_len == constant::WL_BIT => {
+                        return Err(err("missing bit offset".to_string()));
+                    }
+                    None => (parse_number(rest, "byte offset").map_err(err)?, 0),
+                };
+
+                if bit > 7 {
+                    return Err(err(format!("bit offset must be 0..7 got {}", bit)));
+                }
+
+                Address {
+                    area,
+                    db_number,
+                    byte,
+                    bit: bit as u8,
+                    word_len,
+                }
+            }
+        };
+
+        address.validate().map_err(err)?;
+        Ok(address)
+    }
+}
+
+fn parse_number(s: &str, what: &str) -> Result<u32, String> {
+    if s.is_empty() {
+        return Err(format!("missing {}", what));
+    }
+
+    if !s.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("invalid {} '{}'", what, s));
+    }
+
+    s.parse::<u32>()
+        .map_err(|e| format!("invalid {} '{}': {}", what, s, e))
+}
+
+#[test]
+fn test_parse() {
+    let cases = vec![
+        (
+            "DB10.DBX8.4",
+            Area::DataBausteine,
+            10,
+            8,
+            4,
+            constant::WL_BIT,
+        ),
+        (
+            "db10.dbb8",
+            Area::DataBausteine,
+            10,
+            8,
+            0,
+            constant::WL_BYTE,
+        ),
+        (
+            "DB1.DBW20",
+            Area::DataBausteine,
+            1,
+            20,
+            0,
+            constant::WL_WORD,
+        ),
+        (
+            "DB888.DBD12",
+            Area::DataBausteine,
+            888,
+            12,
+            0,
+            constant::WL_DWORD,
+        ),
+        ("MW20", Area::Merker, 0, 20, 0, constant::WL_WORD),
+        ("M3.7", Area::Merker, 0, 3, 7, constant::WL_BIT),
+        ("I0.1", Area::ProcessInput, 0, 0, 1, constant::WL_BIT),
+        ("E0.1", Area::ProcessInput, 0, 0, 1, constant::WL_BIT),
+        ("EB2", Area::ProcessInput, 0, 2, 0, constant::WL_BYTE),
+        ("QD4", Area::ProcessOutput, 0, 4, 0, constant::WL_DWORD),
+        ("AW4", Area::ProcessOutput, 0, 4, 0, constant::WL_WORD),
+        ("A1.0", Area::ProcessOutput, 0, 1, 0, constant::WL_BIT),
+        ("C5", Area::Counter, 0, 5, 0, constant::WL_COUNTER),
+        ("Z5", Area::Counter, 0, 5, 0, constant::WL_COUNTER),
+        ("T3", Area::Timer, 0, 3, 0, constant::WL_TIMER),
+    ];
+
+    for (input, area, db_number, byte, bit, word_len) in cases {
+        let address: Address = input.parse().unwrap();
+        assert_eq!(
+            Address::new(area, db_number, byte, bit, word_len).unwrap(),
+            address,
+            "{}",
+            input
+        );
+    }
+
+    assert_eq!(8 * 8 + 4, "DB10.DBX8.4".parse::<Address>().unwrap().start());
+    assert_eq!(
+        "DB10.DBX8.4",
+        "db10.dbx8.4".parse::<Address>().unwrap().to_string()
+    );
+    assert_eq!("Q4.1", "A4.1".parse::<Address>().unwrap().to_string());
+}
+
+#[test]
+fn test_display_round_trip() {
+    let word_lens = [
+        constant::WL_BIT,
+        constant::WL_BYTE,
+        constant::WL_CHAR,
+        constant::WL_WORD,
+        constant::WL_INT,
+        constant::WL_DWORD,
+        constant::WL_DINT,
+        constant::WL_REAL,
+        constant::WL_COUNTER,
+        constant::WL_TIMER,
+    ];
+    let areas = [
+        Area::ProcessInput,
+        Area::ProcessOutput,
+        Area::Merker,
+        Area::DataBausteine,
+        Area::Counter,
+        Area::Timer,
+    ];
+
+    // every address that can be built prints one that parses back
+    for area in areas.iter() {
+        let db_number = match area {
+            Area::DataBausteine => 1,
+            _ => 0,
+        };
+
+        for word_len in word_lens.iter() {
+            let address = match Address::new(*area, db_number, 8, 0, *word_len) {
+                Ok(address) => address,
+                Err(_) => continue,
+            };
+            assert_eq!(address, address.to_string().parse().unwrap(), "{}", address);
+        }
+    }
+
+    assert!(Address::new(Area::DataBausteine, 1, 8, 0, constant::WL_REAL).is_err());
+    assert!(Address::new(Area::Merker, 0, 8, 0, constant::WL_COUNTER).is_err());
+}
+
+#[test]
+fn test_parse_invalid() {
+    let cases = vec![
+        "",
+        "X1",
+        "DB10",
+        "DB10.DBX8",
+        "DB10.DBX8.8",
+        "DB10.DBW8.1",
+        "DB10.W8",
+        "DB.DBW8",
+        "DB70000.DBW8",
+        "MW",
+        "MW-1",
+        "MK3",
+        "I0",
+        "C",
+        "T1.2",
+    ];
+
+    for input in cases {
+        match input.parse::<Address>() {
+            Err(Error::InvalidAddress { address, .. }) => assert_eq!(input, address),
+            r => panic!("{} should be invalid got {:?}", input, r),
+        }
+    }
+}
@@ -0,0 +1,554 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Async client, the same API as `client::Client` on an `AsyncTransport`
+
+use super::address::Address;
+use super::client::{CPInfo, CpuInfo, DataItem};
+use super::constant::{self, Area, CpuStatus};
+use super::error::Error;
+use super::field::{BitAddress, Field, SizedField};
+use super::telegram::{self, Control, Group, SzlRead};
+use super::transport::{self, AsyncTransport};
+use std::time::Duration;
+
+/// # Examples
+///
+/// ```no_run
+/// use std::net::{Ipv4Addr, IpAddr};
+/// use s7::{async_client::AsyncClient, async_tcp, tcp, transport};
+///
+/// # async fn run() -> Result<(), s7::error::Error> {
+/// let addr = Ipv4Addr::new(127, 0, 0, 1);
+/// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+///
+/// let t = async_tcp::Transport::connect(opts).await?;
+/// let mut cl = AsyncClient::new(t).await?;
+///
+/// let buffer = &mut vec![0u8; 4];
+/// cl.ag_read(888, 8, 4, buffer).await?;
+/// cl.ag_write(888, 8, 4, &[66, 86, 0, 0]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncClient<T: AsyncTransport> {
+    transport: T,
+    // PDU reference of the last request
+    pdu_reference: u16,
+}
+
+impl<T: AsyncTransport> AsyncClient<T> {
+    pub async fn new(mut transport: T) -> Result<AsyncClient<T>, Error> {
+        transport.negotiate().await?;
+        Ok(AsyncClient {
+            transport,
+            pdu_reference: 0,
+        })
+    }
+
+    /// see `Client::session_info`
+    pub fn session_info(&self) -> transport::SessionInfo {
+        self.transport.session_info()
+    }
+
+    pub async fn ag_read(
+        &mut self,
+        db_number: i32,
+        start: i32,
+        size: i32,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.read(
+            Area::DataBausteine,
+            db_number,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    pub async fn ag_write(
+        &mut self,
+        db_number: i32,
+        start: i32,
+        size: i32,
+        buffer: &[u8],
+    ) -> Result<(), Error> {
+        self.write(
+            Area::DataBausteine,
+            db_number,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    pub async fn mb_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(Area::Merker, 0, start, size, constant::WL_BYTE, buffer)
+            .await
+    }
+
+    pub async fn mb_write(&mut self, start: i32, size: i32, buffer: &[u8]) -> Result<(), Error> {
+        self.write(Area::Merker, 0, start, size, constant::WL_BYTE, buffer)
+            .await
+    }
+
+    pub async fn eb_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(
+            Area::ProcessInput,
+            0,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    pub async fn eb_write(&mut self, start: i32, size: i32, buffer: &[u8]) -> Result<(), Error> {
+        self.write(
+            Area::ProcessInput,
+            0,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    pub async fn ab_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(
+            Area::ProcessOutput,
+            0,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    pub async fn ab_write(&mut self, start: i32, size: i32, buffer: &[u8]) -> Result<(), Error> {
+        self.write(
+            Area::ProcessOutput,
+            0,
+            start,
+            size,
+            constant::WL_BYTE,
+            buffer,
+        )
+        .await
+    }
+
+    /// see `Client::read_bit`
+    pub async fn read_bit(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        address: BitAddress,
+    ) -> Result<bool, Error> {
+        let start = telegram::bit_start(area, address)?;
+        let mut buffer = [0u8; 1];
+
+        self.read(area, db_number, start, 1, constant::WL_BIT, &mut buffer)
+            .await?;
+        Ok(buffer[0] & 0x01 != 0)
+    }
+
+    /// see `Client::write_bit`
+    pub async fn write_bit(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        address: BitAddress,
+        value: bool,
+    ) -> Result<(), Error> {
+        let start = telegram::bit_start(area, address)?;
+
+        self.write(area, db_number, start, 1, constant::WL_BIT, &[value as u8])
+            .await
+    }
+
+    /// see `Client::ct_read`
+    pub async fn ct_read(&mut self, start: i32, amount: i32) -> Result<Vec<u16>, Error> {
+        let mut buffer = telegram::element_buffer(start, amount)?;
+        self.read(
+            Area::Counter,
+            0,
+            start,
+            amount,
+            constant::WL_COUNTER,
+            buffer.as_mut_slice(),
+        )
+        .await?;
+
+        telegram::decode_counters(buffer.as_slice())
+    }
+
+    /// see `Client::ct_write`
+    pub async fn ct_write(&mut self, start: i32, values: &[u16]) -> Result<(), Error> {
+        let buffer = telegram::encode_counters(start, values)?;
+
+        self.write(
+            Area::Counter,
+            0,
+            start,
+            values.len() as i32,
+            constant::WL_COUNTER,
+            buffer.as_slice(),
+        )
+        .await
+    }
+
+    /// see `Client::tm_read`
+    pub async fn tm_read(&mut self, start: i32, amount: i32) -> Result<Vec<Duration>, Error> {
+        let mut buffer = telegram::element_buffer(start, amount)?;
+        self.read(
+            Area::Timer,
+            0,
+            start,
+            amount,
+            constant::WL_TIMER,
+            buffer.as_mut_slice(),
+        )
+        .await?;
+
+        telegram::decode_timers(buffer.as_slice())
+    }
+
+    /// see `Client::tm_write`
+    pub async fn tm_write(&mut self, start: i32, values: &[Duration]) -> Result<(), Error> {
+        let buffer = telegram::encode_timers(start, values)?;
+
+        self.write(
+            Area::Timer,
+            0,
+            start,
+            values.len() as i32,
+            constant::WL_TIMER,
+            buffer.as_slice(),
+        )
+        .await
+    }
+
+    /// see `Client::read_address`
+    pub async fn read_address(&mut self, address: &Address) -> Result<Vec<u8>, Error> {
+        let mut buffer = vec![0u8; address.size() as usize];
+        self.read(
+            address.area(),
+            address.db_number() as i32,
+            address.start(),
+            1,
+            address.word_len(),
+            buffer.as_mut_slice(),
+        )
+        .await?;
+        Ok(buffer)
+    }
+
+    /// see `Client::write_address`
+    pub async fn write_address(&mut self, address: &Address, data: &[u8]) -> Result<(), Error> {
+        if data.len() != address.size() as usize {
+            return Err(Error::InvalidInput {
+                input: format!(
+                    "write_address: expected data size {} got {}",
+                    address.size(),
+                    data.len()
+                ),
+            });
+        }
+
+        self.write(
+            address.area(),
+            address.db_number() as i32,
+            address.start(),
+            1,
+            address.word_len(),
+            data,
+        )
+        .await
+    }
+
+    /// see `Client::read_field`
+    pub async fn read_field<F: SizedField>(&mut self, address: &Address) -> Result<F, Error> {
+        let size = telegram::field_size::<F>();
+        let (word_len, amount) = telegram::field_amount(address.area(), size)?;
+        let mut buffer = vec![0u8; size as usize];
+
+        self.read(
+            address.area(),
+            address.db_number() as i32,
+            address.byte() as i32,
+            amount,
+            word_len,
+            buffer.as_mut_slice(),
+        )
+        .await?;
+
+        telegram::decode_field(address, buffer)
+    }
+
+    /// see `Client::write_field`
+    pub async fn write_field<F: Field + ?Sized>(&mut self, field: &F) -> Result<(), Error> {
+        let bytes = field.to_bytes();
+        let (word_len, amount) = telegram::field_amount(field.area(), bytes.len() as i32)?;
+
+        self.write(
+            field.area(),
+            field.data_block(),
+            field.offset(),
+            amount,
+            word_len,
+            bytes.as_slice(),
+        )
+        .await
+    }
+
+    /// see `Client::read_multi`
+    pub async fn read_multi(&mut self, items: &mut [DataItem]) -> Result<(), Error> {
+        for group in telegram::read_groups(items, self.transport.pdu_length())? {
+            match group {
+                Group::Items(group) => {
+                    let request = telegram::read_items_request(items, &group)?;
+                    let response = self.send(request).await?;
+                    telegram::read_items_response(items, &group, response.as_slice())?;
+                }
+                Group::Single(i) => {
+                    let item = &mut items[i];
+                    let mut buffer = vec![0u8; item.size()? as usize];
+                    let result = self
+                        .read(
+                            item.area,
+                            item.db_number,
+                            item.start,
+                            item.amount,
+                            item.word_len,
+                            buffer.as_mut_slice(),
+                        )
+                        .await
+                        .map(|_| buffer);
+                    telegram::read_item_result(item, result)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// see `Client::write_multi`
+    pub async fn write_multi(&mut self, items: &mut [DataItem]) -> Result<(), Error> {
+        for group in telegram::write_groups(items, self.transport.pdu_length())? {
+            match group {
+                Group::Items(group) => {
+                    let request = telegram::write_items_request(items, &group)?;
+                    let response = self.send(request).await?;
+                    telegram::write_items_response(items, &group, response.as_slice())?;
+                }
+                Group::Single(i) => {
+                    let item = &mut items[i];
+                    let result = self
+                        .write(
+                            item.area,
+                            item.db_number,
+                            item.start,
+                            item.amount,
+                            item.word_len,
+                            item.data.as_slice(),
+                        )
+                        .await;
+                    telegram::write_item_result(item, result)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// see `Client::start`
+    pub async fn start(&mut self) -> Result<(), Error> {
+        self.control(Control::ColdStart).await
+    }
+
+    /// see `Client::restart`
+    pub async fn restart(&mut self) -> Result<(), Error> {
+        self.control(Control::WarmStart).await
+    }
+
+    /// Shut down
+    pub async fn stop(&mut self) -> Result<(), Error> {
+        self.control(Control::Stop).await
+    }
+
+    /// get plc status
+    pub async fn plc_status(&mut self) -> Result<CpuStatus, Error> {
+        let response = self.send(transport::PLC_STATUS_TELEGRAM.to_vec()).await?;
+
+        telegram::plc_status_response(response.as_slice())
+    }
+
+    pub async fn cp_info(&mut self) -> Result<CPInfo, Error> {
+        let szl = self.read_szl(0x0131, 0x000).await?;
+        CPInfo::from_szl(&szl)
+    }
+
+    /// get cpu info
+    pub async fn cpu_info(&mut self) -> Result<CpuInfo, Error> {
+        let szl = self.read_szl(0x001C, 0x000).await?;
+        CpuInfo::from_szl(&szl)
+    }
+
+    async fn read_szl(&mut self, id: u16, index: u16) -> Result<transport::S7SZL, Error> {
+        let mut szl = SzlRead::new(id, index);
+
+        while let Some(request) = szl.request() {
+            let response = self.send(request).await?;
+            szl.response(response.as_slice())?;
+        }
+        szl.finish()
+    }
+
+    async fn control(&mut self, control: Control) -> Result<(), Error> {
+        let response = self.send(control.request().to_vec()).await?;
+        control.response(response.as_slice())
+    }
+
+    //read generic area, pass result into a buffer
+    async fn read(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        start: i32,
+        amount: i32,
+        word_len: i32,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let pdu_length = self.transport.pdu_length();
+
+        if pdu_length == 0 {
+            return Err(Error::PduLength(pdu_length));
+        }
+
+        let chunks = telegram::read_chunks(area, start, amount, word_len, pdu_length)?;
+        let requests = chunks
+            .iter()
+            .map(|chunk| self.stamp(telegram::read_request(area, db_number, chunk)))
+            .collect::<Vec<Vec<u8>>>();
+
+        // the chunks are pipelined up to the negotiated parallel jobs
+        let responses = self.transport.send_all(requests.as_slice()).await?;
+
+        for (chunk, response) in chunks.iter().zip(responses.iter()) {
+            let data = telegram::read_response(response.as_slice(), chunk.size)?;
+
+            //copy response to buffer
+            buffer[chunk.offset..chunk.offset + chunk.size].copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    async fn write(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        start: i32,
+        amount: i32,
+        word_len: i32,
+        buffer: &[u8],
+    ) -> Result<(), Error> {
+        let pdu_length = self.transport.pdu_length();
+
+        let chunks = telegram::write_chunks(area, start, amount, word_len, pdu_length)?;
+        let requests = chunks
+            .iter()
+            .map(|chunk| {
+                let data = &buffer[chunk.offset..chunk.offset + chunk.size];
+                self.stamp(telegram::write_request(area, db_number, chunk, data))
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        for response in self.transport.send_all(requests.as_slice()).await? {
+            telegram::write_response(response.as_slice())?;
+        }
+        Ok(())
+    }
+
+    // sets the next PDU reference on the request
+    fn stamp(&mut self, mut request: Vec<u8>) -> Vec<u8> {
+        self.pdu_reference = self.pdu_reference.wrapping_add(1);
+        telegram::set_reference(request.as_mut_slice(), self.pdu_reference);
+        request
+    }
+
+    async fn send(&mut self, request: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let request = self.stamp(request);
+        self.transport.send(request.as_slice()).await
+    }
+}
+
+#[cfg(test)]
+struct TestTransport {
+    pdu_length: i32,
+    requests: Vec<Vec<u8>>,
+    responses: std::collections::VecDeque<Vec<u8>>,
+}
+
+#[cfg(test)]
+impl AsyncTransport for TestTransport {
+    async fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.requests.push(request.to_vec());
+        self.responses.pop_front().ok_or(Error::Send)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.pdu_length
+    }
+
+    async fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> transport::Connection {
+        transport::Connection::PG
+    }
+}
+
+#[tokio::test]
+async fn test_async_client() {
+    let read = vec![
+        3, 0, 0, 29, 2, 240, 128, 50, 3, 0, 0, 5, 0, 0, 2, 0, 8, 0, 0, 4, 1, 0xFF, 9, 0, 4, 0,
+        0x42, 0, 0x07,
+    ];
+    let write = vec![
+        3, 0, 0, 22, 2, 240, 128, 50, 3, 0, 0, 5, 0, 0, 2, 0, 1, 0, 0, 5, 1, 0xFF,
+    ];
+
+    let mut cl = AsyncClient::new(TestTransport {
+        pdu_length: 480,
+        requests: Vec::new(),
+        responses: vec![read, write].into_iter().collect(),
+    })
+    .await
+    .unwrap();
+
+    // the same telegrams as the blocking client
+    assert_eq!(vec![42, 7], cl.ct_read(3, 2).await.unwrap());
+    cl.write_bit(Area::Merker, 0, BitAddress::new(2, 1), true)
+        .await
+        .unwrap();
+
+    let requests = &cl.transport.requests;
+    assert_eq!(constant::WL_COUNTER as u8, requests[0][22]);
+    assert_eq!(Area::Counter as u8, requests[0][27]);
+    assert_eq!([0, 0, 3], requests[0][28..31]);
+    assert_eq!(constant::WL_BIT as u8, requests[1][22]);
+    assert_eq!([0, 0, 17], requests[1][28..31]);
+    assert_eq!(1, requests[1][35]);
+
+    // no responses left
+    assert!(cl.plc_status().await.is_err());
+}
@@ -0,0 +1,250 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! TCP transport implementation on tokio, uses the same `tcp::Options`
+//!
+//! a zero read or write timeout means no timeout
+
+use super::codec::Decoder;
+use super::error::Error;
+use super::tcp::Options;
+use super::telegram;
+use super::transport::{AsyncTransport, Connection, SessionInfo};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+pub struct Transport {
+    options: Options,
+    stream: TcpStream,
+    decoder: Decoder,
+    //negotiated with the PLC, all 0 until connected
+    session: SessionInfo,
+}
+
+impl Transport {
+    pub async fn connect(options: Options) -> Result<Transport, Error> {
+        let addresses =
+            match tokio::net::lookup_host((options.host(), options.checked_port()?)).await {
+                Ok(addresses) => addresses,
+                Err(e) => return Err(Error::Connect(format!("{}: {}", options.host(), e))),
+            };
+
+        let mut last_error = Error::Connect(format!("{}: no address found", options.host()));
+        let mut tcp_client = None;
+
+        // tries every address of the host until one connects
+        for address in addresses {
+            let connect = TcpStream::connect(address);
+
+            let stream = match options.connection_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, connect).await {
+                    Ok(stream) => stream.map_err(Error::from),
+                    Err(_) => Err(Error::Connect("connection timed out".to_string())),
+                },
+                None => connect.await.map_err(Error::from),
+            };
+
+            match stream {
+                Ok(stream) => {
+                    tcp_client = Some(stream);
+                    break;
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        let stream = tcp_client.ok_or(last_error)?;
+
+        Ok(Transport {
+            decoder: Decoder::new(options.pdu_size() as usize),
+            options,
+            stream,
+            session: SessionInfo::default(),
+        })
+    }
+
+    async fn iso_connect(&mut self) -> Result<(), Error> {
+        let (local_tsap, remote_tsap) = self.options.tsap();
+        let request = telegram::connection_request(local_tsap, remote_tsap);
+
+        // Sends the connection request telegram
+        let response = match self.send(request.as_slice()).await {
+            Ok(response) => response,
+            Err(e) => return Err(Error::Connect(e.to_string())),
+        };
+
+        telegram::connection_response(response.as_slice())
+    }
+
+    async fn negotiate_pdu_length(&mut self) -> Result<(), Error> {
+        let (amq_calling, amq_called) = self.options.amq();
+        let request = telegram::negotiate_request(self.options.pdu_size(), amq_calling, amq_called);
+
+        let response = self.send(request.as_slice()).await?;
+        self.session = telegram::negotiate_response(response.as_slice())?;
+        self.decoder
+            .set_pdu_length(self.session.pdu_length() as usize);
+        Ok(())
+    }
+
+    async fn exchange(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.write_request(request).await?;
+
+        let response = self.read_frame().await?;
+        telegram::check_reference(request, &response)?;
+        Ok(response)
+    }
+
+    // sends up to `max_jobs` requests before reading the responses, in any order
+    async fn pipeline(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let mut pending = telegram::Pending::new(requests, self.max_jobs() as usize);
+
+        while !pending.is_done() {
+            while let Some(request) = pending.next_request() {
+                self.write_request(request).await?;
+                pending.sent(request)?;
+            }
+            let response = self.read_frame().await?;
+            pending.received(response)?;
+        }
+        Ok(pending.into_responses())
+    }
+
+    async fn write_request(&mut self, request: &[u8]) -> Result<(), Error> {
+        timeout(self.options.write_timeout, self.stream.write_all(request)).await
+    }
+
+    // reads frames with exact reads until a whole telegram is decoded
+    async fn read_frame(&mut self) -> Result<Vec<u8>, Error> {
+        let read_timeout = self.options.read_timeout;
+        self.decoder.reset();
+
+        loop {
+            timeout(
+                read_timeout,
+                self.stream.read_exact(self.decoder.read_buffer()),
+            )
+            .await?;
+
+            if let Some(telegram) = self.decoder.advance()? {
+                return Ok(telegram);
+            }
+        }
+    }
+}
+
+// runs the io with a timeout unless it is zero
+async fn timeout<F, T>(duration: Duration, io: F) -> Result<T, Error>
+where
+    F: Future<Output = std::io::Result<T>>,
+{
+    if duration.as_nanos() == 0 {
+        return Ok(io.await?);
+    }
+
+    match tokio::time::timeout(duration, io).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(Error::IOError(ErrorKind::TimedOut)),
+    }
+}
+
+impl AsyncTransport for Transport {
+    async fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let result = self.exchange(request).await;
+
+        // a late response would be read as the response of the next request
+        if result.is_err() {
+            let _ = self.stream.shutdown().await;
+        }
+        result
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.session.pdu_length() as i32
+    }
+
+    async fn negotiate(&mut self) -> Result<(), Error> {
+        self.iso_connect().await?;
+        self.negotiate_pdu_length().await
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.options.conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.session
+    }
+
+    async fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let result = self.pipeline(requests).await;
+
+        // the responses still in flight would be read as the responses of the next requests
+        if result.is_err() {
+            let _ = self.stream.shutdown().await;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+async fn test_plc(listener: tokio::net::TcpListener) {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut request = [0u8; 512];
+
+    // connection request
+    stream.read_exact(&mut request[..22]).await.unwrap();
+    stream
+        .write_all(&telegram::test_connection_confirm())
+        .await
+        .unwrap();
+
+    // pdu negotiation, a pdu of 240 and 2 parallel jobs
+    stream.read_exact(&mut request[..25]).await.unwrap();
+    stream
+        .write_all(&telegram::test_negotiation(240, 2))
+        .await
+        .unwrap();
+
+    // both chunks of the read are sent before the first response
+    let mut first = [0u8; 31];
+    let mut second = [0u8; 31];
+    stream.read_exact(&mut first).await.unwrap();
+    stream.read_exact(&mut second).await.unwrap();
+
+    // answered out of order, after an empty frame
+    let second = telegram::test_read_response(&second, &[2; 78]);
+    let first = telegram::test_read_response(&first, &[1; 222]);
+    stream.write_all(&[3, 0, 0, 7, 2, 240, 128]).await.unwrap();
+    stream.write_all(&second).await.unwrap();
+    stream.write_all(&first).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connect() {
+    use super::async_client::AsyncClient;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let plc = tokio::spawn(test_plc(listener));
+
+    let options = Options::new("localhost", addr.port() as i32, 0, 2, Connection::PG);
+    let transport = Transport::connect(options).await.unwrap();
+    let mut cl = AsyncClient::new(transport).await.unwrap();
+    assert_eq!(SessionInfo::new(240, 2, 2), cl.session_info());
+
+    let mut buffer = [0u8; 300];
+    cl.ag_read(888, 0, 300, &mut buffer).await.unwrap();
+    assert_eq!([1; 222], buffer[..222]);
+    assert_eq!([2; 78], buffer[222..]);
+
+    plc.await.unwrap();
+}
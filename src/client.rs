@@ -2,12 +2,16 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
+use super::address::Address;
 use super::constant::{self, Area};
 use super::error::{self, Error};
+use super::field::{BitAddress, Field, SizedField};
+use super::telegram::{self, Control, Group, SzlRead};
 use super::transport::{self, Transport};
 use crate::constant::CpuStatus;
 use byteorder::{BigEndian, ByteOrder};
 use std::str;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct CpuInfo {
@@ -26,15 +30,250 @@ pub struct CPInfo {
     max_bus_rate: u16,
 }
 
+impl CpuInfo {
+    pub(crate) fn from_szl(szl: &transport::S7SZL) -> Result<CpuInfo, Error> {
+        if szl.data.len() < transport::SZL_MIN_RESPONSE {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_PDU,
+            });
+        }
+
+        let text = |range: std::ops::Range<usize>| match str::from_utf8(&szl.data[range.clone()]) {
+            Ok(s) => Ok(s.to_string()),
+            Err(e) => Err(Error::InvalidResponse {
+                bytes: szl.data[range].to_vec(),
+                reason: e.to_string(),
+            }),
+        };
+
+        Ok(CpuInfo {
+            module_type_name: text(172..204)?,
+            serial_number: text(138..162)?,
+            as_name: text(2..26)?,
+            copyright: text(104..130)?,
+            module_name: text(36..60)?,
+        })
+    }
+
+    pub fn module_type_name(&self) -> &str {
+        &self.module_type_name
+    }
+
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    pub fn as_name(&self) -> &str {
+        &self.as_name
+    }
+
+    pub fn copyright(&self) -> &str {
+        &self.copyright
+    }
+
+    pub fn module_name(&self) -> &str {
+        &self.module_name
+    }
+}
+
+impl CPInfo {
+    pub(crate) fn from_szl(szl: &transport::S7SZL) -> Result<CPInfo, Error> {
+        if szl.data.len() < 12 {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_PDU,
+            });
+        }
+
+        Ok(CPInfo {
+            max_pdu_length: BigEndian::read_u16(szl.data[2..].as_ref()),
+            max_connections: BigEndian::read_u16(szl.data[4..].as_ref()),
+            max_mpi_rate: BigEndian::read_u16(szl.data[6..].as_ref()),
+            max_bus_rate: BigEndian::read_u16(szl.data[10..].as_ref()),
+        })
+    }
+
+    pub fn max_pdu_length(&self) -> u16 {
+        self.max_pdu_length
+    }
+
+    pub fn max_connections(&self) -> u16 {
+        self.max_connections
+    }
+
+    pub fn max_mpi_rate(&self) -> u16 {
+        self.max_mpi_rate
+    }
+
+    pub fn max_bus_rate(&self) -> u16 {
+        self.max_bus_rate
+    }
+}
+
+/// a single variable of a multi variable read or write job
+///
+/// `start` is the byte offset of the variable,
+/// or the bit offset (`byte * 8 + bit`) when `word_len` is `WL_BIT`.
+/// `amount` is the number of elements of `word_len` size.
+#[derive(Debug, Clone)]
+pub struct DataItem {
+    pub(crate) area: Area,
+    pub(crate) word_len: i32,
+    pub(crate) db_number: i32,
+    pub(crate) start: i32,
+    pub(crate) amount: i32,
+    pub(crate) data: Vec<u8>,
+    pub(crate) return_code: u8,
+}
+
+impl DataItem {
+    pub fn new(area: Area, db_number: i32, start: i32, word_len: i32, amount: i32) -> DataItem {
+        DataItem {
+            area,
+            word_len,
+            db_number,
+            start,
+            amount,
+            data: Vec::new(),
+            return_code: 0,
+        }
+    }
+
+    pub fn area(&self) -> Area {
+        self.area
+    }
+
+    pub fn db_number(&self) -> i32 {
+        self.db_number
+    }
+
+    pub fn start(&self) -> i32 {
+        self.start
+    }
+
+    pub fn word_len(&self) -> i32 {
+        self.word_len
+    }
+
+    pub fn amount(&self) -> i32 {
+        self.amount
+    }
+
+    /// the data that has been read or is going to be written
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data
+    }
+
+    /// item return code as sent by the PLC, `constant::RES_ITEM_OK` on success
+    pub fn return_code(&self) -> u8 {
+        self.return_code
+    }
+
+    /// the item result, mapped to an error if the PLC rejected the item
+    pub fn result(&self) -> Result<(), Error> {
+        if self.return_code == constant::RES_ITEM_OK {
+            return Ok(());
+        }
+        Err(Error::CPU {
+            code: self.return_code as i32,
+        })
+    }
+
+    // word length, amount and word size, adjusted the same way as in `Client::read`
+    pub(crate) fn transport_params(&self) -> Result<(i32, i32, i32), Error> {
+        let word_len = match self.area {
+            Area::Counter => constant::WL_COUNTER,
+            Area::Timer => constant::WL_TIMER,
+            _ => self.word_len,
+        };
+
+        let word_size = constant::data_size_byte(word_len);
+
+        if word_size == 0 {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_DATA_SIZE,
+            });
+        }
+
+        Ok(match word_len {
+            constant::WL_BIT => (word_len, 1, 1), // Only 1 bit can be transferred at time
+            constant::WL_COUNTER | constant::WL_TIMER => (word_len, self.amount, word_size),
+            _ => (constant::WL_BYTE, self.amount * word_size, 1),
+        })
+    }
+
+    // size of the item data in bytes
+    pub(crate) fn size(&self) -> Result<i32, Error> {
+        let (_, amount, word_size) = self.transport_params()?;
+        Ok(amount * word_size)
+    }
+
+    // request item spec, 12 bytes
+    pub(crate) fn spec(&self) -> Result<Vec<u8>, Error> {
+        let (word_len, amount, _) = self.transport_params()?;
+        let mut spec = transport::READ_WRITE_TELEGRAM[19..31].to_vec();
+
+        spec[3] = word_len as u8;
+        BigEndian::write_u16(spec[4..].as_mut(), amount as u16);
+
+        if let Area::DataBausteine = self.area {
+            BigEndian::write_u16(spec[6..].as_mut(), self.db_number as u16);
+        }
+        spec[8] = self.area as u8;
+
+        let mut address = match word_len {
+            constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER => self.start,
+            _ => self.start << 3,
+        };
+
+        // Address into the PLC (only 3 bytes)
+        spec[11] = (address & 0x0FF) as u8;
+        address >>= 8;
+        spec[10] = (address & 0x0FF) as u8;
+        address >>= 8;
+        spec[9] = (address & 0x0FF) as u8;
+        Ok(spec)
+    }
+
+    // header of the item data in a write request, 4 bytes
+    pub(crate) fn data_header(&self) -> Result<Vec<u8>, Error> {
+        let (word_len, _, _) = self.transport_params()?;
+        let data_size = self.data.len() as u16;
+
+        let (transport_size, length) = match word_len {
+            constant::WL_BIT => (constant::TS_RES_BIT, data_size),
+            constant::WL_COUNTER | constant::WL_TIMER => (constant::TS_RES_OCTET, data_size),
+            _ => (constant::TS_RES_BYTE, data_size << 3),
+        };
+
+        let mut header = vec![0, transport_size as u8, 0, 0];
+        BigEndian::write_u16(header[2..].as_mut(), length);
+        Ok(header)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Client<T: Transport> {
     transport: T,
+    // PDU reference of the last request
+    pdu_reference: u16,
 }
 
 impl<T: Transport> Client<T> {
     pub fn new(mut transport: T) -> Result<Client<T>, Error> {
         transport.negotiate()?;
-        Ok(Client { transport })
+        Ok(Client {
+            transport,
+            pdu_reference: 0,
+        })
+    }
+
+    /// the PDU length and parallel jobs negotiated with the PLC
+    pub fn session_info(&self) -> transport::SessionInfo {
+        self.transport.session_info()
     }
 
     /// # Examples
@@ -43,10 +282,10 @@ impl<T: Transport> Client<T> {
     /// use std::net::{Ipv4Addr, IpAddr};
     /// use s7::{client, tcp, transport};
     /// use std::time::Duration;
-    /// use s7::field::{Bool, Field};
+    /// use s7::field::{BitAddress, Bool, Field};
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -56,9 +295,9 @@ impl<T: Transport> Client<T> {
     ///
     /// let buffer = &mut vec![0u8; Bool::size() as usize];
     /// let db = 888;
-    /// let offset = 8.4;
+    /// let offset = BitAddress::new(8, 4);
     ///
-    /// cl.ag_read(db, offset as i32, Bool::size(), buffer).unwrap();
+    /// cl.ag_read(db, offset.byte as i32, Bool::size(), buffer).unwrap();
     ///
     /// let mut  lights = Bool::new(db, offset, buffer.to_vec()).unwrap();
     /// lights.set_value(!lights.value()); // toggle the light switch
@@ -77,16 +316,16 @@ impl<T: Transport> Client<T> {
         db_number: i32,
         start: i32,
         size: i32,
-        buffer: &mut Vec<u8>,
+        buffer: &mut [u8],
     ) -> Result<(), Error> {
-        return self.read(
+        self.read(
             Area::DataBausteine,
             db_number,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
     /// # Examples
@@ -95,10 +334,10 @@ impl<T: Transport> Client<T> {
     /// use std::net::{Ipv4Addr, IpAddr};
     /// use s7::{client, tcp, transport};
     /// use std::time::Duration;
-    /// use s7::field::{Bool, Field};
+    /// use s7::field::{BitAddress, Bool, Field};
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -108,9 +347,9 @@ impl<T: Transport> Client<T> {
     ///
     /// let buffer = &mut vec![0u8; Bool::size() as usize];
     /// let db = 888;
-    /// let offset = 8.4;
+    /// let offset = BitAddress::new(8, 4);
     ///
-    /// cl.ag_read(db, offset as i32, Bool::size(), buffer).unwrap();
+    /// cl.ag_read(db, offset.byte as i32, Bool::size(), buffer).unwrap();
     ///
     /// let mut  lights = Bool::new(db, offset, buffer.to_vec()).unwrap();
     /// lights.set_value(!lights.value()); // toggle the light switch
@@ -129,16 +368,16 @@ impl<T: Transport> Client<T> {
         db_number: i32,
         start: i32,
         size: i32,
-        buffer: &mut Vec<u8>,
+        buffer: &mut [u8],
     ) -> Result<(), Error> {
-        return self.write(
+        self.write(
             Area::DataBausteine,
             db_number,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
     /// # Examples
@@ -149,7 +388,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -162,8 +401,8 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.mb_read(1, 3, buffer).unwrap();
     /// ```
-    pub fn mb_read(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.read(Area::Merker, 0, start, size, constant::WL_BYTE, buffer);
+    pub fn mb_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(Area::Merker, 0, start, size, constant::WL_BYTE, buffer)
     }
 
     /// # Examples
@@ -174,7 +413,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -187,8 +426,8 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.mb_write(1, 3, buffer).unwrap();
     /// ```
-    pub fn mb_write(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.write(Area::Merker, 0, start, size, constant::WL_BYTE, buffer);
+    pub fn mb_write(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.write(Area::Merker, 0, start, size, constant::WL_BYTE, buffer)
     }
 
     /// # Examples
@@ -199,7 +438,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -212,15 +451,15 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.eb_read(1, 3, buffer).unwrap();
     /// ```
-    pub fn eb_read(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.read(
+    pub fn eb_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(
             Area::ProcessInput,
             0,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
     /// # Examples
@@ -231,7 +470,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -244,15 +483,15 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.eb_write(1, 3, buffer).unwrap();
     /// ```
-    pub fn eb_write(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.write(
+    pub fn eb_write(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.write(
             Area::ProcessInput,
             0,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
     /// # Examples
@@ -263,7 +502,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -276,15 +515,15 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.ab_read(1, 3, buffer).unwrap();
     /// ```
-    pub fn ab_read(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.read(
+    pub fn ab_read(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read(
             Area::ProcessOutput,
             0,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
     /// # Examples
@@ -295,7 +534,7 @@ impl<T: Transport> Client<T> {
     /// use std::time::Duration;
     ///
     /// let addr = Ipv4Addr::new(127, 0, 0, 1);
-    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 5, 5, transport::Connection::PG);
+    /// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
     ///
     /// opts.read_timeout = Duration::from_secs(2);
     /// opts.write_timeout = Duration::from_secs(2);
@@ -308,143 +547,308 @@ impl<T: Transport> Client<T> {
     ///
     /// cl.ab_write(1, 3, buffer).unwrap();
     /// ```
-    pub fn ab_write(&mut self, start: i32, size: i32, buffer: &mut Vec<u8>) -> Result<(), Error> {
-        return self.write(
+    pub fn ab_write(&mut self, start: i32, size: i32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.write(
             Area::ProcessOutput,
             0,
             start,
             size,
             constant::WL_BYTE,
             buffer,
-        );
+        )
     }
 
-    //read generic area, pass result into a buffer
-    fn read(
+    /// reads a single bit of `Area::ProcessInput`, `Area::ProcessOutput`,
+    /// `Area::Merker` or `Area::DataBausteine`, `db_number` is ignored outside of data blocks
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::constant::Area;
+    /// use s7::field::BitAddress;
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let lights = cl.read_bit(Area::DataBausteine, 888, BitAddress::new(8, 4)).unwrap();
+    /// // only this bit is written, the PLC can change the rest of the byte in the meantime
+    /// cl.write_bit(Area::DataBausteine, 888, BitAddress::new(8, 4), !lights).unwrap();
+    /// ```
+    pub fn read_bit(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        address: BitAddress,
+    ) -> Result<bool, Error> {
+        let start = telegram::bit_start(area, address)?;
+        let mut buffer = [0u8; 1];
+
+        self.read(area, db_number, start, 1, constant::WL_BIT, &mut buffer)?;
+        Ok(buffer[0] & 0x01 != 0)
+    }
+
+    /// writes a single bit with the bit transport size,
+    /// the PLC sets exactly that bit and leaves the rest of the byte alone
+    pub fn write_bit(
         &mut self,
         area: Area,
         db_number: i32,
-        mut start: i32,
-        mut amount: i32,
-        mut word_len: i32,
-        buffer: &mut Vec<u8>,
+        address: BitAddress,
+        value: bool,
     ) -> Result<(), Error> {
-        // Some adjustment
-        match area {
-            Area::Counter => word_len = constant::WL_COUNTER,
-            Area::Timer => word_len = constant::WL_TIMER,
-            _ => {}
-        };
+        let start = telegram::bit_start(area, address)?;
 
-        // Calc Word size
-        let mut word_size = constant::data_size_byte(word_len);
+        self.write(area, db_number, start, 1, constant::WL_BIT, &[value as u8])
+    }
 
-        if word_size == 0 {
-            return Err(Error::Response {
-                code: error::ISO_INVALID_DATA_SIZE,
+    /// reads `amount` counters starting at counter `start`,
+    /// the BCD values are decoded to 0..999
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let counters = cl.ct_read(0, 4).unwrap();
+    /// cl.ct_write(2, &[counters[2] + 1]).unwrap();
+    /// ```
+    pub fn ct_read(&mut self, start: i32, amount: i32) -> Result<Vec<u16>, Error> {
+        let mut buffer = telegram::element_buffer(start, amount)?;
+        self.read(
+            Area::Counter,
+            0,
+            start,
+            amount,
+            constant::WL_COUNTER,
+            buffer.as_mut_slice(),
+        )?;
+
+        telegram::decode_counters(buffer.as_slice())
+    }
+
+    /// writes the counters starting at counter `start`, the values must be 0..999
+    pub fn ct_write(&mut self, start: i32, values: &[u16]) -> Result<(), Error> {
+        let buffer = telegram::encode_counters(start, values)?;
+
+        self.write(
+            Area::Counter,
+            0,
+            start,
+            values.len() as i32,
+            constant::WL_COUNTER,
+            buffer.as_slice(),
+        )
+    }
+
+    /// reads `amount` timers starting at timer `start`,
+    /// the `S5TIME` values are decoded with their time base
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use std::time::Duration;
+    /// use s7::{client, tcp, transport};
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let timers = cl.tm_read(0, 4).unwrap();
+    /// cl.tm_write(0, &[Duration::from_secs(5)]).unwrap();
+    /// ```
+    pub fn tm_read(&mut self, start: i32, amount: i32) -> Result<Vec<Duration>, Error> {
+        let mut buffer = telegram::element_buffer(start, amount)?;
+        self.read(
+            Area::Timer,
+            0,
+            start,
+            amount,
+            constant::WL_TIMER,
+            buffer.as_mut_slice(),
+        )?;
+
+        telegram::decode_timers(buffer.as_slice())
+    }
+
+    /// writes the timers starting at timer `start`, the values must be up to 9990 seconds,
+    /// the smallest time base that fits is used so the precision drops for long durations
+    pub fn tm_write(&mut self, start: i32, values: &[Duration]) -> Result<(), Error> {
+        let buffer = telegram::encode_timers(start, values)?;
+
+        self.write(
+            Area::Timer,
+            0,
+            start,
+            values.len() as i32,
+            constant::WL_TIMER,
+            buffer.as_slice(),
+        )
+    }
+
+    /// reads the value at a symbolic address
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::address::Address;
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let address: Address = "DB888.DBW8".parse().unwrap();
+    /// let bytes = cl.read_address(&address).unwrap();
+    ///
+    /// println!("{}", u16::from_be_bytes([bytes[0], bytes[1]]));
+    /// ```
+    pub fn read_address(&mut self, address: &Address) -> Result<Vec<u8>, Error> {
+        let mut buffer = vec![0u8; address.size() as usize];
+        self.read(
+            address.area(),
+            address.db_number() as i32,
+            address.start(),
+            1,
+            address.word_len(),
+            buffer.as_mut_slice(),
+        )?;
+        Ok(buffer)
+    }
+
+    /// writes the value at a symbolic address,
+    /// `data` must be exactly `Address::size` bytes
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::address::Address;
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let address: Address = "Q0.1".parse().unwrap();
+    /// cl.write_address(&address, &[1]).unwrap();
+    /// ```
+    pub fn write_address(&mut self, address: &Address, data: &[u8]) -> Result<(), Error> {
+        if data.len() != address.size() as usize {
+            return Err(Error::InvalidInput {
+                input: format!(
+                    "write_address: expected data size {} got {}",
+                    address.size(),
+                    data.len()
+                ),
             });
         }
 
-        if word_len == constant::WL_BIT {
-            amount = 1; // Only 1 bit can be transferred at time
-        } else {
-            if word_len != constant::WL_COUNTER && word_len != constant::WL_TIMER {
-                amount = amount * word_size;
-                word_size = 1;
-                word_len = constant::WL_BYTE;
-            }
-        }
+        self.write(
+            address.area(),
+            address.db_number() as i32,
+            address.start(),
+            1,
+            address.word_len(),
+            data,
+        )
+    }
 
+    /// reads a field at a symbolic address in any area,
+    /// the width of the address is ignored, the field decides how many bytes are read
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::field::Float;
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let mut cooling = cl.read_field::<Float>(&"MD12".parse().unwrap()).unwrap();
+    /// cooling.set_value(121.3);
+    /// cl.write_field(&cooling).unwrap();
+    /// ```
+    pub fn read_field<F: SizedField>(&mut self, address: &Address) -> Result<F, Error> {
+        let size = telegram::field_size::<F>();
+        let (word_len, amount) = telegram::field_amount(address.area(), size)?;
+        let mut buffer = vec![0u8; size as usize];
+
+        self.read(
+            address.area(),
+            address.db_number() as i32,
+            address.byte() as i32,
+            amount,
+            word_len,
+            buffer.as_mut_slice(),
+        )?;
+
+        telegram::decode_field(address, buffer)
+    }
+
+    /// writes a field to its area, data block and offset
+    pub fn write_field<F: Field + ?Sized>(&mut self, field: &F) -> Result<(), Error> {
+        let bytes = field.to_bytes();
+        let (word_len, amount) = telegram::field_amount(field.area(), bytes.len() as i32)?;
+
+        self.write(
+            field.area(),
+            field.data_block(),
+            field.offset(),
+            amount,
+            word_len,
+            bytes.as_slice(),
+        )
+    }
+
+    //read generic area, pass result into a buffer
+    fn read(
+        &mut self,
+        area: Area,
+        db_number: i32,
+        start: i32,
+        amount: i32,
+        word_len: i32,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
         let pdu_length = self.transport.pdu_length();
 
         if pdu_length == 0 {
             return Err(Error::PduLength(pdu_length));
         }
 
-        let max_elements = (pdu_length - 18) / word_size; // 18 = Reply telegram header //lth note here
+        let chunks = telegram::read_chunks(area, start, amount, word_len, pdu_length)?;
+        let requests = chunks
+            .iter()
+            .map(|chunk| self.stamp(telegram::read_request(area, db_number, chunk)))
+            .collect::<Vec<Vec<u8>>>();
 
-        let mut tot_elements = amount;
-        let db_bytes = (db_number as u16).to_be_bytes();
-        let mut offset = 0;
+        // the chunks are pipelined up to the negotiated parallel jobs
+        let responses = self.transport.send_all(requests.as_slice())?;
 
-        while tot_elements > 0 {
-            let mut num_elements = tot_elements;
+        for (chunk, response) in chunks.iter().zip(responses.iter()) {
+            let data = telegram::read_response(response.as_slice(), chunk.size)?;
 
-            if num_elements > max_elements {
-                num_elements = max_elements;
-            }
-
-            let size_requested = num_elements * word_size;
-            // Setup the telegram
-            let mut request =
-                transport::READ_WRITE_TELEGRAM[..constant::SIZE_HEADER_READ as usize].to_vec();
-
-            // Set DB Number
-            request[25] = db_bytes[0];
-            request[26] = db_bytes[1];
-
-            // Set Area
-            request[27] = area as u8;
-            // match area {
-            //     Area::DataBausteine => request[27] = area as u8,
-            //     _ => {}
-            // }
-
-            // Adjusts Start and word length
-            let mut address = match word_len {
-                constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER => {
-                    request[22] = word_len as u8;
-                    start
-                }
-                _ => start << 3,
-            };
-
-            // Num elements
-            let num_elements_bytes = (num_elements as u16).to_be_bytes();
-            request[23] = num_elements_bytes[0];
-            request[24] = num_elements_bytes[1];
-
-            // Address into the PLC (only 3 bytes)
-            request[30] = (address & 0x0FF) as u8;
-            address = address >> 8;
-            request[29] = (address & 0x0FF) as u8;
-            address = address >> 8;
-            request[28] = (address & 0x0FF) as u8;
-
-            let result = self.transport.send(request.as_slice());
-
-            match result {
-                Ok(response) => {
-                    if response.len() < 25 {
-                        return Err(Error::Response {
-                            code: error::ISO_INVALID_DATA_SIZE,
-                        });
-                    }
-
-                    if response[21] != 0xFF {
-                        return Err(Error::CPU {
-                            code: response[21] as i32,
-                        });
-                    }
-                    let (mut i, end): (usize, usize) = (25, 25 + (size_requested as usize));
-
-                    //copy response to buffer
-                    for k in offset..size_requested {
-                        if i == end {
-                            break;
-                        }
-                        buffer[k as usize] = response[i];
-                        i += 1;
-                    }
-                    offset += size_requested;
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-
-            tot_elements -= num_elements;
-            start += num_elements * word_size
+            //copy response to buffer
+            buffer[chunk.offset..chunk.offset + chunk.size].copy_from_slice(data);
         }
         Ok(())
     }
@@ -453,131 +857,158 @@ impl<T: Transport> Client<T> {
         &mut self,
         area: Area,
         db_number: i32,
-        mut start: i32,
-        mut amount: i32,
-        mut word_len: i32,
-        buffer: &mut Vec<u8>,
+        start: i32,
+        amount: i32,
+        word_len: i32,
+        buffer: &[u8],
     ) -> Result<(), Error> {
-        // Some adjustment
-        word_len = match area {
-            Area::Counter => constant::WL_COUNTER,
-            Area::Timer => constant::WL_TIMER,
-            _ => word_len,
-        };
-
-        // Calc Word size
-        let mut word_size = constant::data_size_byte(word_len);
+        let pdu_length = self.transport.pdu_length();
 
-        if word_size == 0 {
-            return Err(Error::Response {
-                code: error::ISO_INVALID_DATA_SIZE,
-            });
+        let chunks = telegram::write_chunks(area, start, amount, word_len, pdu_length)?;
+        let requests = chunks
+            .iter()
+            .map(|chunk| {
+                let data = &buffer[chunk.offset..chunk.offset + chunk.size];
+                self.stamp(telegram::write_request(area, db_number, chunk, data))
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        for response in self.transport.send_all(requests.as_slice())? {
+            telegram::write_response(response.as_slice())?;
         }
+        Ok(())
+    }
 
-        if word_len == constant::WL_BIT {
-            amount = 1; // Only 1 bit can be transferred at time
-        } else {
-            if word_len != constant::WL_COUNTER && word_len != constant::WL_TIMER {
-                amount = amount * word_size;
-                word_size = 1;
-                word_len = constant::WL_BYTE;
-            }
-        }
+    // sets the next PDU reference on the request
+    fn stamp(&mut self, mut request: Vec<u8>) -> Vec<u8> {
+        self.pdu_reference = self.pdu_reference.wrapping_add(1);
+        telegram::set_reference(request.as_mut_slice(), self.pdu_reference);
+        request
+    }
 
-        let mut offset: i32 = 0;
-        let pdu_length = self.transport.pdu_length();
-        let max_elements = (pdu_length - 35) / word_size; // 35 = Reply telegram header
-        let mut tot_elements = amount;
+    fn send(&mut self, request: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let request = self.stamp(request);
+        self.transport.send(request.as_slice())
+    }
+}
 
-        while tot_elements > 0 {
-            let mut num_elements = tot_elements;
-            if num_elements > max_elements {
-                num_elements = max_elements;
-            }
-            let data_size = num_elements * word_size;
-            let iso_size = constant::SIZE_HEADER_WRITE + data_size;
-
-            // Setup the telegram
-            let mut request_data = transport::READ_WRITE_TELEGRAM.to_vec();
-            // Whole telegram Size
-            BigEndian::write_u16(request_data[2..].as_mut(), iso_size as u16);
-            // Data length
-            let mut length = data_size + 4;
-            BigEndian::write_u16(request_data[15..].as_mut(), length as u16);
-            // Function
-            request_data[17] = 0x05;
-            // Set DB Number
-            request_data[27] = area as u8;
-
-            match area {
-                Area::DataBausteine => {
-                    BigEndian::write_u16(request_data[25..].as_mut(), db_number as u16)
-                }
-                _ => {}
-            }
-            // Adjusts start and word length
-            let mut address = match word_len {
-                constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER => {
-                    length = data_size;
-                    request_data[22] = word_len as u8;
-                    start
-                }
-                _ => {
-                    length = data_size << 3;
-                    start << 3
+impl<T: Transport> Client<T> {
+    /// Reads many variables, from any area, with as few requests as possible.
+    /// The items are packed into requests within the negotiated pdu length and
+    /// the `constant::MAX_VARS` limit. An item too big for a single pdu is read on its own.
+    /// A rejected item doesn't fail the whole job, check `DataItem::result`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::client::DataItem;
+    /// use s7::constant::{self, Area};
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let mut items = vec![
+    ///     DataItem::new(Area::DataBausteine, 888, 12, constant::WL_REAL, 1),
+    ///     DataItem::new(Area::Merker, 0, 20, constant::WL_WORD, 1),
+    ///     DataItem::new(Area::ProcessInput, 0, 1, constant::WL_BIT, 1),
+    /// ];
+    ///
+    /// cl.read_multi(&mut items).unwrap();
+    ///
+    /// for item in items.iter() {
+    ///     match item.result() {
+    ///         Ok(()) => println!("{:?}", item.data()),
+    ///         Err(e) => println!("{}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn read_multi(&mut self, items: &mut [DataItem]) -> Result<(), Error> {
+        for group in telegram::read_groups(items, self.transport.pdu_length())? {
+            match group {
+                Group::Items(group) => {
+                    let request = telegram::read_items_request(items, &group)?;
+                    let response = self.send(request)?;
+                    telegram::read_items_response(items, &group, response.as_slice())?;
                 }
-            };
-
-            // Num elements
-            BigEndian::write_u16(request_data[23..].as_mut(), num_elements as u16);
-            // address into the PLC
-            request_data[30] = (address & 0x0FF) as u8;
-            address = address >> 8;
-            request_data[29] = (address & 0x0FF) as u8;
-            address = address >> 8;
-            request_data[28] = (address & 0x0FF) as u8;
-
-            // Transport Size
-            match word_len {
-                constant::WL_BIT => request_data[32] = constant::TS_RES_BIT as u8,
-                constant::WL_COUNTER | constant::WL_TIMER => {
-                    request_data[32] = constant::TS_RES_OCTET as u8
+                Group::Single(i) => {
+                    let item = &mut items[i];
+                    let mut buffer = vec![0u8; item.size()? as usize];
+                    let result = self
+                        .read(
+                            item.area,
+                            item.db_number,
+                            item.start,
+                            item.amount,
+                            item.word_len,
+                            buffer.as_mut_slice(),
+                        )
+                        .map(|_| buffer);
+                    telegram::read_item_result(item, result)?;
                 }
-                _ => request_data[32] = constant::TS_RES_BYTE as u8, // byte/word/dword etc.
             }
-            // length
-            BigEndian::write_u16(request_data[33..].as_mut(), length as u16);
-
-            //expand values into array
-            request_data.splice(
-                35..35,
-                buffer[offset as usize..offset as usize + data_size as usize].to_vec(),
-            );
-
-            let result = self.transport.send(request_data.as_mut_slice());
-
-            match result {
-                Ok(response) => {
-                    if response.len() != 22 {
-                        return Err(Error::Response {
-                            code: error::ISO_INVALID_PDU,
-                        });
-                    }
-
-                    if response[21] != 0xFF {
-                        return Err(Error::CPU {
-                            code: response[21] as i32,
-                        });
-                    }
+        }
+        Ok(())
+    }
+
+    /// Writes many variables, from any area, with as few requests as possible.
+    /// The data of every item must be set with `DataItem::set_data` and match the item size.
+    /// The items are packed into requests within the negotiated pdu length and
+    /// the `constant::MAX_VARS` limit. An item too big for a single pdu is written on its own.
+    /// A rejected item doesn't fail the whole job, check `DataItem::result`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{client, tcp, transport};
+    /// use s7::client::DataItem;
+    /// use s7::constant::{self, Area};
+    ///
+    /// let addr = Ipv4Addr::new(127, 0, 0, 1);
+    /// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+    /// let t = tcp::Transport::connect(opts).unwrap();
+    /// let mut cl = client::Client::new(t).unwrap();
+    ///
+    /// let mut cooling = DataItem::new(Area::DataBausteine, 888, 12, constant::WL_REAL, 1);
+    /// cooling.set_data(121.3f32.to_be_bytes().to_vec());
+    ///
+    /// let mut lights = DataItem::new(Area::ProcessOutput, 0, 8 * 2 + 1, constant::WL_BIT, 1);
+    /// lights.set_data(vec![1]);
+    ///
+    /// let mut items = vec![cooling, lights];
+    /// cl.write_multi(&mut items).unwrap();
+    ///
+    /// for item in items.iter() {
+    ///     if let Err(e) = item.result() {
+    ///         println!("{}", e);
+    ///     }
+    /// }
+    /// ```
+    pub fn write_multi(&mut self, items: &mut [DataItem]) -> Result<(), Error> {
+        for group in telegram::write_groups(items, self.transport.pdu_length())? {
+            match group {
+                Group::Items(group) => {
+                    let request = telegram::write_items_request(items, &group)?;
+                    let response = self.send(request)?;
+                    telegram::write_items_response(items, &group, response.as_slice())?;
                 }
-                Err(e) => {
-                    return Err(e);
+                Group::Single(i) => {
+                    let item = &mut items[i];
+                    let result = self.write(
+                        item.area,
+                        item.db_number,
+                        item.start,
+                        item.amount,
+                        item.word_len,
+                        item.data.as_slice(),
+                    );
+                    telegram::write_item_result(item, result)?;
                 }
             }
-
-            offset += data_size;
-            tot_elements -= num_elements;
-            start += num_elements * word_size;
         }
         Ok(())
     }
@@ -586,234 +1017,404 @@ impl<T: Transport> Client<T> {
 impl<T: Transport> Client<T> {
     /// Starting the CPU from power off,Current configuration is discarded and program processing begins again with the initial values.
     pub fn start(&mut self) -> Result<(), Error> {
-        self.cold_warm_start_stop(
-            transport::COLD_START_TELEGRAM.as_ref(),
-            transport::PDU_START,
-            error::CLI_CANNOT_START_PLC,
-            transport::PDU_ALREADY_STARTED,
-            error::CLI_ALREADY_RUN,
-        )
+        self.control(Control::ColdStart)
     }
 
     /// Restarting the CPU without turning the power off, Program processing starts once again where Retentive data is retained.
     pub fn restart(&mut self) -> Result<(), Error> {
-        self.cold_warm_start_stop(
-            transport::WARM_START_TELEGRAM.as_ref(),
-            transport::PDU_START,
-            error::CLI_CANNOT_START_PLC,
-            transport::PDU_ALREADY_STARTED,
-            error::CLI_ALREADY_RUN,
-        )
+        self.control(Control::WarmStart)
     }
 
     /// Shut down
     pub fn stop(&mut self) -> Result<(), Error> {
-        self.cold_warm_start_stop(
-            transport::STOP_TELEGRAM.as_ref(),
-            transport::PDU_STOP,
-            error::CLI_CANNOT_STOP_PLC,
-            transport::PDU_ALREADY_STOPPED,
-            error::CLI_ALREADY_STOP,
-        )
+        self.control(Control::Stop)
     }
 
     /// get plc status
     pub fn plc_status(&mut self) -> Result<CpuStatus, Error> {
-        let response = self
-            .transport
-            .send(transport::PLC_STATUS_TELEGRAM.as_ref())?;
-
-        if response.len() < transport::PLC_STATUS_MIN_RESPONSE {
-            return Err(Error::Response {
-                code: error::ISO_INVALID_PDU,
-            });
-        }
-
-        let result = BigEndian::read_u16(response[27..29].as_ref());
-
-        if result != 0 {
-            return Err(Error::CPU {
-                code: result as i32,
-            });
-        }
+        let response = self.send(transport::PLC_STATUS_TELEGRAM.to_vec())?;
 
-        CpuStatus::from_u8(response[44])
+        telegram::plc_status_response(response.as_slice())
     }
 
     pub fn cp_info(&mut self) -> Result<CPInfo, Error> {
         let szl = self.read_szl(0x0131, 0x000)?;
-
-        Ok(CPInfo {
-            max_pdu_length: BigEndian::read_u16(szl.data[2..].as_ref()),
-            max_connections: BigEndian::read_u16(szl.data[4..].as_ref()),
-            max_mpi_rate: BigEndian::read_u16(szl.data[6..].as_ref()),
-            max_bus_rate: BigEndian::read_u16(szl.data[10..].as_ref()),
-        })
+        CPInfo::from_szl(&szl)
     }
 
     /// get cpu info
     pub fn cpu_info(&mut self) -> Result<CpuInfo, Error> {
         let szl = self.read_szl(0x001C, 0x000)?;
-
-        if szl.data.len() < transport::SZL_MIN_RESPONSE {
-            return Err(Error::Response {
-                code: error::ISO_INVALID_PDU,
-            });
-        }
-
-        let module_type_name = match str::from_utf8(szl.data[172..204].as_ref()) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(Error::InvalidResponse {
-                    bytes: szl.data[172..204].to_vec(),
-                    reason: e.to_string(),
-                })
-            }
-        };
-
-        let serial_number = match str::from_utf8(szl.data[138..162].as_ref()) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(Error::InvalidResponse {
-                    bytes: szl.data[138..162].to_vec(),
-                    reason: e.to_string(),
-                })
-            }
-        };
-
-        let as_name = match str::from_utf8(szl.data[2..26].as_ref()) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(Error::InvalidResponse {
-                    bytes: szl.data[2..26].to_vec(),
-                    reason: e.to_string(),
-                })
-            }
-        };
-
-        let copyright = match str::from_utf8(szl.data[104..130].as_ref()) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(Error::InvalidResponse {
-                    bytes: szl.data[104..130].to_vec(),
-                    reason: e.to_string(),
-                })
-            }
-        };
-
-        let module_name = match str::from_utf8(szl.data[36..60].as_ref()) {
-            Ok(s) => s,
-            Err(e) => {
-                return Err(Error::InvalidResponse {
-                    bytes: szl.data[36..60].to_vec(),
-                    reason: e.to_string(),
-                })
-            }
-        };
-
-        Ok(CpuInfo {
-            module_type_name: module_type_name.to_string(),
-            serial_number: serial_number.to_string(),
-            as_name: as_name.to_string(),
-            copyright: copyright.to_string(),
-            module_name: module_name.to_string(),
-        })
+        CpuInfo::from_szl(&szl)
     }
 
     fn read_szl(&mut self, id: u16, index: u16) -> Result<transport::S7SZL, Error> {
-        let data_szl = 0;
-        let mut offset = 0;
-        let seq_out: u16 = 0x0000;
-
-        let mut s7_szlfirst = transport::SZL_FIRST_TELEGRAM.to_vec();
+        let mut szl = SzlRead::new(id, index);
 
-        BigEndian::write_u16(s7_szlfirst[11..].as_mut(), seq_out + 1);
-        BigEndian::write_u16(s7_szlfirst[29..].as_mut(), id);
-        BigEndian::write_u16(s7_szlfirst[31..].as_mut(), index);
+        while let Some(request) = szl.request() {
+            let response = self.send(request)?;
+            szl.response(response.as_slice())?;
+        }
+        szl.finish()
+    }
 
-        let mut res = self.transport.send(s7_szlfirst.as_ref())?;
+    fn control(&mut self, control: Control) -> Result<(), Error> {
+        let response = self.send(control.request().to_vec())?;
+        control.response(response.as_slice())
+    }
+}
 
-        let validate = |res: &[u8], size: usize| -> Result<(), Error> {
-            if res.len() < transport::MIN_SZL_FIRST_TELEGRAM + size {
-                return Err(Error::Response {
-                    code: error::ISO_INVALID_PDU,
-                });
-            }
+#[cfg(test)]
+struct TestTransport {
+    pdu_length: i32,
+    requests: Vec<Vec<u8>>,
+    responses: std::collections::VecDeque<Vec<u8>>,
+}
 
-            if BigEndian::read_u16(res[27..].as_ref()) != 0 && res[29] != 0xFF {
-                return Err(Error::CPU {
-                    code: error::CLI_INVALID_PLC_ANSWER,
-                });
-            }
-            Ok(())
-        };
+#[cfg(test)]
+impl Transport for TestTransport {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.requests.push(request.to_vec());
+        self.responses.pop_front().ok_or(Error::Send)
+    }
 
-        validate(res.as_ref(), 0)?;
+    fn pdu_length(&self) -> i32 {
+        self.pdu_length
+    }
 
-        // Skips extra params (ID, Index ...)
-        let mut data_szl = BigEndian::read_u16(res[31..].as_ref()) - 8;
+    fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 
-        validate(res.as_ref(), data_szl as usize)?;
+    fn connection_type(&self) -> transport::Connection {
+        transport::Connection::PG
+    }
+}
 
-        let mut done = res[26] == 0x00;
-        // Slice sequence
-        let mut seq_in: u8 = res[24];
-        let header = transport::SZLHeader {
-            length_header: BigEndian::read_u16(res[37..].as_ref()) * 2,
-            number_of_data_record: BigEndian::read_u16(res[39..].as_ref()),
-        };
+#[cfg(test)]
+fn test_client(pdu_length: i32, responses: Vec<Vec<u8>>) -> Client<TestTransport> {
+    Client::new(TestTransport {
+        pdu_length,
+        requests: Vec::new(),
+        responses: responses.into_iter().collect(),
+    })
+    .unwrap()
+}
 
-        let len = (offset + data_szl) as usize;
-        let mut data = vec![0u8; len];
+// builds a multi read/write ack response with the given data section
+#[cfg(test)]
+fn test_response(function: u8, items_count: u8, data: &[u8]) -> Vec<u8> {
+    let mut response = vec![3, 0, 0, 0, 2, 240, 128, 50, 3, 0, 0, 5, 0, 0, 2, 0, 0, 0, 0];
+    response.push(function);
+    response.push(items_count);
+    response.extend_from_slice(data);
+    let len = response.len() as u16;
+    BigEndian::write_u16(response[2..].as_mut(), len);
+    BigEndian::write_u16(response[15..].as_mut(), data.len() as u16);
+    response
+}
 
-        data[offset as usize..len].copy_from_slice(res[41..41 + data_szl as usize].as_ref());
+#[test]
+fn test_read_multi() {
+    let data = [
+        // REAL 53.5, transport size byte, length in bits
+        0xFF, 4, 0, 32, 66, 86, 0, 0, //
+        // address out of range
+        5, 0, 0, 0, //
+        // bit, padded
+        0xFF, 3, 0, 1, 1, 0, //
+        // counter, length in bytes
+        0xFF, 9, 0, 2, 0, 42,
+    ];
+    let mut cl = test_client(480, vec![test_response(4, 4, &data)]);
+
+    let mut items = vec![
+        DataItem::new(Area::DataBausteine, 888, 8, constant::WL_REAL, 1),
+        DataItem::new(Area::Merker, 0, 2000, constant::WL_WORD, 1),
+        DataItem::new(Area::ProcessInput, 0, 8 * 2 + 3, constant::WL_BIT, 1),
+        DataItem::new(Area::Counter, 0, 3, constant::WL_COUNTER, 1),
+    ];
+    cl.read_multi(&mut items).unwrap();
+
+    assert_eq!(vec![66, 86, 0, 0], items[0].data());
+    assert!(items[1].result().is_err());
+    assert!(items[1].data().is_empty());
+    assert_eq!(vec![1], items[2].data());
+    assert_eq!(vec![0, 42], items[3].data());
+
+    let requests = &cl.transport.requests;
+    assert_eq!(1, requests.len());
+
+    let request = &requests[0];
+    assert_eq!(19 + 4 * 12, request.len());
+    assert_eq!(request.len() as u16, BigEndian::read_u16(&request[2..]));
+    assert_eq!(2 + 4 * 12, BigEndian::read_u16(&request[13..]));
+    assert_eq!(4, request[18]);
+    // db 888, area, offset 8 in bits
+    assert_eq!(
+        vec![18, 10, 16, 2, 0, 4, 3, 120, 0x84, 0, 0, 64],
+        request[19..31].to_vec()
+    );
+    // bit 2.3, no shift
+    assert_eq!(
+        vec![1, 0, 1, 0, 0, 0x81, 0, 0, 19],
+        request[46..55].to_vec()
+    );
+}
 
-        let mut szl = transport::S7SZL { header, data };
-        offset += data_szl;
+#[test]
+fn test_read_multi_split() {
+    // room for 2 item specs per request
+    let data = [0xFF, 4, 0, 16, 1, 2];
+    let mut cl = test_client(
+        40,
+        vec![
+            test_response(4, 2, &[&data[..], &data[..]].concat()),
+            test_response(4, 1, &data),
+        ],
+    );
+
+    let mut items = vec![DataItem::new(Area::Merker, 0, 0, constant::WL_WORD, 1); 3];
+    cl.read_multi(&mut items).unwrap();
+
+    assert_eq!(2, cl.transport.requests.len());
+    assert_eq!(2, cl.transport.requests[0][18]);
+    assert_eq!(1, cl.transport.requests[1][18]);
+
+    for item in items.iter() {
+        assert!(item.result().is_ok());
+        assert_eq!(vec![1, 2], item.data());
+    }
+}
 
-        let mut s7szlnext: Vec<u8> = transport::SZL_NEXT_TELEGRAM.to_vec();
+#[test]
+fn test_write_multi() {
+    let mut cl = test_client(480, vec![test_response(5, 3, &[0xFF, 5, 0xFF])]);
+
+    let mut real = DataItem::new(Area::DataBausteine, 888, 8, constant::WL_REAL, 1);
+    real.set_data(vec![66, 86, 0, 0]);
+    let mut byte = DataItem::new(Area::Merker, 0, 2000, constant::WL_BYTE, 1);
+    byte.set_data(vec![7]);
+    let mut bit = DataItem::new(Area::ProcessOutput, 0, 8 * 2 + 3, constant::WL_BIT, 1);
+    bit.set_data(vec![1]);
+
+    let mut items = vec![real, byte, bit];
+    cl.write_multi(&mut items).unwrap();
+
+    assert!(items[0].result().is_ok());
+    assert!(items[1].result().is_err());
+    assert!(items[2].result().is_ok());
+
+    let request = &cl.transport.requests[0];
+    let data_offset = 19 + 3 * 12;
+    assert_eq!(5, request[17]);
+    assert_eq!(3, request[18]);
+    assert_eq!(request.len() as u16, BigEndian::read_u16(&request[2..]));
+    assert_eq!(
+        (request.len() - data_offset) as u16,
+        BigEndian::read_u16(&request[15..])
+    );
+    assert_eq!(
+        vec![
+            0, 4, 0, 32, 66, 86, 0, 0, // real
+            0, 4, 0, 8, 7, 0, // byte, padded
+            0, 3, 0, 1, 1, // bit, last item is not padded
+        ],
+        request[data_offset..].to_vec()
+    );
+}
 
-        while !done {
-            BigEndian::write_u16(s7_szlfirst[11..].as_mut(), seq_out + 1);
-            s7szlnext[24] = seq_in;
+#[test]
+fn test_write_multi_size_mismatch() {
+    let mut cl = test_client(480, vec![]);
 
-            res = self.transport.send(s7szlnext.as_ref())?;
+    let mut item = DataItem::new(Area::Merker, 0, 0, constant::WL_DWORD, 1);
+    item.set_data(vec![1, 2]);
 
-            validate(res.as_ref(), 0)?;
+    assert!(cl.write_multi(&mut [item]).is_err());
+    assert!(cl.transport.requests.is_empty());
+}
 
-            data_szl = BigEndian::read_u16(res[31..].as_ref());
-            done = res[26] == 0x00;
-            seq_in = res[24];
+#[test]
+fn test_read_write_address() {
+    let mut cl = test_client(
+        480,
+        vec![
+            test_response(4, 1, &[0xFF, 3, 0, 1, 1]),
+            test_response(5, 1, &[0xFF]),
+        ],
+    );
+
+    let address: Address = "DB10.DBX8.4".parse().unwrap();
+    assert_eq!(vec![1], cl.read_address(&address).unwrap());
+
+    let address: Address = "MW20".parse().unwrap();
+    assert!(cl.write_address(&address, &[1]).is_err());
+    cl.write_address(&address, &[1, 2]).unwrap();
+
+    let read = &cl.transport.requests[0];
+    // bit transport size, 1 element, db 10, bit offset 68
+    assert_eq!(vec![1, 0, 1, 0, 10, 0x84, 0, 0, 68], read[22..31].to_vec());
+
+    let write = &cl.transport.requests[1];
+    // byte transport size, 2 elements, merker, offset 20 in bits
+    assert_eq!(vec![2, 0, 2, 0, 0, 0x83, 0, 0, 160], write[22..31].to_vec());
+    assert_eq!(vec![1, 2], write[35..].to_vec());
+}
 
-            szl.data = vec![0u8; len];
-            offset += data_szl;
-            szl.header.length_header += szl.header.length_header;
-        }
-        Ok(szl)
-    }
+#[test]
+fn test_read_write_field() {
+    use crate::field::{Bool, Float, Word};
+
+    let mut cl = test_client(
+        480,
+        vec![
+            test_response(4, 1, &[0xFF, 4, 0, 32, 66, 86, 0, 0]),
+            test_response(4, 1, &[0xFF, 4, 0, 8, 0b0001_0001]),
+            test_response(4, 1, &[0xFF, 9, 0, 2, 0, 42]),
+            test_response(5, 1, &[0xFF]),
+        ],
+    );
+
+    let mut cooling: Float = cl.read_field(&"MD12".parse().unwrap()).unwrap();
+    assert_eq!(53.5, cooling.value());
+    assert_eq!(Area::Merker, cooling.area());
+    assert_eq!(12, cooling.offset());
+
+    let lights: Bool = cl.read_field(&"DB888.DBX8.4".parse().unwrap()).unwrap();
+    assert!(lights.value());
+    assert_eq!(BitAddress::new(8, 4), lights.bit_address());
+
+    let counter: Word = cl.read_field(&"C3".parse().unwrap()).unwrap();
+    assert_eq!(42, counter.value());
+    // a single byte is half a timer
+    assert!(cl.read_field::<Bool>(&"T1".parse().unwrap()).is_err());
+
+    cooling.set_value(-1.5);
+    cl.write_field(&cooling).unwrap();
+
+    let requests = &cl.transport.requests;
+    // byte transport size, 4 bytes, merker, offset 12 in bits
+    assert_eq!(
+        vec![2, 0, 4, 0, 0, 0x83, 0, 0, 96],
+        requests[0][22..31].to_vec()
+    );
+    // the whole byte of the bool is read
+    assert_eq!(
+        vec![2, 0, 1, 3, 0x78, 0x84, 0, 0, 64],
+        requests[1][22..31].to_vec()
+    );
+    // 1 counter
+    assert_eq!(
+        vec![0x1C, 0, 1, 0, 0, 0x1C, 0, 0, 3],
+        requests[2][22..31].to_vec()
+    );
+    assert_eq!(vec![191, 192, 0, 0], requests[3][35..].to_vec());
+    assert_eq!(vec![0, 0, 0x83, 0, 0, 96], requests[3][25..31].to_vec());
+}
 
-    fn cold_warm_start_stop(
-        &mut self,
-        req: &[u8],
-        start_cmp: u8,
-        start: i32,
-        already_cmp: u8,
-        already: i32,
-    ) -> Result<(), Error> {
-        let response = self.transport.send(req)?;
+#[test]
+fn test_counters_timers() {
+    let mut cl = test_client(
+        480,
+        vec![
+            test_response(4, 1, &[0xFF, 9, 0, 4, 0x01, 0x23, 0x09, 0x99]),
+            test_response(5, 1, &[0xFF]),
+            test_response(4, 1, &[0xFF, 9, 0, 4, 0x21, 0x27, 0x01, 0x0A]),
+            test_response(5, 1, &[0xFF]),
+        ],
+    );
+
+    assert_eq!(vec![123, 999], cl.ct_read(3, 2).unwrap());
+    assert!(cl.ct_write(3, &[1000]).is_err());
+    cl.ct_write(3, &[42]).unwrap();
+
+    // the second timer has an invalid BCD digit
+    assert!(cl.tm_read(1, 2).is_err());
+    assert!(cl.tm_write(1, &[Duration::from_secs(10_000)]).is_err());
+    cl.tm_write(1, &[Duration::from_secs(127)]).unwrap();
+
+    let requests = &cl.transport.requests;
+    // counter transport size, 2 counters, counter area, counter number 3
+    assert_eq!(
+        vec![0x1C, 0, 2, 0, 0, 0x1C, 0, 0, 3],
+        requests[0][22..31].to_vec()
+    );
+    assert_eq!(vec![0x00, 0x42], requests[1][35..].to_vec());
+    assert_eq!(
+        vec![0x1D, 0, 2, 0, 0, 0x1D, 0, 0, 1],
+        requests[2][22..31].to_vec()
+    );
+    // 127s in the 1s time base
+    assert_eq!(vec![0x21, 0x27], requests[3][35..].to_vec());
+}
 
-        if response.len() < transport::TELEGRAM_MIN_RESPONSE {
-            return Err(Error::Response {
-                code: error::ISO_INVALID_PDU,
-            });
-        }
+#[test]
+fn test_read_write_bit() {
+    let mut cl = test_client(
+        480,
+        vec![
+            test_response(4, 1, &[0xFF, 3, 0, 1, 1]),
+            test_response(5, 1, &[0xFF]),
+        ],
+    );
+
+    assert!(cl
+        .read_bit(Area::ProcessInput, 0, BitAddress::new(2, 3))
+        .unwrap());
+    cl.write_bit(Area::DataBausteine, 888, BitAddress::new(8, 4), true)
+        .unwrap();
+
+    assert!(cl.read_bit(Area::Merker, 0, BitAddress::new(2, 8)).is_err());
+    assert!(cl
+        .write_bit(Area::Counter, 0, BitAddress::new(2, 0), true)
+        .is_err());
+    // the byte offset doesn't fit the 3 bytes bit address
+    assert!(cl
+        .write_bit(Area::Merker, 0, BitAddress::new(0x20_0000, 1), true)
+        .is_err());
+    assert!(cl
+        .read_bit(Area::Merker, 0, BitAddress::new(0x2000_0000, 0))
+        .is_err());
+
+    let requests = &cl.transport.requests;
+    assert_eq!(2, requests.len());
+    // bit transport size, 1 element, inputs, bit offset 19
+    assert_eq!(
+        vec![1, 0, 1, 0, 0, 0x81, 0, 0, 19],
+        requests[0][22..31].to_vec()
+    );
+    // bit transport size, 1 element, db 888, bit offset 68
+    assert_eq!(
+        vec![1, 0, 1, 3, 0x78, 0x84, 0, 0, 68],
+        requests[1][22..31].to_vec()
+    );
+    // bit data transport size with a length of 1 bit
+    assert_eq!(vec![0, 3, 0, 1, 1], requests[1][31..].to_vec());
+}
 
-        if response[17] != start_cmp {
-            return Err(Error::Response { code: start });
-        }
-        if response[18] == already_cmp {
-            return Err(Error::Response { code: already });
-        }
-        Ok(())
-    }
+#[test]
+fn test_pdu_reference() {
+    let mut cl = test_client(
+        240,
+        vec![
+            test_response(4, 1, &[0xFF, 4, 0, 0]),
+            test_response(4, 1, &[0xFF, 4, 0, 0]),
+            test_response(5, 1, &[0xFF]),
+        ],
+    );
+
+    // both chunks are sent before the empty responses fail the read,
+    // every request has its own reference
+    let mut buffer = vec![0u8; 300];
+    assert!(cl.ag_read(888, 0, 300, &mut buffer).is_err());
+    cl.mb_write(0, 1, &mut [1]).unwrap();
+
+    let references = cl
+        .transport
+        .requests
+        .iter()
+        .map(|request| telegram::reference(request).unwrap())
+        .collect::<Vec<u16>>();
+    assert_eq!(vec![1, 2, 3], references);
 }
@@ -0,0 +1,379 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! TPKT (RFC 1006) and COTP (ISO 8073) framing
+//!
+//! The `Decoder` is fed whole TPKT frames and reassembles the COTP data fragments
+//! of a S7 PDU until the one with the EOT bit. It does no io, a transport fills
+//! `Decoder::read_buffer` with an exact read and calls `Decoder::advance` until a telegram is decoded,
+//! `Decoder::read` does that with a blocking reader.
+//! The telegram it returns always has the 7 byte TPKT + COTP DT header in front of the S7 PDU,
+//! like a response that fit a single frame.
+
+use super::error::Error;
+use byteorder::{BigEndian, ByteOrder};
+use std::fmt;
+use std::io::Read;
+
+pub const TPKT_VERSION: u8 = 3;
+pub const TPKT_HEADER_SIZE: usize = 4;
+/// TPKT header + COTP data header
+pub const ISO_HEADER_SIZE: usize = 7;
+
+/// COTP data transfer
+pub const COTP_DT: u8 = 0xF0;
+/// COTP connection request
+pub const COTP_CR: u8 = 0xE0;
+/// COTP connection confirm
+pub const COTP_CC: u8 = 0xD0;
+/// last data unit of a PDU
+pub const COTP_EOT: u8 = 0x80;
+
+/// a malformed frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// the TPKT version is not 3
+    Version(u8),
+    /// the TPKT length is too short for the COTP header or longer than a negotiated PDU
+    Length { length: usize, max: usize },
+    /// the COTP length indicator is longer than the frame
+    CotpLength(u8),
+    /// a COTP PDU other than data or connection confirm, ie a disconnect request
+    CotpType(u8),
+    /// the reassembled S7 PDU is longer than the negotiated PDU
+    PduLength { length: usize, max: usize },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Version(version) => write!(f, "invalid TPKT version {}", version),
+            FrameError::Length { length, max } => {
+                write!(f, "invalid TPKT length {} max {}", length, max)
+            }
+            FrameError::CotpLength(length) => write!(f, "invalid COTP length {}", length),
+            FrameError::CotpType(pdu_type) => write!(f, "unexpected COTP PDU {:#04x}", pdu_type),
+            FrameError::PduLength { length, max } => {
+                write!(f, "PDU length {} over the negotiated {}", length, max)
+            }
+        }
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(e: FrameError) -> Self {
+        Error::Frame(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    pdu_length: usize,
+    // data of the fragments received so far
+    payload: Vec<u8>,
+    // the frame being read, only its TPKT header until the length is known
+    frame: Vec<u8>,
+    // whether `frame` has been resized to the whole frame
+    header_read: bool,
+}
+
+impl Decoder {
+    /// `pdu_length` is the requested one until the negotiation is done
+    pub fn new(pdu_length: usize) -> Decoder {
+        Decoder {
+            pdu_length,
+            payload: Vec::new(),
+            frame: vec![0; TPKT_HEADER_SIZE],
+            header_read: false,
+        }
+    }
+
+    pub fn pdu_length(&self) -> usize {
+        self.pdu_length
+    }
+
+    /// call with the negotiated pdu length, the buffers are sized for it
+    pub fn set_pdu_length(&mut self, pdu_length: usize) {
+        self.pdu_length = pdu_length;
+        self.payload = Vec::with_capacity(pdu_length);
+    }
+
+    /// a buffer for the TPKT header that fits the largest frame
+    pub fn frame_buffer(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(self.pdu_length + ISO_HEADER_SIZE);
+        frame.resize(TPKT_HEADER_SIZE, 0);
+        frame
+    }
+
+    /// drops the fragments of a PDU that wasn't completed, ie after an io error
+    pub fn reset(&mut self) {
+        self.payload.clear();
+        self.frame.resize(TPKT_HEADER_SIZE, 0);
+        self.header_read = false;
+    }
+
+    /// the bytes to fill with the next exact read, the TPKT header then the rest of the frame
+    pub fn read_buffer(&mut self) -> &mut [u8] {
+        if self.header_read {
+            return &mut self.frame[TPKT_HEADER_SIZE..];
+        }
+        &mut self.frame
+    }
+
+    /// call once the read buffer is filled, returns the telegram once its last fragment is read
+    pub fn advance(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        if !self.header_read {
+            let length = match self.frame_length(&self.frame) {
+                Ok(length) => length,
+                Err(e) => {
+                    self.reset();
+                    return Err(e);
+                }
+            };
+
+            self.frame.resize(length, 0);
+            self.header_read = true;
+            return Ok(None);
+        }
+
+        let frame = std::mem::take(&mut self.frame);
+        let result = self.decode(&frame);
+
+        self.frame = frame;
+        self.frame.truncate(TPKT_HEADER_SIZE);
+        self.header_read = false;
+        result
+    }
+
+    /// length of the whole frame from its TPKT header
+    pub fn frame_length(&self, tpkt: &[u8]) -> Result<usize, FrameError> {
+        if tpkt[0] != TPKT_VERSION {
+            return Err(FrameError::Version(tpkt[0]));
+        }
+
+        let length = BigEndian::read_u16(&tpkt[2..]) as usize;
+        let max = self.pdu_length + ISO_HEADER_SIZE;
+
+        // the COTP header has at least the length indicator and the PDU type
+        if length < TPKT_HEADER_SIZE + 2 || length > max {
+            return Err(FrameError::Length { length, max });
+        }
+        Ok(length)
+    }
+
+    /// decodes a whole frame, returns the telegram once its last fragment is decoded
+    pub fn decode(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, FrameError> {
+        let result = self.decode_frame(frame);
+
+        if result.is_err() {
+            self.reset();
+        }
+        result
+    }
+
+    fn decode_frame(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, FrameError> {
+        let length = self.frame_length(frame)?;
+
+        if frame.len() != length {
+            return Err(FrameError::Length {
+                length: frame.len(),
+                max: length,
+            });
+        }
+
+        // the length indicator doesn't count itself
+        let indicator = frame[TPKT_HEADER_SIZE];
+        let data = TPKT_HEADER_SIZE + 1 + indicator as usize;
+
+        if indicator < 1 || data > length {
+            return Err(FrameError::CotpLength(indicator));
+        }
+
+        match frame[TPKT_HEADER_SIZE + 1] {
+            COTP_CC => Ok(Some(frame.to_vec())),
+            COTP_DT => {
+                if indicator < 2 {
+                    return Err(FrameError::CotpLength(indicator));
+                }
+
+                self.payload.extend_from_slice(&frame[data..]);
+
+                if self.payload.len() > self.pdu_length {
+                    return Err(FrameError::PduLength {
+                        length: self.payload.len(),
+                        max: self.pdu_length,
+                    });
+                }
+
+                // more fragments follow
+                if frame[TPKT_HEADER_SIZE + 2] & COTP_EOT == 0 {
+                    return Ok(None);
+                }
+
+                // an empty frame that doesn't end a fragmented PDU is skipped
+                if self.payload.is_empty() {
+                    return Ok(None);
+                }
+
+                let telegram = encode(&self.payload);
+                self.payload.clear();
+                Ok(Some(telegram))
+            }
+            pdu_type => Err(FrameError::CotpType(pdu_type)),
+        }
+    }
+
+    /// reads frames with exact reads until a whole telegram is decoded
+    pub fn read<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>, Error> {
+        self.reset();
+
+        loop {
+            reader.read_exact(self.read_buffer())?;
+
+            if let Some(telegram) = self.advance()? {
+                return Ok(telegram);
+            }
+        }
+    }
+}
+
+/// frames a S7 PDU in a single COTP data unit
+pub fn encode(pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(ISO_HEADER_SIZE + pdu.len());
+    frame.extend_from_slice(&[TPKT_VERSION, 0, 0, 0, 2, COTP_DT, COTP_EOT]);
+    frame.extend_from_slice(pdu);
+
+    let length = frame.len() as u16;
+    BigEndian::write_u16(frame[2..].as_mut(), length);
+    frame
+}
+
+#[test]
+fn test_decode() {
+    let mut decoder = Decoder::new(240);
+
+    // a single frame
+    let frame = encode(&[50, 3, 0, 0]);
+    assert_eq!(Some(frame.clone()), decoder.decode(&frame).unwrap());
+
+    // the empty frame is skipped
+    assert_eq!(None, decoder.decode(&[3, 0, 0, 7, 2, 0xF0, 0x80]).unwrap());
+
+    // fragments are reassembled in a single frame
+    assert_eq!(
+        None,
+        decoder.decode(&[3, 0, 0, 9, 2, 0xF0, 0, 50, 3]).unwrap()
+    );
+    assert_eq!(None, decoder.decode(&[3, 0, 0, 8, 2, 0xF0, 1, 0]).unwrap());
+    assert_eq!(
+        Some(frame),
+        decoder.decode(&[3, 0, 0, 8, 2, 0xF0, 0x82, 0]).unwrap()
+    );
+
+    // connection confirm is returned as is
+    let confirm = [3, 0, 0, 11, 6, 0xD0, 0, 0, 0, 1, 0];
+    assert_eq!(Some(confirm.to_vec()), decoder.decode(&confirm).unwrap());
+}
+
+#[test]
+fn test_decode_errors() {
+    let mut decoder = Decoder::new(4);
+
+    assert_eq!(
+        FrameError::Version(2),
+        decoder.decode(&[2, 0, 0, 7, 2, 0xF0, 0x80]).unwrap_err()
+    );
+    assert_eq!(
+        FrameError::Length { length: 5, max: 11 },
+        decoder.frame_length(&[3, 0, 0, 5]).unwrap_err()
+    );
+    assert_eq!(
+        FrameError::Length {
+            length: 12,
+            max: 11
+        },
+        decoder.frame_length(&[3, 0, 0, 12]).unwrap_err()
+    );
+    // the frame is shorter than its TPKT length
+    assert!(decoder.decode(&[3, 0, 0, 8, 2, 0xF0, 0x80]).is_err());
+    assert_eq!(
+        FrameError::CotpLength(3),
+        decoder.decode(&[3, 0, 0, 7, 3, 0xF0, 0x80]).unwrap_err()
+    );
+    // disconnect request
+    assert_eq!(
+        FrameError::CotpType(0x80),
+        decoder.decode(&[3, 0, 0, 7, 2, 0x80, 0]).unwrap_err()
+    );
+    // the fragments are longer than the pdu
+    assert_eq!(
+        None,
+        decoder.decode(&[3, 0, 0, 10, 2, 0xF0, 0, 1, 2, 3]).unwrap()
+    );
+    assert_eq!(
+        FrameError::PduLength { length: 6, max: 4 },
+        decoder
+            .decode(&[3, 0, 0, 10, 2, 0xF0, 0x80, 4, 5, 6])
+            .unwrap_err()
+    );
+}
+
+#[test]
+fn test_advance() {
+    let mut decoder = Decoder::new(240);
+    let frame = encode(&[50, 3, 0, 0]);
+
+    assert_eq!(TPKT_HEADER_SIZE, decoder.read_buffer().len());
+    decoder
+        .read_buffer()
+        .copy_from_slice(&frame[..TPKT_HEADER_SIZE]);
+    assert_eq!(None, decoder.advance().unwrap());
+
+    // the rest of the frame from its TPKT length
+    assert_eq!(7, decoder.read_buffer().len());
+    decoder
+        .read_buffer()
+        .copy_from_slice(&frame[TPKT_HEADER_SIZE..]);
+    assert_eq!(Some(frame), decoder.advance().unwrap());
+
+    // an invalid header starts over
+    decoder.read_buffer().copy_from_slice(&[3, 0, 1, 0]);
+    assert!(decoder.advance().is_err());
+    assert_eq!(TPKT_HEADER_SIZE, decoder.read_buffer().len());
+}
+
+#[test]
+fn test_read() {
+    // a fragmented response split across reads at random places
+    struct Segments(Vec<Vec<u8>>);
+
+    impl Read for Segments {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let segment = &mut self.0[0];
+            let n = buf.len().min(segment.len());
+            buf[..n].copy_from_slice(&segment[..n]);
+            segment.drain(..n);
+            if segment.is_empty() {
+                self.0.remove(0);
+            }
+            Ok(n)
+        }
+    }
+
+    let mut reader = Segments(vec![
+        vec![3, 0],
+        vec![0, 9, 2, 0xF0, 0, 50],
+        vec![3, 3, 0, 0, 9, 2, 0xF0, 0x81, 0],
+        vec![0],
+    ]);
+    let mut decoder = Decoder::new(240);
+
+    assert_eq!(encode(&[50, 3, 0, 0]), decoder.read(&mut reader).unwrap());
+    // the connection was closed in the middle of a frame
+    let mut reader = Segments(vec![vec![3, 0, 0, 9, 2]]);
+    assert!(decoder.read(&mut reader).is_err());
+}
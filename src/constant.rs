@@ -1,9 +1,8 @@
 use crate::error::Error;
 
 // Area ID
-#[derive(Clone, Copy)]
-#[allow(dead_code)]
-pub(crate) enum Area {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Area {
     ProcessInput = 0x81,
     ProcessOutput = 0x82,
     /// Merkers are address registers within the CPU.
@@ -18,6 +17,20 @@ pub(crate) enum Area {
     Unknown,
 }
 
+impl Area {
+    pub(crate) fn from_u8(value: u8) -> Area {
+        match value {
+            0x81 => Area::ProcessInput,
+            0x82 => Area::ProcessOutput,
+            0x83 => Area::Merker,
+            0x84 => Area::DataBausteine,
+            0x1C => Area::Counter,
+            0x1D => Area::Timer,
+            _ => Area::Unknown,
+        }
+    }
+}
+
 // Word Length
 pub const WL_BIT: i32 = 0x01; //Bit (inside a word)
 pub const WL_BYTE: i32 = 0x02; //Byte (8 bit)
@@ -41,6 +54,7 @@ pub fn data_size_byte(word_length: i32) -> i32 {
 }
 
 // PLC Status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CpuStatus {
     Unknown = 0,
     Stop = 4,
@@ -65,9 +79,13 @@ pub const SIZE_HEADER_WRITE: i32 = 35; // Header Size when Writing
 // Result transport size
 pub const TS_RES_BIT: i32 = 3;
 pub const TS_RES_BYTE: i32 = 4;
-#[allow(dead_code)]
 pub const TS_RES_INT: i32 = 5;
-//todo implement read write multi
-#[allow(dead_code)]
 pub const TS_RES_REAL: i32 = 7;
 pub const TS_RES_OCTET: i32 = 9;
+
+// Item return code
+pub const RES_ITEM_OK: u8 = 0xFF;
+
+// Multi read/write
+pub const MAX_VARS: usize = 20; // Max vars that can be transferred with a single request
+pub const SIZE_ITEM_SPEC: i32 = 12; // Size of a single item spec in a request
@@ -2,6 +2,7 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
+use super::codec::FrameError;
 use std::error;
 use std::fmt;
 use std::io::{Error as IOError, ErrorKind};
@@ -58,18 +59,18 @@ const CLI_INVALID_PARAM_NUMBER: i32 = 0x02500000;
 const CLI_CANNOT_CHANGE_PARAM: i32 = 0x02600000;
 const CLI_FUNCTION_NOT_IMPLEMENTED: i32 = 0x02700000;
 
-const CODE_7_ADDRESS_OUT_OF_RANGE: i32 = 5;
-const CODE_7_INVALID_TRANSPORT_SIZE: i32 = 6;
-const CODE_7_WRITE_DATA_SIZE_MISMATCH: i32 = 7;
-const CODE_7_RES_ITEM_NOT_AVAILABLE: i32 = 10;
-const CODE_7_RES_ITEM_NOT_AVAILABLE1: i32 = 53769;
-const CODE_7_INVALID_VALUE: i32 = 56321;
-const CODE_7_NEED_PASSWORD: i32 = 53825;
-const CODE_7_INVALID_PASSWORD: i32 = 54786;
-const CODE_7_NO_PASSWORD_TO_CLEAR: i32 = 54788;
-const CODE_7_NO_PASSWORD_TO_SET: i32 = 54789;
-const CODE_7_FUN_NOT_AVAILABLE: i32 = 33028;
-const CODE_7_DATA_OVER_PDU: i32 = 34048;
+pub const CODE_7_ADDRESS_OUT_OF_RANGE: i32 = 5;
+pub const CODE_7_INVALID_TRANSPORT_SIZE: i32 = 6;
+pub const CODE_7_WRITE_DATA_SIZE_MISMATCH: i32 = 7;
+pub const CODE_7_RES_ITEM_NOT_AVAILABLE: i32 = 10;
+pub const CODE_7_RES_ITEM_NOT_AVAILABLE1: i32 = 53769;
+pub const CODE_7_INVALID_VALUE: i32 = 56321;
+pub const CODE_7_NEED_PASSWORD: i32 = 53825;
+pub const CODE_7_INVALID_PASSWORD: i32 = 54786;
+pub const CODE_7_NO_PASSWORD_TO_CLEAR: i32 = 54788;
+pub const CODE_7_NO_PASSWORD_TO_SET: i32 = 54789;
+pub const CODE_7_FUN_NOT_AVAILABLE: i32 = 33028;
+pub const CODE_7_DATA_OVER_PDU: i32 = 34048;
 
 #[derive(Debug)]
 pub enum Error {
@@ -85,6 +86,9 @@ pub enum Error {
     TryFrom(Vec<u8>, String),
     InvalidCpuStatus(u8),
     InvalidResponse { reason: String, bytes: Vec<u8> },
+    InvalidAddress { address: String, reason: String },
+    Frame(FrameError),
+    Replay(String),
 }
 
 impl fmt::Display for Error {
@@ -108,6 +112,11 @@ impl fmt::Display for Error {
             Error::InvalidResponse { reason, bytes } => {
                 write!(f, "Invalid response {:?} err {}", bytes, reason)
             }
+            Error::InvalidAddress { address, reason } => {
+                write!(f, "Invalid address {} reason {}", address, reason)
+            }
+            Error::Frame(e) => write!(f, "Frame error: {}", e),
+            Error::Replay(reason) => write!(f, "Replay error: {}", reason),
         }
     }
 }
@@ -4,17 +4,30 @@
 
 //! Parses bytes from `Area::DataBausteine` to types for easier manipulation
 
+use super::constant::Area;
 use super::error::Error;
 use byteorder::{BigEndian, ByteOrder};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+mod array;
+mod string;
+mod time;
+
+pub use array::Array;
+pub use string::{CodePage, S7String, S7WString};
+pub(crate) use time::{decode_s5time, encode_s5time};
+pub use time::{Date, DateAndTime, Dtl, LTime, PlcDateTime, S5Time, Time, TimeOfDay};
 
 /// Fields collection type alias for convenience
 /// # Examples
 ///
 /// ```
-/// use s7::field::{Float, Bool, Fields};
+/// use s7::field::{BitAddress, Float, Bool, Fields};
 ///
-/// let float = Float::new(888, 8.0, vec![66, 86, 0, 0]).unwrap();
-/// let boolean = Bool::new(888, 8.0, vec![1u8]).unwrap();
+/// let float = Float::new(888, BitAddress::new(8, 0), vec![66, 86, 0, 0]).unwrap();
+/// let boolean = Bool::new(888, BitAddress::new(8, 0), vec![1u8]).unwrap();
 /// println!("bool: {}", boolean.value());
 /// println!("float: {}", float.value());
 /// let fields: Fields = vec![Box::new(float), Box::new(boolean)];
@@ -30,32 +43,153 @@ use byteorder::{BigEndian, ByteOrder};
 /// ```
 pub type Fields = Vec<Box<dyn Field>>;
 
+/// exact position of a field within a data block
+/// `bit` is only used by `Bool`, zero for all other types
+///
+/// # Examples
+///
+/// ```
+/// use s7::field::BitAddress;
+///
+/// let address: BitAddress = "8.3".parse().unwrap();
+/// assert_eq!(BitAddress::new(8, 3), address);
+/// assert_eq!("8.3", address.to_string());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BitAddress {
+    pub byte: u32,
+    pub bit: u8,
+}
+
+impl BitAddress {
+    pub const fn new(byte: u32, bit: u8) -> BitAddress {
+        BitAddress { byte, bit }
+    }
+
+    /// converts the legacy float notation, ie `8.3` is byte 8, bit 3
+    /// the bit is rounded, so `8.3` is never read as `8.29999`
+    pub fn from_float(offset: f64) -> Result<BitAddress, Error> {
+        let err = |reason: String| Error::InvalidAddress {
+            address: offset.to_string(),
+            reason,
+        };
+
+        if !(0.0..=u32::MAX as f64).contains(&offset) {
+            return Err(err(format!("byte offset must be 0..{}", u32::MAX)));
+        }
+
+        let byte = offset.trunc();
+        let bit = ((offset - byte) * 10.0).round() as u8;
+
+        if bit > 7 {
+            return Err(err(format!("bit offset must be 0..7 got {}", bit)));
+        }
+        Ok(BitAddress {
+            byte: byte as u32,
+            bit,
+        })
+    }
+}
+
+impl fmt::Display for BitAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.byte, self.bit)
+    }
+}
+
+impl FromStr for BitAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: String| Error::InvalidAddress {
+            address: s.to_string(),
+            reason,
+        };
+
+        let (byte, bit) = match s.find('.') {
+            Some(dot) => (&s[..dot], &s[dot + 1..]),
+            None => (s, "0"),
+        };
+
+        let bit = bit
+            .parse()
+            .map_err(|e| err(format!("invalid bit offset '{}': {}", bit, e)))?;
+
+        if bit > 7 {
+            return Err(err(format!("bit offset must be 0..7 got {}", bit)));
+        }
+
+        Ok(BitAddress {
+            byte: byte
+                .parse()
+                .map_err(|e| err(format!("invalid byte offset '{}': {}", byte, e)))?,
+            bit,
+        })
+    }
+}
+
 /// represents a type stored in the hardware
 /// ie `bool`, `real(32 bit float)`
 pub trait Field {
     /// data block
     fn data_block(&self) -> i32;
-    /// offset in the data block
-    /// for convenience, we truncate the float
-    /// we don't care about the digits after the decimal point anymore
+    /// memory area, fields outside of data blocks have to override it
+    fn area(&self) -> Area {
+        Area::DataBausteine
+    }
+    /// byte offset in the data block
     fn offset(&self) -> i32;
+    /// exact byte and bit offset in the data block, bit 0 of the byte offset by default
+    fn bit_address(&self) -> BitAddress {
+        BitAddress::new(self.offset() as u32, 0)
+    }
 
     fn to_bytes(&self) -> Vec<u8>;
 }
 
+/// a field with a fixed size, it can be decoded from a buffer
+/// without knowing anything but its position
+///
+/// # Examples
+///
+/// ```
+/// use s7::constant::Area;
+/// use s7::field::{BitAddress, Field, Int, SizedField};
+///
+/// let mut counter = Int::decode(0, BitAddress::new(20, 0), vec![0, 42]).unwrap();
+/// counter.set_area(Area::Merker);
+///
+/// assert_eq!(2, Int::bit_size() / 8);
+/// assert_eq!(Area::Merker, counter.area());
+/// ```
+pub trait SizedField: Field + Sized {
+    /// size in bits, 1 for `Bool`, a multiple of 8 for everything else
+    fn bit_size() -> u32;
+
+    /// `bytes` are the `bit_size` bits rounded up to whole bytes
+    fn decode(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Self, Error>;
+
+    /// moves the field to another memory area, the data block is ignored outside of `Area::DataBausteine`
+    fn set_area(&mut self, area: Area);
+
+    /// writes the value to `buf` which is the same size as in `decode`,
+    /// fields smaller than a byte must leave the other bits alone
+    fn encode_to(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self.to_bytes().as_slice())
+    }
+}
+
 /// PLC float field
 #[derive(Debug)]
 pub struct Float {
     data_block: i32,
-    /// offset example 8.1
-    /// left side is index within the block
-    /// right side is the bit position only used for bool, zero for all other types
-    offset: f32,
+    area: Area,
+    offset: BitAddress,
     value: f32,
 }
 
 impl Float {
-    pub fn new(data_block: i32, offset: f32, mut bytes: Vec<u8>) -> Result<Float, Error> {
+    pub fn new(data_block: i32, offset: BitAddress, mut bytes: Vec<u8>) -> Result<Float, Error> {
         let len = bytes.len();
         if bytes.len() != Float::size() as usize {
             return Err(Error::TryFrom(
@@ -64,24 +198,32 @@ impl Float {
             ));
         }
 
-        let bit_offset = ((offset * 10.0) as usize % 10) as u8;
-        if bit_offset != 0 {
+        if offset.bit != 0 {
             return Err(Error::TryFrom(
                 bytes,
                 format!(
                     "Float.new: float should not have a bit offset got {}",
-                    bit_offset
+                    offset.bit
                 ),
             ));
         }
 
         Ok(Float {
             data_block,
+            area: Area::DataBausteine,
             offset,
             value: BigEndian::read_f32(bytes.as_mut_slice()),
         })
     }
 
+    /// offset example 8.1
+    /// left side is index within the block
+    /// right side is the bit position, must be zero
+    #[deprecated(note = "use `Float::new` with a `BitAddress`")]
+    pub fn from_float_offset(data_block: i32, offset: f32, bytes: Vec<u8>) -> Result<Float, Error> {
+        Float::new(data_block, BitAddress::from_float(offset as f64)?, bytes)
+    }
+
     pub fn size() -> i32 {
         4
     }
@@ -100,55 +242,77 @@ impl Field for Float {
         self.data_block
     }
 
+    fn area(&self) -> Area {
+        self.area
+    }
+
     fn offset(&self) -> i32 {
-        self.offset as i32
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut buf = vec![0u8; Float::size() as usize];
         BigEndian::write_f32(buf.as_mut_slice(), self.value);
-        return buf;
+        buf
     }
 }
 
 #[derive(Debug)]
 pub struct Double {
     data_block: i32,
-    /// offset example 8.1
-    /// left side is index within the block
-    /// right side is the bit position only used for bool, zero for all other types
-    offset: f64,
+    area: Area,
+    offset: BitAddress,
     value: f64,
 }
 
 impl Double {
-    pub fn new(data_block: i32, offset: f64, mut bytes: Vec<u8>) -> Result<Double, Error> {
+    pub fn new(data_block: i32, offset: BitAddress, mut bytes: Vec<u8>) -> Result<Double, Error> {
         let len = bytes.len();
         if bytes.len() != Double::size() as usize {
             return Err(Error::TryFrom(
                 bytes,
-                format!("Double.new: expected buf size {} got {}", Double::size(), len),
+                format!(
+                    "Double.new: expected buf size {} got {}",
+                    Double::size(),
+                    len
+                ),
             ));
         }
 
-        let bit_offset = ((offset * 10.0) as usize % 10) as u8;
-        if bit_offset != 0 {
+        if offset.bit != 0 {
             return Err(Error::TryFrom(
                 bytes,
                 format!(
                     "Double.new: double should not have a bit offset got {}",
-                    bit_offset
+                    offset.bit
                 ),
             ));
         }
 
         Ok(Double {
             data_block,
+            area: Area::DataBausteine,
             offset,
             value: BigEndian::read_f64(bytes.as_mut_slice()),
         })
     }
 
+    /// offset example 8.1
+    /// left side is index within the block
+    /// right side is the bit position, must be zero
+    #[deprecated(note = "use `Double::new` with a `BitAddress`")]
+    pub fn from_float_offset(
+        data_block: i32,
+        offset: f64,
+        bytes: Vec<u8>,
+    ) -> Result<Double, Error> {
+        Double::new(data_block, BitAddress::from_float(offset)?, bytes)
+    }
+
     pub fn size() -> i32 {
         8
     }
@@ -167,27 +331,34 @@ impl Field for Double {
         self.data_block
     }
 
+    fn area(&self) -> Area {
+        self.area
+    }
+
     fn offset(&self) -> i32 {
-        self.offset as i32
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut buf = vec![0u8; Double::size() as usize];
         BigEndian::write_f64(buf.as_mut_slice(), self.value);
-        return buf;
+        buf
     }
 }
 
-
 /// Bool represents a single bit in a byte from `Area::DataBausteine`
 #[derive(Debug)]
 pub struct Bool {
     /// index of the block it's stored at
     data_block: i32,
-    /// example 8.1
-    /// left side is index within the block
-    /// right side is the bit position only used for bool, zero for all other types
-    offset: f32,
+    /// memory area, `Area::DataBausteine` unless moved with `set_area`
+    area: Area,
+    /// byte within the block and the bit position within the byte
+    offset: BitAddress,
     /// the actual primitive value
     byte: u8,
     /// the current value that will be written to the byte
@@ -195,7 +366,7 @@ pub struct Bool {
 }
 
 impl Bool {
-    pub fn new(data_block: i32, offset: f32, bytes: Vec<u8>) -> Result<Self, Error> {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Self, Error> {
         let len = bytes.len();
         if bytes.len() != Self::size() as usize {
             return Err(Error::TryFrom(
@@ -204,28 +375,36 @@ impl Bool {
             ));
         }
 
-        let bit_offset = ((offset * 10.0) as usize % 10) as u8;
-        if bit_offset > 7 {
+        if offset.bit > 7 {
             return Err(Error::TryFrom(
                 bytes,
-                format!("Bool.new: max offset is 7 got {}", offset),
+                format!("Bool.new: max offset is 7 got {}", offset.bit),
             ));
         }
 
         Ok(Bool {
             data_block,
+            area: Area::DataBausteine,
             offset,
             byte: bytes[0],
-            value: bytes[0] & (1 << bit_offset) != 0,
+            value: bytes[0] & (1 << offset.bit) != 0,
         })
     }
 
+    /// offset example 8.1
+    /// left side is index within the block
+    /// right side is the bit position
+    #[deprecated(note = "use `Bool::new` with a `BitAddress`")]
+    pub fn from_float_offset(data_block: i32, offset: f32, bytes: Vec<u8>) -> Result<Self, Error> {
+        Bool::new(data_block, BitAddress::from_float(offset as f64)?, bytes)
+    }
+
     #[inline(always)]
     fn set_value_at(b: u8, bit_pos: u8, val: bool) -> u8 {
         if val {
             return b | (1 << bit_pos);
         }
-        return b & !(1 << bit_pos);
+        b & !(1 << bit_pos)
     }
 
     pub fn size() -> i32 {
@@ -239,11 +418,7 @@ impl Bool {
 
     pub fn set_value(&mut self, v: bool) {
         self.value = v;
-        self.byte = Bool::set_value_at(
-            self.byte,
-            ((self.offset * 10.0) as usize % 10) as u8,
-            self.value,
-        );
+        self.byte = Bool::set_value_at(self.byte, self.offset.bit, self.value);
     }
 }
 
@@ -252,8 +427,16 @@ impl Field for Bool {
         self.data_block
     }
 
+    fn area(&self) -> Area {
+        self.area
+    }
+
     fn offset(&self) -> i32 {
-        self.offset as i32
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -265,15 +448,13 @@ impl Field for Bool {
 #[derive(Debug)]
 pub struct Word {
     data_block: i32,
-    /// offset example 8.1
-    /// left side is index within the block
-    /// right side is the bit position only used for bool, zero for all other types
-    offset: f32,
+    area: Area,
+    offset: BitAddress,
     value: u16,
 }
 
 impl Word {
-    pub fn new(data_block: i32, offset: f32, mut bytes: Vec<u8>) -> Result<Word, Error> {
+    pub fn new(data_block: i32, offset: BitAddress, mut bytes: Vec<u8>) -> Result<Word, Error> {
         let len = bytes.len();
         if bytes.len() != Word::size() as usize {
             return Err(Error::TryFrom(
@@ -282,24 +463,32 @@ impl Word {
             ));
         }
 
-        let bit_offset = ((offset * 10.0) as usize % 10) as u8;
-        if bit_offset != 0 {
+        if offset.bit != 0 {
             return Err(Error::TryFrom(
                 bytes,
                 format!(
-                    "Word.new: float should not have a bit offset got {}",
-                    bit_offset
+                    "Word.new: word should not have a bit offset got {}",
+                    offset.bit
                 ),
             ));
         }
 
         Ok(Word {
             data_block,
+            area: Area::DataBausteine,
             offset,
             value: BigEndian::read_u16(bytes.as_mut_slice()),
         })
     }
 
+    /// offset example 8.1
+    /// left side is index within the block
+    /// right side is the bit position, must be zero
+    #[deprecated(note = "use `Word::new` with a `BitAddress`")]
+    pub fn from_float_offset(data_block: i32, offset: f32, bytes: Vec<u8>) -> Result<Word, Error> {
+        Word::new(data_block, BitAddress::from_float(offset as f64)?, bytes)
+    }
+
     pub fn size() -> i32 {
         2
     }
@@ -318,22 +507,303 @@ impl Field for Word {
         self.data_block
     }
 
+    fn area(&self) -> Area {
+        self.area
+    }
+
     fn offset(&self) -> i32 {
-        self.offset as i32
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let mut buf = vec![0u8; Word::size() as usize];
         BigEndian::write_u16(buf.as_mut_slice(), self.value);
-        return buf;
+        buf
+    }
+}
+
+// generates a field for a fixed size big endian number
+macro_rules! number_field {
+    ($(#[$doc:meta])* $name:ident, $type:ty, $size:expr) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub struct $name {
+            data_block: i32,
+            area: Area,
+            offset: BitAddress,
+            value: $type,
+        }
+
+        impl $name {
+            pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<$name, Error> {
+                let len = bytes.len();
+                if bytes.len() != $name::size() as usize {
+                    return Err(Error::TryFrom(
+                        bytes,
+                        format!(
+                            "{}.new: expected buf size {} got {}",
+                            stringify!($name),
+                            $name::size(),
+                            len
+                        ),
+                    ));
+                }
+
+                if offset.bit != 0 {
+                    return Err(Error::TryFrom(
+                        bytes,
+                        format!(
+                            "{}.new: {} should not have a bit offset got {}",
+                            stringify!($name),
+                            stringify!($type),
+                            offset.bit
+                        ),
+                    ));
+                }
+
+                let mut buf = [0u8; $size];
+                buf.copy_from_slice(bytes.as_slice());
+
+                Ok($name {
+                    data_block,
+                    area: Area::DataBausteine,
+                    offset,
+                    value: <$type>::from_be_bytes(buf),
+                })
+            }
+
+            pub fn size() -> i32 {
+                $size
+            }
+
+            pub fn value(&self) -> $type {
+                self.value
+            }
+
+            pub fn set_value(&mut self, v: $type) {
+                self.value = v
+            }
+        }
+
+        impl Field for $name {
+            fn data_block(&self) -> i32 {
+                self.data_block
+            }
+
+            fn area(&self) -> Area {
+                self.area
+            }
+
+            fn offset(&self) -> i32 {
+                self.offset.byte as i32
+            }
+
+            fn bit_address(&self) -> BitAddress {
+                self.offset
+            }
+
+            fn to_bytes(&self) -> Vec<u8> {
+                self.value.to_be_bytes().to_vec()
+            }
+        }
+    };
+}
+
+number_field!(
+    /// PLC byte field, 8 bit bit string
+    Byte,
+    u8,
+    1
+);
+number_field!(
+    /// PLC sint field, 8 bit signed integer
+    SInt,
+    i8,
+    1
+);
+number_field!(
+    /// PLC usint field, 8 bit unsigned integer
+    USInt,
+    u8,
+    1
+);
+number_field!(
+    /// PLC int field, 16 bit signed integer
+    Int,
+    i16,
+    2
+);
+number_field!(
+    /// PLC uint field, 16 bit unsigned integer
+    UInt,
+    u16,
+    2
+);
+number_field!(
+    /// PLC dword field, 32 bit bit string
+    DWord,
+    u32,
+    4
+);
+number_field!(
+    /// PLC dint field, 32 bit signed integer
+    DInt,
+    i32,
+    4
+);
+number_field!(
+    /// PLC udint field, 32 bit unsigned integer
+    UDInt,
+    u32,
+    4
+);
+number_field!(
+    /// PLC lword field, 64 bit bit string
+    LWord,
+    u64,
+    8
+);
+number_field!(
+    /// PLC lint field, 64 bit signed integer
+    LInt,
+    i64,
+    8
+);
+number_field!(
+    /// PLC ulint field, 64 bit unsigned integer
+    ULInt,
+    u64,
+    8
+);
+
+/// PLC lreal field, 64 bit float
+pub type LReal = Double;
+
+/// PLC char field, a single byte character
+/// the byte is read as ISO 8859-1,
+/// characters that don't fit in a byte are written as `?`
+#[derive(Debug)]
+pub struct Char {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: char,
+}
+
+impl Char {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Char, Error> {
+        let len = bytes.len();
+        if bytes.len() != Char::size() as usize {
+            return Err(Error::TryFrom(
+                bytes,
+                format!("Char.new: expected buf size {} got {}", Char::size(), len),
+            ));
+        }
+
+        if offset.bit != 0 {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "Char.new: char should not have a bit offset got {}",
+                    offset.bit
+                ),
+            ));
+        }
+
+        Ok(Char {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value: bytes[0] as char,
+        })
+    }
+
+    pub fn size() -> i32 {
+        1
+    }
+
+    pub fn value(&self) -> char {
+        self.value
+    }
+
+    pub fn set_value(&mut self, v: char) {
+        self.value = v
+    }
+}
+
+impl Field for Char {
+    fn data_block(&self) -> i32 {
+        self.data_block
+    }
+
+    fn area(&self) -> Area {
+        self.area
+    }
+
+    fn offset(&self) -> i32 {
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![u8::try_from(self.value).unwrap_or(b'?')]
     }
 }
 
+// implements `SizedField` for the fields with a `new` and a `size`
+macro_rules! sized_field {
+    ($($name:ident),*) => {
+        $(
+            impl SizedField for $name {
+                fn bit_size() -> u32 {
+                    $name::size() as u32 * 8
+                }
+
+                fn decode(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Self, Error> {
+                    $name::new(data_block, offset, bytes)
+                }
+
+                fn set_area(&mut self, area: Area) {
+                    self.area = area
+                }
+            }
+        )*
+    };
+}
+
+sized_field!(
+    Float, Double, Word, Byte, SInt, USInt, Int, UInt, DWord, DInt, UDInt, LWord, LInt, ULInt, Char
+);
+
+impl SizedField for Bool {
+    fn bit_size() -> u32 {
+        1
+    }
+
+    fn decode(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Self, Error> {
+        Bool::new(data_block, offset, bytes)
+    }
+
+    fn set_area(&mut self, area: Area) {
+        self.area = area
+    }
+
+    fn encode_to(&self, buf: &mut [u8]) {
+        buf[0] = Bool::set_value_at(buf[0], self.offset.bit, self.value);
+    }
+}
 
 #[test]
 fn test_fields() {
-    let float = Float::new(888, 8.0, vec![66, 86, 0, 0]).unwrap();
-    let boolean = Bool::new(888, 8.0, vec![1u8]).unwrap();
+    let float = Float::new(888, BitAddress::new(8, 0), vec![66, 86, 0, 0]).unwrap();
+    let boolean = Bool::new(888, BitAddress::new(8, 0), vec![1u8]).unwrap();
     assert!(boolean.value());
     assert_eq!(53.5, float.value());
     let fields: Fields = vec![Box::new(float), Box::new(boolean)];
@@ -353,7 +823,7 @@ fn test_float() {
     let val: f32 = 53.5;
     let mut b = vec![0u8; Float::size() as usize];
     BigEndian::write_f32(b.as_mut_slice(), val);
-    let mut field = Float::new(888, 8.0, b).unwrap();
+    let mut field = Float::new(888, BitAddress::new(8, 0), b).unwrap();
     field.set_value(val);
     let result = field.to_bytes();
 
@@ -361,52 +831,46 @@ fn test_float() {
 
     // test invalid bit offset
     // float should not have a bit offset
-    match Float::new(888, 8.1, vec![66, 86, 0, 0]) {
-        Ok(_) => {
-            println!("should return an error at invalid bit offset 1. Floats should not have a bit offset");
-            assert!(false)
-        }
-        Err(_) => {}
-    }
+    assert!(
+        Float::new(888, BitAddress::new(8, 1), vec![66, 86, 0, 0]).is_err(),
+        "should return an error at invalid bit offset 1. Floats should not have a bit offset"
+    );
 }
 
 #[test]
 fn test_bool() {
     let b = vec![1u8; 1];
-    let mut field = Bool::new(888, 8.1, b).unwrap();
+    let mut field = Bool::new(888, BitAddress::new(8, 1), b).unwrap();
     field.set_value(true);
 
     let mut res: Vec<u8> = field.to_bytes();
 
     assert_eq!(res.len(), 1);
     assert_eq!(res[0], 3);
-    assert_eq!(field.value(), true);
+    assert!(field.value());
 
     field.set_value(false);
     res = field.to_bytes();
 
     assert_eq!(res.len(), 1);
     assert_eq!(res[0], 1);
-    assert_eq!(field.value(), false);
+    assert!(!field.value());
 
     let bb = vec![0b00001000u8; 1];
-    field = Bool::new(888, 8.4, bb).unwrap();
+    field = Bool::new(888, BitAddress::new(8, 4), bb).unwrap();
     field.set_value(true);
 
     res = field.to_bytes();
 
     assert_eq!(res.len(), 1);
     assert_eq!(res[0], 24);
-    assert_eq!(field.value(), true);
+    assert!(field.value());
 
     // test invalid bit offset
-    match Bool::new(888, 8.8, vec![0b00001000u8; 1]) {
-        Ok(_) => {
-            println!("should return an error at invalid bit offset 8");
-            assert!(false)
-        }
-        Err(_) => {}
-    }
+    assert!(
+        Bool::new(888, BitAddress::new(8, 8), vec![0b00001000u8; 1]).is_err(),
+        "should return an error at invalid bit offset 8"
+    );
 }
 
 #[test]
@@ -414,7 +878,7 @@ fn test_word() {
     let val: u16 = 43981;
     let mut b = vec![0u8; Word::size() as usize];
     BigEndian::write_u16(b.as_mut_slice(), val);
-    let mut field = Word::new(888, 8.0, b).unwrap();
+    let mut field = Word::new(888, BitAddress::new(8, 0), b).unwrap();
     field.set_value(val);
     let result = field.to_bytes();
 
@@ -422,11 +886,145 @@ fn test_word() {
 
     // test invalid bit offset
     // words should not have a bit offset
-    match Word::new(888, 8.1, vec![12, 23]) {
-        Ok(_) => {
-            println!("should return an error at invalid bit offset 1. Words should not have a bit offset");
-            assert!(false)
+    assert!(
+        Word::new(888, BitAddress::new(8, 1), vec![12, 23]).is_err(),
+        "should return an error at invalid bit offset 1. Words should not have a bit offset"
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_float_offset() {
+    // 8.3 as f32 is 8.30000019, the bit must not become 2 or 4
+    for bit in 0..8u8 {
+        let offset = 8.0 + bit as f32 / 10.0;
+        assert_eq!(
+            BitAddress::new(8, bit),
+            BitAddress::from_float(offset as f64).unwrap()
+        );
+
+        let field = Bool::from_float_offset(888, offset, vec![1 << bit]).unwrap();
+        assert!(field.value());
+        assert_eq!(8, field.offset());
+    }
+
+    assert!(Float::from_float_offset(888, 8.3, vec![66, 86, 0, 0]).is_err());
+    // negative, not a number or past the bit 7
+    for offset in [-3.0, f64::NAN, 8.9] {
+        assert!(BitAddress::from_float(offset).is_err());
+    }
+    assert!(Bool::from_float_offset(888, -3.0, vec![1]).is_err());
+    assert_eq!(
+        BitAddress::new(70000, 0),
+        Double::from_float_offset(888, 70000.0, vec![0; 8])
+            .unwrap()
+            .bit_address()
+    );
+}
+
+#[test]
+fn test_bit_address_parse() {
+    assert_eq!(BitAddress::new(8, 3), "8.3".parse().unwrap());
+    assert_eq!(BitAddress::new(12, 0), "12".parse().unwrap());
+    assert!("8.x".parse::<BitAddress>().is_err());
+    assert!("-1.0".parse::<BitAddress>().is_err());
+    assert!("8.9".parse::<BitAddress>().is_err());
+    assert!("8.255".parse::<BitAddress>().is_err());
+}
+
+#[test]
+fn test_numbers() {
+    let offset = BitAddress::new(8, 0);
+
+    let mut byte = Byte::new(888, offset, vec![0xAB]).unwrap();
+    assert_eq!(0xAB, byte.value());
+    byte.set_value(0x12);
+    assert_eq!(vec![0x12], byte.to_bytes());
+
+    let mut sint = SInt::new(888, offset, vec![0xFF]).unwrap();
+    assert_eq!(-1, sint.value());
+    sint.set_value(-128);
+    assert_eq!(vec![0x80], sint.to_bytes());
+
+    let usint = USInt::new(888, offset, vec![200]).unwrap();
+    assert_eq!(vec![200], usint.to_bytes());
+
+    let mut int = Int::new(888, offset, vec![0xFF, 0xFE]).unwrap();
+    assert_eq!(-2, int.value());
+    int.set_value(-32768);
+    assert_eq!(vec![0x80, 0], int.to_bytes());
+
+    let mut uint = UInt::new(888, offset, vec![171, 205]).unwrap();
+    assert_eq!(43981, uint.value());
+    uint.set_value(1);
+    assert_eq!(vec![0, 1], uint.to_bytes());
+
+    let mut dint = DInt::new(888, offset, vec![0xFF, 0xFF, 0xFF, 0xFE]).unwrap();
+    assert_eq!(-2, dint.value());
+    dint.set_value(0x01020304);
+    assert_eq!(vec![1, 2, 3, 4], dint.to_bytes());
+
+    let dword = DWord::new(888, offset, vec![0xDE, 0xAD, 0xBE, 0xEF]).unwrap();
+    assert_eq!(0xDEADBEEF, dword.value());
+
+    let udint = UDInt::new(888, offset, vec![0, 0, 1, 0]).unwrap();
+    assert_eq!(256, udint.value());
+
+    let mut lint = LInt::new(888, offset, vec![0xFF; 8]).unwrap();
+    assert_eq!(-1, lint.value());
+    lint.set_value(i64::MIN);
+    assert_eq!(vec![0x80, 0, 0, 0, 0, 0, 0, 0], lint.to_bytes());
+
+    let ulint = ULInt::new(888, offset, vec![0, 0, 0, 0, 0, 0, 1, 0]).unwrap();
+    assert_eq!(256, ulint.value());
+
+    let mut lword = LWord::new(888, offset, vec![0; 8]).unwrap();
+    lword.set_value(0x0102030405060708);
+    assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], lword.to_bytes());
+
+    let mut lreal = LReal::new(888, offset, vec![64, 42, 192, 0, 0, 0, 0, 0]).unwrap();
+    assert_eq!(13.375, lreal.value());
+    lreal.set_value(-1.5);
+    assert_eq!(vec![191, 248, 0, 0, 0, 0, 0, 0], lreal.to_bytes());
+
+    // invalid buffer size
+    assert!(DInt::new(888, offset, vec![1, 2]).is_err());
+    // numbers should not have a bit offset
+    assert!(Int::new(888, BitAddress::new(8, 1), vec![1, 2]).is_err());
+}
+
+#[test]
+fn test_char() {
+    let mut field = Char::new(888, BitAddress::new(8, 0), vec![b'A']).unwrap();
+    assert_eq!('A', field.value());
+
+    field.set_value('é');
+    assert_eq!(vec![0xE9], field.to_bytes());
+
+    field.set_value('€');
+    assert_eq!(vec![b'?'], field.to_bytes());
+
+    assert!(Char::new(888, BitAddress::new(8, 1), vec![b'A']).is_err());
+}
+
+#[test]
+fn test_default_bit_address() {
+    // a field implemented before the bit address
+    struct Legacy;
+
+    impl Field for Legacy {
+        fn data_block(&self) -> i32 {
+            888
+        }
+
+        fn offset(&self) -> i32 {
+            8
+        }
+
+        fn to_bytes(&self) -> Vec<u8> {
+            vec![0]
         }
-        Err(_) => {}
     }
+
+    assert_eq!(BitAddress::new(8, 0), Legacy.bit_address());
 }
@@ -0,0 +1,233 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! `ARRAY[..] OF <type>` fields
+
+use super::*;
+use std::ops::{Index, IndexMut};
+
+/// PLC `ARRAY[..] OF <type>` field, decoded from one contiguous buffer
+///
+/// the elements follow each other without padding,
+/// `Bool` elements are packed 8 per byte starting at bit 0 like the PLC does
+///
+/// # Examples
+///
+/// ```
+/// use s7::field::{Array, BitAddress, Field, Float};
+///
+/// let bytes = vec![66, 86, 0, 0, 0, 0, 0, 0];
+/// assert_eq!(Array::<Float>::size(2) as usize, bytes.len());
+///
+/// let mut values: Array<Float> = Array::new(888, BitAddress::new(8, 0), 2, bytes).unwrap();
+/// assert_eq!(53.5, values[0].value());
+///
+/// values[1].set_value(-1.5);
+/// assert_eq!(vec![1], values.changed().collect::<Vec<usize>>());
+/// assert_eq!(vec![66, 86, 0, 0, 191, 192, 0, 0], values.to_bytes());
+/// ```
+#[derive(Debug)]
+pub struct Array<T> {
+    data_block: i32,
+    offset: BitAddress,
+    /// the buffer it was decoded from, keeps the padding bits of `Bool` arrays
+    bytes: Vec<u8>,
+    elements: Vec<T>,
+    changed: Vec<bool>,
+}
+
+impl<T: SizedField> Array<T> {
+    /// decodes `len` elements from `bytes` which must be `Array::<T>::size(len)` long
+    pub fn new(
+        data_block: i32,
+        offset: BitAddress,
+        len: usize,
+        bytes: Vec<u8>,
+    ) -> Result<Array<T>, Error> {
+        if bytes.len() != Array::<T>::size(len) as usize {
+            let size = bytes.len();
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "Array.new: expected buf size {} got {}",
+                    Array::<T>::size(len),
+                    size
+                ),
+            ));
+        }
+
+        // arrays always start at a byte boundary
+        if offset.bit != 0 {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "Array.new: array should not have a bit offset got {}",
+                    offset.bit
+                ),
+            ));
+        }
+
+        let mut elements = Vec::with_capacity(len);
+
+        for i in 0..len {
+            let (address, range) = Array::<T>::position(offset, i);
+            elements.push(T::decode(data_block, address, bytes[range].to_vec())?);
+        }
+
+        Ok(Array {
+            data_block,
+            offset,
+            bytes,
+            changed: vec![false; len],
+            elements,
+        })
+    }
+
+    /// size of `len` elements in bytes
+    pub fn size(len: usize) -> i32 {
+        (len as u32 * T::bit_size()).div_ceil(8) as i32
+    }
+
+    // address of an element and its bytes within the array buffer
+    fn position(offset: BitAddress, index: usize) -> (BitAddress, std::ops::Range<usize>) {
+        let start = index as u32 * T::bit_size();
+        let end = (start + T::bit_size()).div_ceil(8);
+        let address = BitAddress::new(offset.byte + start / 8, (start % 8) as u8);
+
+        (address, (start / 8) as usize..end as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.elements.get(index)
+    }
+
+    /// marks the element as changed
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let element = self.elements.get_mut(index)?;
+        self.changed[index] = true;
+        Some(element)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.elements.iter()
+    }
+
+    /// indexes of the elements borrowed mutably since the array was decoded
+    /// or since the last `reset_changed`
+    pub fn changed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.changed
+            .iter()
+            .enumerate()
+            .filter(|(_, changed)| **changed)
+            .map(|(i, _)| i)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed.iter().any(|changed| *changed)
+    }
+
+    /// call after the array has been written
+    pub fn reset_changed(&mut self) {
+        self.changed.iter_mut().for_each(|changed| *changed = false)
+    }
+}
+
+impl<T: SizedField> Index<usize> for Array<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.elements[index]
+    }
+}
+
+impl<T: SizedField> IndexMut<usize> for Array<T> {
+    /// marks the element as changed
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.changed[index] = true;
+        &mut self.elements[index]
+    }
+}
+
+impl<T: SizedField> Field for Array<T> {
+    fn data_block(&self) -> i32 {
+        self.data_block
+    }
+
+    fn offset(&self) -> i32 {
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = self.bytes.clone();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            let (_, range) = Array::<T>::position(self.offset, i);
+            element.encode_to(&mut buf[range]);
+        }
+        buf
+    }
+}
+
+#[test]
+fn test_array() {
+    let bytes = vec![0, 1, 0xFF, 0xFE, 0x7F, 0xFF];
+    let mut array: Array<Int> = Array::new(888, BitAddress::new(10, 0), 3, bytes).unwrap();
+
+    assert_eq!(3, array.len());
+    assert_eq!(
+        vec![1, -2, 32767],
+        array.iter().map(|i| i.value()).collect::<Vec<i16>>()
+    );
+    assert_eq!(BitAddress::new(12, 0), array[1].bit_address());
+    assert!(!array.is_changed());
+    assert!(array.get(3).is_none());
+
+    array.get_mut(2).unwrap().set_value(-1);
+    assert_eq!(vec![2], array.changed().collect::<Vec<usize>>());
+    assert_eq!(vec![0, 1, 0xFF, 0xFE, 0xFF, 0xFF], array.to_bytes());
+
+    array.reset_changed();
+    assert!(!array.is_changed());
+
+    // invalid buffer size
+    assert!(Array::<Int>::new(888, BitAddress::new(10, 0), 3, vec![0; 5]).is_err());
+    // arrays should not have a bit offset
+    assert!(Array::<Int>::new(888, BitAddress::new(10, 1), 1, vec![0; 2]).is_err());
+}
+
+#[test]
+fn test_bool_array() {
+    // ARRAY[0..9] OF BOOL takes two bytes, bits 2..7 of the second byte are unused
+    assert_eq!(2, Array::<Bool>::size(10));
+    assert_eq!(1, Array::<Bool>::size(8));
+
+    let bytes = vec![0b1000_0001, 0b1111_1110];
+    let mut array: Array<Bool> = Array::new(888, BitAddress::new(4, 0), 10, bytes).unwrap();
+
+    assert!(array[0].value());
+    assert!(!array[1].value());
+    assert!(array[7].value());
+    assert!(!array[8].value());
+    assert!(array[9].value());
+    assert_eq!(BitAddress::new(5, 1), array[9].bit_address());
+
+    array[1].set_value(true);
+    array[7].set_value(false);
+    array[8].set_value(true);
+
+    assert_eq!(vec![1, 7, 8], array.changed().collect::<Vec<usize>>());
+    assert_eq!(vec![0b0000_0011, 0b1111_1111], array.to_bytes());
+}
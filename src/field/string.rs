@@ -0,0 +1,459 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! `STRING` and `WSTRING` fields
+
+use super::{BitAddress, Field};
+use crate::error::Error;
+use byteorder::{BigEndian, ByteOrder};
+
+/// the code page used to encode the characters of a `STRING`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodePage {
+    /// 7 bit ASCII, bytes above 0x7F are rejected
+    Ascii,
+    /// ISO 8859-1, every byte maps to the unicode code point of the same value
+    #[default]
+    Latin1,
+    /// Windows-1252, the default of the TIA portal on western systems
+    Windows1252,
+}
+
+// Windows-1252 characters at 0x80..=0x9F, the unassigned bytes map to the C1 controls
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl CodePage {
+    fn decode(self, b: u8) -> Option<char> {
+        match self {
+            CodePage::Ascii if b > 0x7F => None,
+            CodePage::Windows1252 if (0x80..=0x9F).contains(&b) => {
+                Some(WINDOWS_1252[(b - 0x80) as usize])
+            }
+            _ => Some(b as char),
+        }
+    }
+
+    fn encode(self, c: char) -> Option<u8> {
+        match self {
+            CodePage::Ascii if c.is_ascii() => Some(c as u8),
+            CodePage::Ascii => None,
+            CodePage::Latin1 if (c as u32) <= 0xFF => Some(c as u8),
+            CodePage::Latin1 => None,
+            CodePage::Windows1252 => match WINDOWS_1252.iter().position(|w| *w == c) {
+                Some(i) => Some(0x80 + i as u8),
+                None if (c as u32) <= 0xFF && !(0x80..=0x9F).contains(&(c as u32)) => Some(c as u8),
+                None => None,
+            },
+        }
+    }
+}
+
+/// PLC `STRING[n]` field
+/// one byte max length, one byte current length and `n` bytes of characters
+///
+/// # Examples
+///
+/// ```
+/// use s7::field::{BitAddress, Field, S7String};
+///
+/// let bytes = vec![10, 5, b'B', b'0', b'1', b'2', b'3', 0, 0, 0, 0, 0];
+/// assert_eq!(S7String::size(10) as usize, bytes.len());
+///
+/// let mut batch = S7String::new(888, BitAddress::new(8, 0), bytes).unwrap();
+/// assert_eq!("B0123", batch.value());
+///
+/// batch.set_value("B0124").unwrap();
+/// assert!(batch.set_value("longer than ten").is_err());
+/// ```
+#[derive(Debug)]
+pub struct S7String {
+    data_block: i32,
+    offset: BitAddress,
+    max_length: u8,
+    code_page: CodePage,
+    value: String,
+}
+
+impl S7String {
+    /// the characters are decoded as `CodePage::Latin1`
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<S7String, Error> {
+        S7String::with_code_page(data_block, offset, bytes, CodePage::default())
+    }
+
+    pub fn with_code_page(
+        data_block: i32,
+        offset: BitAddress,
+        bytes: Vec<u8>,
+        code_page: CodePage,
+    ) -> Result<S7String, Error> {
+        if bytes.len() < 2 {
+            let len = bytes.len();
+            return Err(Error::TryFrom(
+                bytes,
+                format!("S7String.new: expected at least 2 header bytes got {}", len),
+            ));
+        }
+
+        let (max_length, length) = (bytes[0], bytes[1]);
+
+        if bytes.len() != S7String::size(max_length) as usize {
+            let len = bytes.len();
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7String.new: expected buf size {} got {}",
+                    S7String::size(max_length),
+                    len
+                ),
+            ));
+        }
+
+        if length > max_length {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7String.new: length {} is over the max length {}",
+                    length, max_length
+                ),
+            ));
+        }
+
+        if offset.bit != 0 {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7String.new: string should not have a bit offset got {}",
+                    offset.bit
+                ),
+            ));
+        }
+
+        let mut value = String::with_capacity(length as usize);
+
+        for &b in bytes[2..2 + length as usize].iter() {
+            match code_page.decode(b) {
+                Some(c) => value.push(c),
+                None => {
+                    return Err(Error::TryFrom(
+                        bytes,
+                        format!("S7String.new: invalid {:?} character {:#04x}", code_page, b),
+                    ))
+                }
+            }
+        }
+
+        Ok(S7String {
+            data_block,
+            offset,
+            max_length,
+            code_page,
+            value,
+        })
+    }
+
+    /// size of a `STRING[max_length]` in bytes
+    pub fn size(max_length: u8) -> i32 {
+        max_length as i32 + 2
+    }
+
+    pub fn max_length(&self) -> u8 {
+        self.max_length
+    }
+
+    pub fn code_page(&self) -> CodePage {
+        self.code_page
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// fails if the value is longer than the max length
+    /// or has characters that are not in the code page
+    pub fn set_value(&mut self, v: &str) -> Result<(), Error> {
+        let mut length = 0;
+
+        for c in v.chars() {
+            if self.code_page.encode(c).is_none() {
+                return Err(Error::InvalidInput {
+                    input: format!(
+                        "S7String.set_value: {:?} is not a {:?} character",
+                        c, self.code_page
+                    ),
+                });
+            }
+            length += 1;
+        }
+
+        if length > self.max_length as usize {
+            return Err(Error::InvalidInput {
+                input: format!(
+                    "S7String.set_value: length {} is over the max length {}",
+                    length, self.max_length
+                ),
+            });
+        }
+
+        self.value = v.to_string();
+        Ok(())
+    }
+}
+
+impl Field for S7String {
+    fn data_block(&self) -> i32 {
+        self.data_block
+    }
+
+    fn offset(&self) -> i32 {
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; S7String::size(self.max_length) as usize];
+        buf[0] = self.max_length;
+
+        // set_value guarantees that every character is encodable
+        let mut length = 0;
+        for c in self.value.chars() {
+            buf[2 + length] = self.code_page.encode(c).unwrap_or(b'?');
+            length += 1;
+        }
+        buf[1] = length as u8;
+        buf
+    }
+}
+
+/// PLC `WSTRING[n]` field
+/// two bytes max length, two bytes current length and `n` UTF-16 characters
+///
+/// # Examples
+///
+/// ```
+/// use s7::field::{BitAddress, Field, S7WString};
+///
+/// let bytes = vec![0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// assert_eq!(S7WString::size(4) as usize, bytes.len());
+///
+/// let mut recipe = S7WString::new(888, BitAddress::new(8, 0), bytes).unwrap();
+/// recipe.set_value("Käse").unwrap();
+///
+/// assert_eq!(vec![0, 4, 0, 4, 0, 75, 0, 228, 0, 115, 0, 101], recipe.to_bytes());
+/// ```
+#[derive(Debug)]
+pub struct S7WString {
+    data_block: i32,
+    offset: BitAddress,
+    max_length: u16,
+    value: String,
+}
+
+impl S7WString {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<S7WString, Error> {
+        if bytes.len() < 4 {
+            let len = bytes.len();
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7WString.new: expected at least 4 header bytes got {}",
+                    len
+                ),
+            ));
+        }
+
+        let max_length = BigEndian::read_u16(&bytes[0..]);
+        let length = BigEndian::read_u16(&bytes[2..]);
+
+        if bytes.len() != S7WString::size(max_length) as usize {
+            let len = bytes.len();
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7WString.new: expected buf size {} got {}",
+                    S7WString::size(max_length),
+                    len
+                ),
+            ));
+        }
+
+        if length > max_length {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7WString.new: length {} is over the max length {}",
+                    length, max_length
+                ),
+            ));
+        }
+
+        if offset.bit != 0 {
+            return Err(Error::TryFrom(
+                bytes,
+                format!(
+                    "S7WString.new: string should not have a bit offset got {}",
+                    offset.bit
+                ),
+            ));
+        }
+
+        let units: Vec<u16> = bytes[4..4 + length as usize * 2]
+            .chunks(2)
+            .map(BigEndian::read_u16)
+            .collect();
+
+        let value = match String::from_utf16(&units) {
+            Ok(value) => value,
+            Err(e) => return Err(Error::TryFrom(bytes, format!("S7WString.new: {}", e))),
+        };
+
+        Ok(S7WString {
+            data_block,
+            offset,
+            max_length,
+            value,
+        })
+    }
+
+    /// size of a `WSTRING[max_length]` in bytes
+    pub fn size(max_length: u16) -> i32 {
+        max_length as i32 * 2 + 4
+    }
+
+    pub fn max_length(&self) -> u16 {
+        self.max_length
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// fails if the value needs more UTF-16 characters than the max length
+    pub fn set_value(&mut self, v: &str) -> Result<(), Error> {
+        let length = v.encode_utf16().count();
+
+        if length > self.max_length as usize {
+            return Err(Error::InvalidInput {
+                input: format!(
+                    "S7WString.set_value: length {} is over the max length {}",
+                    length, self.max_length
+                ),
+            });
+        }
+
+        self.value = v.to_string();
+        Ok(())
+    }
+}
+
+impl Field for S7WString {
+    fn data_block(&self) -> i32 {
+        self.data_block
+    }
+
+    fn offset(&self) -> i32 {
+        self.offset.byte as i32
+    }
+
+    fn bit_address(&self) -> BitAddress {
+        self.offset
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; S7WString::size(self.max_length) as usize];
+        BigEndian::write_u16(&mut buf[0..], self.max_length);
+
+        let mut length = 0;
+        for unit in self.value.encode_utf16() {
+            BigEndian::write_u16(&mut buf[4 + length * 2..], unit);
+            length += 1;
+        }
+        BigEndian::write_u16(&mut buf[2..], length as u16);
+        buf
+    }
+}
+
+#[test]
+fn test_string() {
+    let offset = BitAddress::new(8, 0);
+    let bytes = vec![6, 3, b'a', b'b', b'c', 0, 0, 0];
+    let mut field = S7String::new(888, offset, bytes.clone()).unwrap();
+
+    assert_eq!("abc", field.value());
+    assert_eq!(6, field.max_length());
+    assert_eq!(bytes, field.to_bytes());
+
+    field.set_value("abcdef").unwrap();
+    assert_eq!(
+        vec![6, 6, b'a', b'b', b'c', b'd', b'e', b'f'],
+        field.to_bytes()
+    );
+
+    field.set_value("").unwrap();
+    assert_eq!(vec![6, 0, 0, 0, 0, 0, 0, 0], field.to_bytes());
+
+    // over the max length
+    assert!(field.set_value("abcdefg").is_err());
+    assert_eq!("", field.value());
+
+    // current length over the max length
+    assert!(S7String::new(888, offset, vec![2, 3, 0, 0]).is_err());
+    // buffer doesn't match the max length
+    assert!(S7String::new(888, offset, vec![4, 0, 0, 0]).is_err());
+    assert!(S7String::new(888, offset, vec![4]).is_err());
+}
+
+#[test]
+fn test_string_code_page() {
+    let offset = BitAddress::new(8, 0);
+
+    let field = S7String::new(888, offset, vec![2, 2, 0xE9, 0x80]).unwrap();
+    assert_eq!("é\u{80}", field.value());
+
+    let mut field =
+        S7String::with_code_page(888, offset, vec![2, 2, 0xE9, 0x80], CodePage::Windows1252)
+            .unwrap();
+    assert_eq!("é€", field.value());
+
+    field.set_value("€Š").unwrap();
+    assert_eq!(vec![2, 2, 0x80, 0x8A], field.to_bytes());
+    assert!(field.set_value("日").is_err());
+
+    assert!(S7String::with_code_page(888, offset, vec![2, 1, 0xE9, 0], CodePage::Ascii).is_err());
+
+    let mut field =
+        S7String::with_code_page(888, offset, vec![2, 0, 0, 0], CodePage::Ascii).unwrap();
+    assert!(field.set_value("é").is_err());
+    field.set_value("ok").unwrap();
+}
+
+#[test]
+fn test_wstring() {
+    let offset = BitAddress::new(8, 0);
+    let bytes = vec![0, 3, 0, 2, 0x00, 0xE4, 0xD8, 0x3D, 0xDE, 0x00];
+
+    // "ä" and the first half of a surrogate pair
+    assert!(S7WString::new(888, offset, bytes).is_err());
+
+    let bytes = vec![0, 3, 0, 3, 0x00, 0xE4, 0xD8, 0x3D, 0xDE, 0x00];
+    let mut field = S7WString::new(888, offset, bytes.clone()).unwrap();
+    assert_eq!("ä😀", field.value());
+    assert_eq!(bytes, field.to_bytes());
+
+    field.set_value("abc").unwrap();
+    assert_eq!(vec![0, 3, 0, 3, 0, 97, 0, 98, 0, 99], field.to_bytes());
+
+    // the emoji takes 2 UTF-16 characters
+    assert!(field.set_value("ab😀").is_err());
+
+    assert!(S7WString::new(888, offset, vec![0, 1, 0, 2, 0, 0]).is_err());
+    assert!(S7WString::new(888, offset, vec![0, 2, 0, 0, 0, 0]).is_err());
+}
@@ -0,0 +1,962 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Date and time fields, `DATE`, `TIME`, `TIME_OF_DAY`, `DATE_AND_TIME`, `DTL`, `LTIME` and `S5TIME`
+
+use super::{BitAddress, Field, SizedField};
+use crate::constant::Area;
+use crate::error::Error;
+use byteorder::{BigEndian, ByteOrder};
+#[cfg(feature = "chrono")]
+use std::convert::TryFrom;
+use std::time::Duration;
+
+const MS_PER_DAY: u32 = 86_400_000;
+// days from 1970-01-01 to 1990-01-01, the epoch of `DATE` and `DATE_AND_TIME`
+const DAYS_TO_1990: i64 = 7305;
+// S5TIME time bases in milliseconds
+const S5TIME_BASES: [u64; 4] = [10, 100, 1_000, 10_000];
+
+/// a calendar date and time, as stored by the date fields
+///
+/// # Examples
+///
+/// ```
+/// use s7::field::PlcDateTime;
+///
+/// let dt = PlcDateTime::new(2019, 11, 23, 13, 37, 5, 250_000_000).unwrap();
+/// assert_eq!(7, dt.weekday()); // saturday
+///
+/// assert!(PlcDateTime::new(2019, 2, 29, 0, 0, 0, 0).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PlcDateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+}
+
+impl PlcDateTime {
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        nanosecond: u32,
+    ) -> Result<PlcDateTime, Error> {
+        let err = |what: &str, value: u32| {
+            Err(Error::InvalidInput {
+                input: format!("PlcDateTime.new: invalid {} {}", what, value),
+            })
+        };
+
+        if !(1..=12).contains(&month) {
+            return err("month", month as u32);
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return err("day", day as u32);
+        }
+        if hour > 23 {
+            return err("hour", hour as u32);
+        }
+        if minute > 59 {
+            return err("minute", minute as u32);
+        }
+        if second > 59 {
+            return err("second", second as u32);
+        }
+        if nanosecond > 999_999_999 {
+            return err("nanosecond", nanosecond);
+        }
+
+        Ok(PlcDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            nanosecond,
+        })
+    }
+
+    /// midnight of the given date
+    pub fn from_date(year: u16, month: u8, day: u8) -> Result<PlcDateTime, Error> {
+        PlcDateTime::new(year, month, day, 0, 0, 0, 0)
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub fn nanosecond(&self) -> u32 {
+        self.nanosecond
+    }
+
+    /// day of the week the way the PLC counts it, 1 is sunday and 7 is saturday
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 was a thursday
+        ((self.days_since_unix_epoch() + 4).rem_euclid(7) + 1) as u8
+    }
+
+    fn days_since_unix_epoch(&self) -> i64 {
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = self.year as i64 - if month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let yoe = year - era * 400;
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    fn from_days_since_unix_epoch(days: i64) -> Result<PlcDateTime, Error> {
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        PlcDateTime::from_date(year as u16, month, day)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<PlcDateTime> for chrono::NaiveDateTime {
+    fn from(dt: PlcDateTime) -> Self {
+        // a `PlcDateTime` is always a valid date and time
+        chrono::NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)
+            .and_then(|d| {
+                d.and_hms_nano_opt(
+                    dt.hour as u32,
+                    dt.minute as u32,
+                    dt.second as u32,
+                    dt.nanosecond,
+                )
+            })
+            .expect("PlcDateTime is a valid date and time")
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::NaiveDateTime> for PlcDateTime {
+    type Error = Error;
+
+    fn try_from(dt: chrono::NaiveDateTime) -> Result<Self, Self::Error> {
+        use chrono::{Datelike, Timelike};
+
+        if dt.year() < 0 || dt.year() > u16::MAX as i32 {
+            return Err(Error::InvalidInput {
+                input: format!("PlcDateTime.try_from: invalid year {}", dt.year()),
+            });
+        }
+
+        PlcDateTime::new(
+            dt.year() as u16,
+            dt.month() as u8,
+            dt.day() as u8,
+            dt.hour() as u8,
+            dt.minute() as u8,
+            // leap seconds are folded into the last second
+            dt.second().min(59) as u8,
+            dt.nanosecond().min(999_999_999),
+        )
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn from_bcd(b: u8) -> Option<u8> {
+    let (high, low) = (b >> 4, b & 0x0F);
+    if high > 9 || low > 9 {
+        return None;
+    }
+    Some(high * 10 + low)
+}
+
+fn to_bcd(v: u8) -> u8 {
+    ((v / 10) << 4) | (v % 10)
+}
+
+// validates the buffer size and the bit offset, gives the buffer back
+fn check(name: &str, bytes: Vec<u8>, size: i32, offset: BitAddress) -> Result<Vec<u8>, Error> {
+    let len = bytes.len();
+    if len != size as usize {
+        return Err(Error::TryFrom(
+            bytes,
+            format!("{}.new: expected buf size {} got {}", name, size, len),
+        ));
+    }
+
+    if offset.bit != 0 {
+        return Err(Error::TryFrom(
+            bytes,
+            format!(
+                "{}.new: {} should not have a bit offset got {}",
+                name, name, offset.bit
+            ),
+        ));
+    }
+    Ok(bytes)
+}
+
+macro_rules! impl_field {
+    ($name:ident) => {
+        impl Field for $name {
+            fn data_block(&self) -> i32 {
+                self.data_block
+            }
+
+            fn area(&self) -> Area {
+                self.area
+            }
+
+            fn offset(&self) -> i32 {
+                self.offset.byte as i32
+            }
+
+            fn bit_address(&self) -> BitAddress {
+                self.offset
+            }
+
+            fn to_bytes(&self) -> Vec<u8> {
+                self.encode()
+            }
+        }
+
+        impl SizedField for $name {
+            fn bit_size() -> u32 {
+                $name::size() as u32 * 8
+            }
+
+            fn decode(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Self, Error> {
+                $name::new(data_block, offset, bytes)
+            }
+
+            fn set_area(&mut self, area: Area) {
+                self.area = area
+            }
+        }
+    };
+}
+
+/// PLC `DATE` field, days since 1990-01-01
+#[derive(Debug)]
+pub struct Date {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: PlcDateTime,
+}
+
+impl Date {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Date, Error> {
+        let bytes = check("Date", bytes, Date::size(), offset)?;
+        let days = BigEndian::read_u16(bytes.as_slice()) as i64;
+
+        Ok(Date {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value: PlcDateTime::from_days_since_unix_epoch(DAYS_TO_1990 + days)?,
+        })
+    }
+
+    pub fn size() -> i32 {
+        2
+    }
+
+    pub fn value(&self) -> PlcDateTime {
+        self.value
+    }
+
+    /// the time of day is dropped, the date must be within 1990-01-01 and 2169-06-06
+    pub fn set_value(&mut self, v: PlcDateTime) -> Result<(), Error> {
+        let days = v.days_since_unix_epoch() - DAYS_TO_1990;
+
+        if days < 0 || days > u16::MAX as i64 {
+            return Err(Error::InvalidInput {
+                input: format!("Date.set_value: {:?} is out of range", v),
+            });
+        }
+
+        self.value = PlcDateTime::from_date(v.year, v.month, v.day)?;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let days = (self.value.days_since_unix_epoch() - DAYS_TO_1990) as u16;
+        days.to_be_bytes().to_vec()
+    }
+}
+
+impl_field!(Date);
+
+/// PLC `TIME` field, signed duration in milliseconds
+#[derive(Debug)]
+pub struct Time {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: i32,
+}
+
+impl Time {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Time, Error> {
+        let bytes = check("Time", bytes, Time::size(), offset)?;
+
+        Ok(Time {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value: BigEndian::read_i32(bytes.as_slice()),
+        })
+    }
+
+    pub fn size() -> i32 {
+        4
+    }
+
+    /// milliseconds
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, v: i32) {
+        self.value = v
+    }
+
+    /// `None` if the time is negative
+    pub fn duration(&self) -> Option<Duration> {
+        if self.value < 0 {
+            return None;
+        }
+        Some(Duration::from_millis(self.value as u64))
+    }
+
+    /// fails if the duration is over `i32::MAX` milliseconds, about 24 days
+    pub fn set_duration(&mut self, v: Duration) -> Result<(), Error> {
+        if v.as_millis() > i32::MAX as u128 {
+            return Err(Error::InvalidInput {
+                input: format!("Time.set_duration: {:?} is out of range", v),
+            });
+        }
+        self.value = v.as_millis() as i32;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
+    }
+}
+
+impl_field!(Time);
+
+/// PLC `LTIME` field, signed duration in nanoseconds
+#[derive(Debug)]
+pub struct LTime {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: i64,
+}
+
+impl LTime {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<LTime, Error> {
+        let bytes = check("LTime", bytes, LTime::size(), offset)?;
+
+        Ok(LTime {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value: BigEndian::read_i64(bytes.as_slice()),
+        })
+    }
+
+    pub fn size() -> i32 {
+        8
+    }
+
+    /// nanoseconds
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, v: i64) {
+        self.value = v
+    }
+
+    /// `None` if the time is negative
+    pub fn duration(&self) -> Option<Duration> {
+        if self.value < 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(self.value as u64))
+    }
+
+    /// fails if the duration is over `i64::MAX` nanoseconds, about 292 years
+    pub fn set_duration(&mut self, v: Duration) -> Result<(), Error> {
+        if v.as_nanos() > i64::MAX as u128 {
+            return Err(Error::InvalidInput {
+                input: format!("LTime.set_duration: {:?} is out of range", v),
+            });
+        }
+        self.value = v.as_nanos() as i64;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
+    }
+}
+
+impl_field!(LTime);
+
+/// PLC `TIME_OF_DAY` field, milliseconds since midnight
+#[derive(Debug)]
+pub struct TimeOfDay {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: u32,
+}
+
+impl TimeOfDay {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<TimeOfDay, Error> {
+        let bytes = check("TimeOfDay", bytes, TimeOfDay::size(), offset)?;
+        let value = BigEndian::read_u32(bytes.as_slice());
+
+        if value >= MS_PER_DAY {
+            return Err(Error::TryFrom(
+                bytes,
+                format!("TimeOfDay.new: {} ms is past midnight", value),
+            ));
+        }
+
+        Ok(TimeOfDay {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value,
+        })
+    }
+
+    pub fn size() -> i32 {
+        4
+    }
+
+    /// time since midnight
+    pub fn value(&self) -> Duration {
+        Duration::from_millis(self.value as u64)
+    }
+
+    /// fails if the value is a day or longer, the precision is milliseconds
+    pub fn set_value(&mut self, v: Duration) -> Result<(), Error> {
+        if v.as_millis() >= MS_PER_DAY as u128 {
+            return Err(Error::InvalidInput {
+                input: format!("TimeOfDay.set_value: {:?} is past midnight", v),
+            });
+        }
+        self.value = v.as_millis() as u32;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
+    }
+}
+
+impl_field!(TimeOfDay);
+
+/// PLC `S5TIME` field, 3 BCD digits and a time base of 10ms, 100ms, 1s or 10s
+#[derive(Debug)]
+pub struct S5Time {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: Duration,
+}
+
+impl S5Time {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<S5Time, Error> {
+        let bytes = check("S5Time", bytes, S5Time::size(), offset)?;
+
+        let value = match decode_s5time(BigEndian::read_u16(bytes.as_slice())) {
+            Some(value) => value,
+            None => {
+                return Err(Error::TryFrom(
+                    bytes,
+                    "S5Time.new: invalid BCD value".to_string(),
+                ))
+            }
+        };
+
+        Ok(S5Time {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value,
+        })
+    }
+
+    pub fn size() -> i32 {
+        2
+    }
+
+    pub fn value(&self) -> Duration {
+        self.value
+    }
+
+    /// the smallest time base that fits the value is used and the remainder is dropped,
+    /// fails if the value is over 9990 seconds
+    pub fn set_value(&mut self, v: Duration) -> Result<(), Error> {
+        let raw = encode_s5time(v)?;
+        // keep what the PLC will see
+        self.value = decode_s5time(raw).unwrap_or(v);
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        // set_value guarantees the value is in range
+        encode_s5time(self.value)
+            .unwrap_or_default()
+            .to_be_bytes()
+            .to_vec()
+    }
+}
+
+impl_field!(S5Time);
+
+/// decodes a raw `S5TIME` word, `None` on invalid BCD digits
+pub(crate) fn decode_s5time(raw: u16) -> Option<Duration> {
+    let base = S5TIME_BASES[((raw >> 12) & 0x03) as usize];
+    let hundreds = ((raw >> 8) & 0x0F) as u64;
+    let tens_ones = from_bcd(raw as u8)? as u64;
+
+    if hundreds > 9 {
+        return None;
+    }
+
+    Some(Duration::from_millis((hundreds * 100 + tens_ones) * base))
+}
+
+/// encodes a raw `S5TIME` word, with the smallest time base that fits the duration
+pub(crate) fn encode_s5time(v: Duration) -> Result<u16, Error> {
+    let ms = v.as_millis();
+
+    if ms > 999 * S5TIME_BASES[3] as u128 {
+        return Err(Error::InvalidInput {
+            input: format!("S5Time: {:?} is over 9990s", v),
+        });
+    }
+
+    let (i, base) = S5TIME_BASES
+        .iter()
+        .enumerate()
+        .find(|(_, base)| ms / **base as u128 <= 999)
+        .unwrap_or((3, &S5TIME_BASES[3]));
+    let count = (ms / *base as u128) as u16;
+
+    Ok(((i as u16) << 12) | ((count / 100) << 8) | to_bcd((count % 100) as u8) as u16)
+}
+
+/// PLC `DATE_AND_TIME` field, 8 BCD bytes from 1990 to 2089 with millisecond precision
+#[derive(Debug)]
+pub struct DateAndTime {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: PlcDateTime,
+}
+
+impl DateAndTime {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<DateAndTime, Error> {
+        let bytes = check("DateAndTime", bytes, DateAndTime::size(), offset)?;
+
+        let mut digits = [0u8; 7];
+        for i in 0..digits.len() {
+            match from_bcd(bytes[i]) {
+                Some(d) => digits[i] = d,
+                None => {
+                    let b = bytes[i];
+                    return Err(Error::TryFrom(
+                        bytes,
+                        format!("DateAndTime.new: invalid BCD byte {:#04x} at {}", b, i),
+                    ));
+                }
+            }
+        }
+
+        let ms_low = bytes[7] >> 4;
+        if ms_low > 9 {
+            return Err(Error::TryFrom(
+                bytes,
+                format!("DateAndTime.new: invalid BCD milliseconds digit {}", ms_low),
+            ));
+        }
+
+        let year = match digits[0] {
+            90..=99 => 1900 + digits[0] as u16,
+            _ => 2000 + digits[0] as u16,
+        };
+        let ms = digits[6] as u32 * 10 + ms_low as u32;
+
+        let value = match PlcDateTime::new(
+            year,
+            digits[1],
+            digits[2],
+            digits[3],
+            digits[4],
+            digits[5],
+            ms * 1_000_000,
+        ) {
+            Ok(value) => value,
+            Err(e) => {
+                return Err(Error::TryFrom(bytes, format!("DateAndTime.new: {}", e)));
+            }
+        };
+
+        Ok(DateAndTime {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value,
+        })
+    }
+
+    pub fn size() -> i32 {
+        8
+    }
+
+    pub fn value(&self) -> PlcDateTime {
+        self.value
+    }
+
+    /// the year must be within 1990 and 2089, the nanoseconds are truncated to milliseconds
+    pub fn set_value(&mut self, v: PlcDateTime) -> Result<(), Error> {
+        if !(1990..=2089).contains(&v.year) {
+            return Err(Error::InvalidInput {
+                input: format!("DateAndTime.set_value: year {} is out of range", v.year),
+            });
+        }
+        self.value = PlcDateTime {
+            nanosecond: v.nanosecond / 1_000_000 * 1_000_000,
+            ..v
+        };
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let v = &self.value;
+        let ms = v.nanosecond / 1_000_000;
+
+        vec![
+            to_bcd((v.year % 100) as u8),
+            to_bcd(v.month),
+            to_bcd(v.day),
+            to_bcd(v.hour),
+            to_bcd(v.minute),
+            to_bcd(v.second),
+            to_bcd((ms / 10) as u8),
+            (((ms % 10) as u8) << 4) | v.weekday(),
+        ]
+    }
+}
+
+impl_field!(DateAndTime);
+
+// the years a DTL can hold
+const DTL_YEARS: std::ops::RangeInclusive<u16> = 1970..=2262;
+
+/// PLC `DTL` field, 12 bytes date and time with nanosecond precision
+#[derive(Debug)]
+pub struct Dtl {
+    data_block: i32,
+    area: Area,
+    offset: BitAddress,
+    value: PlcDateTime,
+}
+
+impl Dtl {
+    pub fn new(data_block: i32, offset: BitAddress, bytes: Vec<u8>) -> Result<Dtl, Error> {
+        let bytes = check("Dtl", bytes, Dtl::size(), offset)?;
+
+        let value = match PlcDateTime::new(
+            BigEndian::read_u16(&bytes[0..]),
+            bytes[2],
+            bytes[3],
+            bytes[5],
+            bytes[6],
+            bytes[7],
+            BigEndian::read_u32(&bytes[8..]),
+        ) {
+            Ok(value) => value,
+            Err(e) => return Err(Error::TryFrom(bytes, format!("Dtl.new: {}", e))),
+        };
+
+        // like `set_value`, so a value read can be written back
+        if !DTL_YEARS.contains(&value.year) {
+            let reason = format!("Dtl.new: year {} is out of range", value.year);
+            return Err(Error::TryFrom(bytes, reason));
+        }
+
+        Ok(Dtl {
+            data_block,
+            area: Area::DataBausteine,
+            offset,
+            value,
+        })
+    }
+
+    pub fn size() -> i32 {
+        12
+    }
+
+    pub fn value(&self) -> PlcDateTime {
+        self.value
+    }
+
+    /// the year must be within 1970 and 2262
+    pub fn set_value(&mut self, v: PlcDateTime) -> Result<(), Error> {
+        if !DTL_YEARS.contains(&v.year) {
+            return Err(Error::InvalidInput {
+                input: format!("Dtl.set_value: year {} is out of range", v.year),
+            });
+        }
+        self.value = v;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let v = &self.value;
+        let mut buf = vec![0u8; Dtl::size() as usize];
+
+        BigEndian::write_u16(&mut buf[0..], v.year);
+        buf[2] = v.month;
+        buf[3] = v.day;
+        buf[4] = v.weekday();
+        buf[5] = v.hour;
+        buf[6] = v.minute;
+        buf[7] = v.second;
+        BigEndian::write_u32(&mut buf[8..], v.nanosecond);
+        buf
+    }
+}
+
+impl_field!(Dtl);
+
+#[test]
+fn test_date() {
+    let offset = BitAddress::new(8, 0);
+
+    let mut field = Date::new(888, offset, vec![0, 0]).unwrap();
+    assert_eq!(PlcDateTime::from_date(1990, 1, 1).unwrap(), field.value());
+
+    // 2019-11-23 is 10918 days after 1990-01-01
+    field
+        .set_value(PlcDateTime::new(2019, 11, 23, 13, 37, 0, 0).unwrap())
+        .unwrap();
+    assert_eq!(10918u16.to_be_bytes().to_vec(), field.to_bytes());
+    assert_eq!(PlcDateTime::from_date(2019, 11, 23).unwrap(), field.value());
+
+    let field = Date::new(888, offset, vec![0xFF, 0xFF]).unwrap();
+    assert_eq!(PlcDateTime::from_date(2169, 6, 6).unwrap(), field.value());
+
+    let mut field = Date::new(888, offset, vec![0, 0]).unwrap();
+    assert!(field
+        .set_value(PlcDateTime::from_date(1989, 12, 31).unwrap())
+        .is_err());
+}
+
+#[test]
+fn test_time() {
+    let offset = BitAddress::new(8, 0);
+
+    let mut field = Time::new(888, offset, vec![0, 0, 0x03, 0xE8]).unwrap();
+    assert_eq!(1000, field.value());
+    assert_eq!(Some(Duration::from_secs(1)), field.duration());
+
+    field.set_value(-1);
+    assert_eq!(None, field.duration());
+    assert_eq!(vec![0xFF; 4], field.to_bytes());
+
+    field.set_duration(Duration::from_millis(1500)).unwrap();
+    assert_eq!(vec![0, 0, 0x05, 0xDC], field.to_bytes());
+    assert!(field.set_duration(Duration::from_secs(30 * 86400)).is_err());
+
+    let mut field = LTime::new(888, offset, vec![0, 0, 0, 0, 0, 0, 0x03, 0xE8]).unwrap();
+    assert_eq!(Some(Duration::from_micros(1)), field.duration());
+    field.set_duration(Duration::from_secs(1)).unwrap();
+    assert_eq!(1_000_000_000, field.value());
+
+    let mut field = TimeOfDay::new(888, offset, vec![0x02, 0xCA, 0x1C, 0x80]).unwrap();
+    assert_eq!(Duration::from_secs(13 * 3600), field.value());
+    assert!(field.set_value(Duration::from_secs(86400)).is_err());
+    field.set_value(Duration::from_millis(1)).unwrap();
+    assert_eq!(vec![0, 0, 0, 1], field.to_bytes());
+
+    assert!(TimeOfDay::new(888, offset, MS_PER_DAY.to_be_bytes().to_vec()).is_err());
+}
+
+#[test]
+fn test_s5time() {
+    let offset = BitAddress::new(8, 0);
+
+    // time base 1s, 127
+    let mut field = S5Time::new(888, offset, vec![0x21, 0x27]).unwrap();
+    assert_eq!(Duration::from_secs(127), field.value());
+
+    field.set_value(Duration::from_millis(2500)).unwrap();
+    // 250 * 10ms
+    assert_eq!(vec![0x02, 0x50], field.to_bytes());
+
+    field.set_value(Duration::from_secs(9990)).unwrap();
+    assert_eq!(vec![0x39, 0x99], field.to_bytes());
+
+    // 1234.5s is 123 * 10s
+    field.set_value(Duration::from_millis(1_234_500)).unwrap();
+    assert_eq!(Duration::from_secs(1230), field.value());
+
+    assert!(field.set_value(Duration::from_secs(9991)).is_err());
+    assert!(S5Time::new(888, offset, vec![0x0A, 0x00]).is_err());
+    assert!(S5Time::new(888, offset, vec![0x00, 0x1A]).is_err());
+}
+
+#[test]
+fn test_date_and_time() {
+    let offset = BitAddress::new(8, 0);
+    let bytes = vec![0x19, 0x11, 0x23, 0x13, 0x37, 0x05, 0x25, 0x07];
+
+    let field = DateAndTime::new(888, offset, bytes.clone()).unwrap();
+    assert_eq!(
+        PlcDateTime::new(2019, 11, 23, 13, 37, 5, 250_000_000).unwrap(),
+        field.value()
+    );
+    assert_eq!(bytes, field.to_bytes());
+
+    let field = DateAndTime::new(888, offset, vec![0x90, 1, 1, 0, 0, 0, 0, 0x02]).unwrap();
+    assert_eq!(1990, field.value().year());
+
+    let mut field = DateAndTime::new(888, offset, bytes).unwrap();
+    assert!(field
+        .set_value(PlcDateTime::from_date(2090, 1, 1).unwrap())
+        .is_err());
+
+    // only the milliseconds are sent
+    field
+        .set_value(PlcDateTime::new(2024, 2, 29, 23, 59, 59, 999_999_999).unwrap())
+        .unwrap();
+    assert_eq!(
+        PlcDateTime::new(2024, 2, 29, 23, 59, 59, 999_000_000).unwrap(),
+        field.value()
+    );
+    assert_eq!(
+        field.value(),
+        DateAndTime::new(888, offset, field.to_bytes())
+            .unwrap()
+            .value()
+    );
+
+    // invalid BCD
+    assert!(DateAndTime::new(888, offset, vec![0x1A, 1, 1, 0, 0, 0, 0, 0]).is_err());
+    assert!(DateAndTime::new(888, offset, vec![0x19, 1, 1, 0, 0, 0, 0, 0xA0]).is_err());
+    // invalid date
+    assert!(DateAndTime::new(888, offset, vec![0x19, 0x02, 0x29, 0, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_dtl() {
+    let offset = BitAddress::new(8, 0);
+    let bytes = vec![0x07, 0xE3, 11, 23, 7, 13, 37, 5, 0x0E, 0xE6, 0xB2, 0x80];
+
+    let mut field = Dtl::new(888, offset, bytes.clone()).unwrap();
+    assert_eq!(
+        PlcDateTime::new(2019, 11, 23, 13, 37, 5, 250_000_000).unwrap(),
+        field.value()
+    );
+    assert_eq!(bytes, field.to_bytes());
+
+    field
+        .set_value(PlcDateTime::new(2000, 2, 29, 23, 59, 59, 999_999_999).unwrap())
+        .unwrap();
+    assert_eq!(
+        vec![0x07, 0xD0, 2, 29, 3, 23, 59, 59, 0x3B, 0x9A, 0xC9, 0xFF],
+        field.to_bytes()
+    );
+
+    // month 13
+    assert!(Dtl::new(888, offset, vec![0x07, 0xE3, 13, 1, 1, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    // 1969 and 2263
+    assert!(Dtl::new(888, offset, vec![0x07, 0xB1, 1, 1, 4, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    assert!(Dtl::new(888, offset, vec![0x08, 0xD7, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_calendar() {
+    let dt = PlcDateTime::from_date(1970, 1, 1).unwrap();
+    assert_eq!(0, dt.days_since_unix_epoch());
+    assert_eq!(5, dt.weekday());
+
+    for days in [-1, 0, 59, 365, 7305, 10918, 73_000].iter() {
+        let dt = PlcDateTime::from_days_since_unix_epoch(*days).unwrap();
+        assert_eq!(*days, dt.days_since_unix_epoch());
+    }
+
+    assert!(PlcDateTime::new(2000, 2, 29, 0, 0, 0, 0).is_ok());
+    assert!(PlcDateTime::new(1900, 2, 29, 0, 0, 0, 0).is_err());
+    assert!(PlcDateTime::new(2000, 4, 31, 0, 0, 0, 0).is_err());
+    assert!(PlcDateTime::new(2000, 1, 1, 24, 0, 0, 0).is_err());
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn test_chrono() {
+    let dt = PlcDateTime::new(2019, 11, 23, 13, 37, 5, 250_000_000).unwrap();
+    let naive = chrono::NaiveDateTime::from(dt);
+
+    assert_eq!("2019-11-23 13:37:05.250", naive.to_string());
+    assert_eq!(dt, PlcDateTime::try_from(naive).unwrap());
+}
@@ -0,0 +1,332 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Maps rust structs to the layout of a UDT in a non-optimized data block
+//!
+//! The offsets follow the rules of the PLC
+//! * `BOOL`s are packed one bit after the other
+//! * `BYTE`, `CHAR`, `SINT` and `USINT` start at the next byte
+//! * everything else, `STRING`s, arrays and structs start at the next even byte
+//! * arrays and structs take up an even number of bytes
+//!
+//! With the `derive` feature `#[derive(S7Struct)]` implements `S7Struct` and `Member`
+//! for structs with named fields, `STRING` fields need their max length
+//!
+//! ```ignore
+//! use s7::layout::S7Struct;
+//!
+//! #[derive(S7Struct)]
+//! struct Motor {
+//!     running: bool,        // 0.0
+//!     fault: bool,          // 0.1
+//!     speed: i16,           // 2
+//!     #[s7(string = 10)]
+//!     name: String,         // 4
+//!     setpoints: [f32; 2],  // 16
+//! }
+//! ```
+
+use super::client::Client;
+use super::error::Error;
+use super::field::{BitAddress, Field, S7String};
+use super::transport::Transport;
+use std::convert::TryFrom;
+
+#[cfg(feature = "derive")]
+pub use s7_derive::S7Struct;
+
+/// keeps track of the next free offset while the members of a struct are placed
+/// all offsets are in bits from the start of the struct
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    bits: usize,
+}
+
+impl Layout {
+    pub fn new() -> Layout {
+        Layout { bits: 0 }
+    }
+
+    /// places a `BOOL` right after the previous one
+    pub fn bit(&mut self) -> usize {
+        self.bits += 1;
+        self.bits - 1
+    }
+
+    /// places `size` bytes at the next byte
+    pub fn bytes(&mut self, size: usize) -> usize {
+        let offset = self.bits.next_multiple_of(8);
+        self.bits = offset + size * 8;
+        offset
+    }
+
+    /// places `size` bytes at the next even byte
+    pub fn words(&mut self, size: usize) -> usize {
+        let offset = self.align();
+        self.bits = offset + size * 8;
+        offset
+    }
+
+    /// moves to the next even byte
+    pub fn align(&mut self) -> usize {
+        self.bits = self.bits.next_multiple_of(16);
+        self.bits
+    }
+
+    /// size in bytes padded to an even byte
+    pub fn size(&self) -> usize {
+        self.bits.next_multiple_of(16) / 8
+    }
+}
+
+/// a type that can be a member of an `S7Struct`
+pub trait Member: Sized {
+    /// reserves the member in the layout and returns its offset in bits
+    fn place(layout: &mut Layout) -> usize;
+
+    /// `bytes` is the whole struct
+    fn decode(bytes: &[u8], offset: usize) -> Result<Self, Error>;
+
+    fn encode(&self, bytes: &mut [u8], offset: usize) -> Result<(), Error>;
+}
+
+impl Member for bool {
+    fn place(layout: &mut Layout) -> usize {
+        layout.bit()
+    }
+
+    fn decode(bytes: &[u8], offset: usize) -> Result<Self, Error> {
+        Ok(bytes[offset / 8] & (1 << (offset % 8)) != 0)
+    }
+
+    fn encode(&self, bytes: &mut [u8], offset: usize) -> Result<(), Error> {
+        if *self {
+            bytes[offset / 8] |= 1 << (offset % 8);
+        } else {
+            bytes[offset / 8] &= !(1 << (offset % 8));
+        }
+        Ok(())
+    }
+}
+
+// implements `Member` for a big endian number
+macro_rules! number_member {
+    ($($type:ty, $size:expr, $place:ident);*) => {
+        $(
+            impl Member for $type {
+                fn place(layout: &mut Layout) -> usize {
+                    layout.$place($size)
+                }
+
+                fn decode(bytes: &[u8], offset: usize) -> Result<Self, Error> {
+                    let mut buf = [0u8; $size];
+                    buf.copy_from_slice(&bytes[offset / 8..offset / 8 + $size]);
+                    Ok(<$type>::from_be_bytes(buf))
+                }
+
+                fn encode(&self, bytes: &mut [u8], offset: usize) -> Result<(), Error> {
+                    bytes[offset / 8..offset / 8 + $size].copy_from_slice(&self.to_be_bytes());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+number_member!(
+    u8, 1, bytes;
+    i8, 1, bytes;
+    u16, 2, words;
+    i16, 2, words;
+    u32, 4, words;
+    i32, 4, words;
+    u64, 8, words;
+    i64, 8, words;
+    f32, 4, words;
+    f64, 8, words
+);
+
+/// a `CHAR`, the byte is read as ISO 8859-1
+impl Member for char {
+    fn place(layout: &mut Layout) -> usize {
+        layout.bytes(1)
+    }
+
+    fn decode(bytes: &[u8], offset: usize) -> Result<Self, Error> {
+        Ok(bytes[offset / 8] as char)
+    }
+
+    fn encode(&self, bytes: &mut [u8], offset: usize) -> Result<(), Error> {
+        bytes[offset / 8] = u8::try_from(*self).map_err(|_| Error::InvalidInput {
+            input: format!("char: {:?} does not fit in a byte", self),
+        })?;
+        Ok(())
+    }
+}
+
+/// an `ARRAY[0..N-1]`, `BOOL` elements are packed
+impl<T: Member, const N: usize> Member for [T; N] {
+    fn place(layout: &mut Layout) -> usize {
+        let offset = layout.align();
+        for _ in 0..N {
+            T::place(layout);
+        }
+        layout.align();
+        offset
+    }
+
+    // the array starts at an even byte so the elements
+    // are at the same offsets as in a layout of their own
+    fn decode(bytes: &[u8], offset: usize) -> Result<Self, Error> {
+        let mut layout = Layout::new();
+        let mut elements = Vec::with_capacity(N);
+
+        for _ in 0..N {
+            elements.push(T::decode(bytes, offset + T::place(&mut layout))?);
+        }
+
+        match <[T; N]>::try_from(elements) {
+            Ok(elements) => Ok(elements),
+            Err(_) => unreachable!("the array has exactly N elements"),
+        }
+    }
+
+    fn encode(&self, bytes: &mut [u8], offset: usize) -> Result<(), Error> {
+        let mut layout = Layout::new();
+
+        for element in self.iter() {
+            element.encode(bytes, offset + T::place(&mut layout))?;
+        }
+        Ok(())
+    }
+}
+
+/// places a `STRING[max_length]`
+pub fn place_string(layout: &mut Layout, max_length: u8) -> usize {
+    layout.words(S7String::size(max_length) as usize)
+}
+
+pub fn decode_string(bytes: &[u8], offset: usize, max_length: u8) -> Result<String, Error> {
+    let start = offset / 8;
+    let end = start + S7String::size(max_length) as usize;
+    let string = S7String::new(
+        0,
+        BitAddress::new(start as u32, 0),
+        bytes[start..end].to_vec(),
+    )?;
+
+    Ok(string.value().to_string())
+}
+
+/// fails if the value is longer than `max_length`
+pub fn encode_string(
+    value: &str,
+    bytes: &mut [u8],
+    offset: usize,
+    max_length: u8,
+) -> Result<(), Error> {
+    let start = offset / 8;
+    let size = S7String::size(max_length) as usize;
+
+    let mut empty = vec![0u8; size];
+    empty[0] = max_length;
+
+    let mut string = S7String::new(0, BitAddress::new(start as u32, 0), empty)?;
+    string.set_value(value)?;
+
+    bytes[start..start + size].copy_from_slice(string.to_bytes().as_slice());
+    Ok(())
+}
+
+/// used by the generated `from_bytes`
+#[doc(hidden)]
+pub fn check_size(name: &str, bytes: &[u8], size: usize) -> Result<(), Error> {
+    if bytes.len() != size {
+        return Err(Error::TryFrom(
+            bytes.to_vec(),
+            format!(
+                "{}.from_bytes: expected buf size {} got {}",
+                name,
+                size,
+                bytes.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// a struct mapped to a UDT, usually implemented with `#[derive(S7Struct)]`
+pub trait S7Struct: Sized {
+    /// size in bytes, always even
+    fn size() -> usize;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error>;
+
+    /// fails if a value does not fit, ie a string that is too long
+    fn to_bytes(&self) -> Result<Vec<u8>, Error>;
+
+    /// reads the struct from `db_number` at byte `offset`
+    fn read_from<T: Transport>(
+        client: &mut Client<T>,
+        db_number: i32,
+        offset: i32,
+    ) -> Result<Self, Error> {
+        let mut buffer = vec![0u8; Self::size()];
+        client.ag_read(db_number, offset, buffer.len() as i32, &mut buffer)?;
+        Self::from_bytes(&buffer)
+    }
+
+    /// writes the whole struct to `db_number` at byte `offset`
+    fn write_to<T: Transport>(
+        &self,
+        client: &mut Client<T>,
+        db_number: i32,
+        offset: i32,
+    ) -> Result<(), Error> {
+        let mut buffer = self.to_bytes()?;
+        client.ag_write(db_number, offset, buffer.len() as i32, &mut buffer)
+    }
+}
+
+#[test]
+fn test_layout() {
+    let mut layout = Layout::new();
+
+    assert_eq!(0, layout.bit());
+    assert_eq!(1, layout.bit());
+    // bytes start at the next byte, words at the next even byte
+    assert_eq!(8, layout.bytes(1));
+    assert_eq!(16, layout.bytes(1));
+    assert_eq!(32, layout.words(2));
+    assert_eq!(48, layout.bit());
+    assert_eq!(8, layout.size());
+    assert_eq!(64, <[u8; 3]>::place(&mut layout));
+    assert_eq!(96, <[bool; 3]>::place(&mut layout));
+    assert_eq!(14, layout.size());
+}
+
+#[test]
+fn test_members() {
+    let mut bytes = vec![0u8; 12];
+
+    true.encode(&mut bytes, 3).unwrap();
+    (-2i16).encode(&mut bytes, 16).unwrap();
+    [true, false, true].encode(&mut bytes, 32).unwrap();
+    encode_string("ab", &mut bytes, 48, 4).unwrap();
+
+    assert_eq!(
+        vec![8, 0, 0xFF, 0xFE, 0b101, 0, 4, 2, b'a', b'b', 0, 0],
+        bytes
+    );
+    assert!(bool::decode(&bytes, 3).unwrap());
+    assert_eq!(-2, i16::decode(&bytes, 16).unwrap());
+    assert_eq!(
+        [true, false, true],
+        <[bool; 3]>::decode(&bytes, 32).unwrap()
+    );
+    assert_eq!("ab", decode_string(&bytes, 48, 4).unwrap());
+
+    assert!(encode_string("abcde", &mut bytes, 48, 4).is_err());
+    assert!('€'.encode(&mut bytes, 0).is_err());
+}
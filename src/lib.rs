@@ -2,9 +2,22 @@
 // This software may be modified and distributed under the terms
 // of the BSD license. See the LICENSE file for details.
 
+pub mod address;
+#[cfg(feature = "tokio")]
+pub mod async_client;
+#[cfg(feature = "tokio")]
+pub mod async_tcp;
 pub mod client;
-mod constant;
+pub mod codec;
+pub mod constant;
 pub mod error;
 pub mod field;
+pub mod layout;
+pub mod mock;
+pub mod pool;
+pub mod reconnect;
+pub mod replay;
+pub mod server;
 pub mod tcp;
+mod telegram;
 pub mod transport;
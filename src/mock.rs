@@ -0,0 +1,523 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! A transport answering with canned responses, to unit test code using the `Client`
+//!
+//! The expectations queued with `MockTransport::expect` are met in order:
+//! every request is checked against the next expectation and answered with its response.
+//! A request that doesn't match, or that comes when no expectation is left, panics
+//! like a failed assertion.
+
+use super::client::DataItem;
+use super::constant::{self, Area};
+use super::error::Error;
+use super::telegram::{self, ack};
+use super::transport::{self, Connection, SessionInfo, Transport};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(feature = "tokio")]
+use super::transport::AsyncTransport;
+
+type Respond = Box<dyn FnMut(&Request) -> Result<Vec<u8>, Error> + Send>;
+type Predicate = Box<dyn Fn(&Request) -> bool + Send>;
+
+/// a request sent to the mock, decoded
+#[derive(Debug, Clone)]
+pub struct Request {
+    telegram: Vec<u8>,
+    function: Option<u8>,
+    items: Vec<DataItem>,
+}
+
+impl Request {
+    fn decode(telegram: &[u8]) -> Request {
+        let function = match telegram.len() > 17 && telegram[8] == transport::PDU_JOB {
+            true => Some(telegram[17]),
+            false => None,
+        };
+
+        let items = match function {
+            Some(transport::PDU_READ) | Some(transport::PDU_WRITE) => {
+                telegram::decode_items(telegram).unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+
+        Request {
+            telegram: telegram.to_vec(),
+            function,
+            items,
+        }
+    }
+
+    /// the telegram as sent, with the TPKT and COTP header
+    pub fn telegram(&self) -> &[u8] {
+        &self.telegram
+    }
+
+    /// the function of a job, 0x04 read, 0x05 write, 0x28 start, 0x29 stop,
+    /// `None` for the userdata requests like the SZL reads
+    pub fn function(&self) -> Option<u8> {
+        self.function
+    }
+
+    /// the items of a read or write, a write item has the data to write
+    pub fn items(&self) -> &[DataItem] {
+        &self.items
+    }
+}
+
+enum Response {
+    // a read gets zeros, a write and the other jobs succeed
+    Success,
+    Telegram(Vec<u8>),
+    Data(Vec<u8>),
+    Error(i32),
+    With(Respond),
+}
+
+/// a request the mock expects and its response
+///
+/// the area, DB, address and length are matched against every item of the request,
+/// `matching` checks requests with different items
+pub struct Expectation {
+    // `Some(None)` is a userdata request
+    function: Option<Option<u8>>,
+    area: Option<Area>,
+    db_number: Option<i32>,
+    address: Option<i32>,
+    length: Option<i32>,
+    predicate: Option<Predicate>,
+    response: Response,
+}
+
+impl fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("function", &self.function)
+            .field("area", &self.area)
+            .field("db_number", &self.db_number)
+            .field("address", &self.address)
+            .field("length", &self.length)
+            .field("predicate", &self.predicate.is_some())
+            .finish()
+    }
+}
+
+impl Expectation {
+    /// any request
+    pub fn any() -> Expectation {
+        Expectation {
+            function: None,
+            area: None,
+            db_number: None,
+            address: None,
+            length: None,
+            predicate: None,
+            response: Response::Success,
+        }
+    }
+
+    /// a job with the function code
+    pub fn function(function: u8) -> Expectation {
+        Expectation {
+            function: Some(Some(function)),
+            ..Expectation::any()
+        }
+    }
+
+    pub fn read() -> Expectation {
+        Expectation::function(transport::PDU_READ)
+    }
+
+    pub fn write() -> Expectation {
+        Expectation::function(transport::PDU_WRITE)
+    }
+
+    /// a userdata request, ie a SZL read of `cpu_info` or `plc_status`
+    pub fn userdata() -> Expectation {
+        Expectation {
+            function: Some(None),
+            ..Expectation::any()
+        }
+    }
+
+    pub fn area(mut self, area: Area) -> Expectation {
+        self.area = Some(area);
+        self
+    }
+
+    pub fn db_number(mut self, db_number: i32) -> Expectation {
+        self.db_number = Some(db_number);
+        self
+    }
+
+    /// the start of the item, see `DataItem`
+    pub fn address(mut self, address: i32) -> Expectation {
+        self.address = Some(address);
+        self
+    }
+
+    /// the number of elements of the item
+    pub fn length(mut self, length: i32) -> Expectation {
+        self.length = Some(length);
+        self
+    }
+
+    /// the request has to pass the predicate as well
+    pub fn matching<F>(mut self, predicate: F) -> Expectation
+    where
+        F: Fn(&Request) -> bool + Send + 'static,
+    {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// answers with the telegram, its PDU reference is set to the one of the request
+    pub fn returns(mut self, telegram: Vec<u8>) -> Expectation {
+        self.response = Response::Telegram(telegram);
+        self
+    }
+
+    /// answers a read with the data of all its items, one after the other
+    pub fn returns_data(mut self, data: &[u8]) -> Expectation {
+        self.response = Response::Data(data.to_vec());
+        self
+    }
+
+    /// rejects the job with an error class and code like `error::CODE_7_NEED_PASSWORD`
+    pub fn returns_error(mut self, code: i32) -> Expectation {
+        self.response = Response::Error(code);
+        self
+    }
+
+    /// answers with the result of the closure
+    pub fn returns_with<F>(mut self, respond: F) -> Expectation
+    where
+        F: FnMut(&Request) -> Result<Vec<u8>, Error> + Send + 'static,
+    {
+        self.response = Response::With(Box::new(respond));
+        self
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        if let Some(function) = self.function {
+            if function != request.function {
+                return false;
+            }
+        }
+
+        let items = request.items.iter().all(|item| {
+            self.area.map_or(true, |area| area == item.area())
+                && self.db_number.map_or(true, |db| db == item.db_number())
+                && self.address.map_or(true, |address| address == item.start())
+                && self.length.map_or(true, |length| length == item.amount())
+        });
+
+        let item_fields = self.area.is_some()
+            || self.db_number.is_some()
+            || self.address.is_some()
+            || self.length.is_some();
+
+        // a request without items doesn't match the item fields
+        if item_fields && request.items.is_empty() {
+            return false;
+        }
+
+        items && self.predicate.as_ref().map_or(true, |p| p(request))
+    }
+
+    fn respond(&mut self, request: &Request) -> Result<Vec<u8>, Error> {
+        let telegram = request.telegram();
+
+        match &mut self.response {
+            Response::Success => match request.function {
+                Some(transport::PDU_READ) => {
+                    let size: i32 = request.items.iter().map(|i| i.size().unwrap_or(0)).sum();
+                    Ok(read_response(request, &vec![0; size as usize]))
+                }
+                Some(transport::PDU_WRITE) => Ok(ack(
+                    telegram,
+                    0,
+                    &[transport::PDU_WRITE, request.items.len() as u8],
+                    &vec![constant::RES_ITEM_OK; request.items.len()],
+                )),
+                Some(function) => Ok(ack(telegram, 0, &[function, 0], &[])),
+                None => Ok(ack(telegram, 0, &[], &[])),
+            },
+            Response::Telegram(response) => {
+                let mut response = response.clone();
+
+                if let (Ok(reference), true) = (telegram::reference(telegram), response.len() >= 13)
+                {
+                    telegram::set_reference(&mut response, reference);
+                }
+                Ok(response)
+            }
+            Response::Data(data) => Ok(read_response(request, data)),
+            Response::Error(code) => Ok(ack(telegram, *code, &[], &[])),
+            Response::With(respond) => respond(request),
+        }
+    }
+}
+
+// a read response with the data split between the items
+fn read_response(request: &Request, data: &[u8]) -> Vec<u8> {
+    let mut remaining = data;
+    let mut items = Vec::with_capacity(request.items.len());
+
+    for item in request.items.iter() {
+        let size = item.size().unwrap_or(0) as usize;
+        let (bytes, rest) = remaining.split_at(size.min(remaining.len()));
+        remaining = rest;
+        items.push((item.word_len(), Ok(bytes.to_vec())));
+    }
+    telegram::read_ack(request.telegram(), &items)
+}
+
+struct State {
+    session: SessionInfo,
+    conn_type: Connection,
+    expectations: VecDeque<Expectation>,
+    requests: Vec<Request>,
+}
+
+/// clones share the expectations and the recorded requests,
+/// keep one to check them after the client took the transport
+///
+/// # Examples
+///
+/// ```
+/// use s7::client::Client;
+/// use s7::constant::Area;
+/// use s7::mock::{Expectation, MockTransport};
+///
+/// let mock = MockTransport::new();
+/// mock.expect(
+///     Expectation::read()
+///         .area(Area::DataBausteine)
+///         .db_number(888)
+///         .address(8)
+///         .length(4)
+///         .returns_data(&[1, 2, 3, 4]),
+/// );
+/// mock.expect(Expectation::write().area(Area::Merker));
+///
+/// let mut cl = Client::new(mock.clone()).unwrap();
+/// let buffer = &mut vec![0u8; 4];
+/// cl.ag_read(888, 8, 4, buffer).unwrap();
+/// assert_eq!(vec![1, 2, 3, 4], *buffer);
+///
+/// cl.mb_write(0, 1, &mut [0xFF]).unwrap();
+/// assert_eq!([0xFF], mock.requests()[1].items()[0].data());
+///
+/// mock.verify();
+/// ```
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for MockTransport {
+    fn default() -> MockTransport {
+        MockTransport::new()
+    }
+}
+
+impl MockTransport {
+    /// a PDU of 480 and a single job at a time
+    pub fn new() -> MockTransport {
+        MockTransport {
+            state: Arc::new(Mutex::new(State {
+                session: SessionInfo::new(480, 1, 1),
+                conn_type: Connection::PG,
+                expectations: VecDeque::new(),
+                requests: Vec::new(),
+            })),
+        }
+    }
+
+    /// the session the client gets after the negotiation
+    pub fn with_session(self, session: SessionInfo) -> MockTransport {
+        self.lock().session = session;
+        self
+    }
+
+    pub fn with_connection_type(self, conn_type: Connection) -> MockTransport {
+        self.lock().conn_type = conn_type;
+        self
+    }
+
+    /// queues the expectation after the ones not met yet
+    pub fn expect(&self, expectation: Expectation) {
+        self.lock().expectations.push_back(expectation);
+    }
+
+    /// every request sent so far, decoded
+    pub fn requests(&self) -> Vec<Request> {
+        self.lock().requests.clone()
+    }
+
+    /// number of expectations not met yet
+    pub fn pending(&self) -> usize {
+        self.lock().expectations.len()
+    }
+
+    /// panics if an expectation has not been met
+    pub fn verify(&self) {
+        let state = self.lock();
+
+        if !state.expectations.is_empty() {
+            let pending = format!("{:?}", state.expectations);
+            drop(state);
+            panic!("expected requests were not sent: {}", pending);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        telegram::lock(&self.state)
+    }
+
+    fn call(&self, telegram: &[u8]) -> Result<Vec<u8>, Error> {
+        let request = Request::decode(telegram);
+
+        let mut state = self.lock();
+        state.requests.push(request.clone());
+        let expectation = state.expectations.pop_front();
+        drop(state);
+
+        let mut expectation = match expectation {
+            Some(expectation) => expectation,
+            None => panic!("unexpected request {:?}", request),
+        };
+
+        if !expectation.matches(&request) {
+            panic!(
+                "request {:?} doesn't match the expected {:?}",
+                request, expectation
+            );
+        }
+        expectation.respond(&request)
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call(request)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.lock().session.pdu_length() as i32
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.lock().conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.lock().session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.lock().session
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncTransport for MockTransport {
+    async fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call(request)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.lock().session.pdu_length() as i32
+    }
+
+    async fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.lock().conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.lock().session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.lock().session
+    }
+}
+
+#[test]
+fn test_mock() {
+    use super::client::Client;
+    use super::error;
+
+    let mock = MockTransport::new().with_session(SessionInfo::new(240, 1, 1));
+    // split into 2 reads by the pdu
+    mock.expect(
+        Expectation::read()
+            .db_number(1)
+            .address(0)
+            .returns_data(&[1; 222]),
+    );
+    mock.expect(Expectation::read().address(222).length(78));
+    mock.expect(Expectation::write().returns_error(error::CODE_7_NEED_PASSWORD));
+    mock.expect(Expectation::any().returns_with(|request| {
+        assert_eq!(2, request.items().len());
+        Err(Error::Send)
+    }));
+
+    let mut cl = Client::new(mock.clone()).unwrap();
+    assert_eq!(240, cl.session_info().pdu_length());
+
+    let mut buffer = vec![0xFF; 300];
+    cl.ag_read(1, 0, 300, &mut buffer).unwrap();
+    assert_eq!([1; 222], buffer[..222]);
+    assert_eq!([0; 78], buffer[222..]);
+
+    match cl.ag_write(1, 0, 2, &mut [1, 2]) {
+        Err(Error::CPU { code }) => assert_eq!(error::CODE_7_NEED_PASSWORD, code),
+        result => panic!("unexpected {:?}", result),
+    }
+    assert_eq!([1, 2], mock.requests()[2].items()[0].data());
+
+    let mut items = vec![
+        DataItem::new(Area::Merker, 0, 0, constant::WL_BYTE, 1),
+        DataItem::new(Area::Counter, 0, 0, constant::WL_COUNTER, 1),
+    ];
+    assert!(cl.read_multi(&mut items).is_err());
+
+    assert_eq!(4, mock.requests().len());
+    assert_eq!(0, mock.pending());
+    mock.verify();
+}
+
+#[test]
+#[should_panic(expected = "doesn't match")]
+fn test_mock_mismatch() {
+    use super::client::Client;
+
+    let mock = MockTransport::new();
+    mock.expect(Expectation::read().area(Area::Merker));
+
+    let mut cl = Client::new(mock).unwrap();
+    let _ = cl.ag_read(1, 0, 1, &mut [0]);
+}
+
+#[test]
+#[should_panic(expected = "were not sent")]
+fn test_mock_verify() {
+    let mock = MockTransport::new();
+    mock.expect(Expectation::userdata());
+    mock.verify();
+}
@@ -0,0 +1,352 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! A pool of clients connected to the same PLC, shared between threads
+//!
+//! A PLC only has a few connection resources, the pool opens at most
+//! the `max_connections` reported by `Client::cp_info`.
+//! Threads waiting for a client are served in the order they asked for one.
+//! A client whose connection died is dropped with `Lease::discard`,
+//! the pool connects a new one when it's needed.
+
+use super::client::Client;
+use super::error::Error;
+use super::transport::Transport;
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+
+type Connect<T> = Box<dyn FnMut() -> Result<T, Error> + Send>;
+
+struct Shared<T: Transport> {
+    state: Mutex<State<T>>,
+    returned: Condvar,
+    connect: Mutex<Connect<T>>,
+}
+
+struct State<T: Transport> {
+    idle: Vec<Client<T>>,
+    // idle, leased and connecting clients
+    open: usize,
+    // tickets of the threads waiting for a client, first come first served
+    waiting: VecDeque<u64>,
+    next_ticket: u64,
+}
+
+/// # Examples
+///
+/// ```no_run
+/// use std::net::{Ipv4Addr, IpAddr};
+/// use std::time::Duration;
+/// use s7::{pool::Pool, tcp, transport};
+///
+/// let addr = Ipv4Addr::new(127, 0, 0, 1);
+/// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::Basic);
+/// opts.read_timeout = Duration::from_secs(2);
+/// opts.write_timeout = Duration::from_secs(2);
+///
+/// let pool = Pool::new(4, move || tcp::Transport::connect(opts.clone())).unwrap();
+///
+/// let workers: Vec<_> = (0..8)
+///     .map(|i| {
+///         let pool = pool.clone();
+///         std::thread::spawn(move || {
+///             let buffer = &mut vec![0u8; 2];
+///             pool.get().unwrap().ag_read(888, i * 2, 2, buffer).unwrap();
+///         })
+///     })
+///     .collect();
+///
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// ```
+pub struct Pool<T: Transport> {
+    shared: Arc<Shared<T>>,
+    size: usize,
+}
+
+impl<T: Transport> Clone for Pool<T> {
+    fn clone(&self) -> Self {
+        Pool {
+            shared: self.shared.clone(),
+            size: self.size,
+        }
+    }
+}
+
+impl<T: Transport> Pool<T> {
+    /// connects up to `size` clients with `connect`.
+    /// the size is limited to the `max_connections` of the CPU,
+    /// if it doesn't answer `cp_info` the size is used as is
+    pub fn new<F>(size: usize, mut connect: F) -> Result<Pool<T>, Error>
+    where
+        F: FnMut() -> Result<T, Error> + Send + 'static,
+    {
+        if size == 0 {
+            return Err(Error::InvalidInput {
+                input: "pool size must be at least 1".to_string(),
+            });
+        }
+
+        let mut first = Client::new(connect()?)?;
+
+        let size = match first.cp_info() {
+            Ok(info) if info.max_connections() > 0 => size.min(info.max_connections() as usize),
+            _ => size,
+        };
+
+        let mut idle = Vec::with_capacity(size);
+        idle.push(first);
+
+        while idle.len() < size {
+            idle.push(Client::new(connect()?)?);
+        }
+
+        Ok(Pool {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    idle,
+                    open: size,
+                    waiting: VecDeque::new(),
+                    next_ticket: 0,
+                }),
+                returned: Condvar::new(),
+                connect: Mutex::new(Box::new(connect)),
+            }),
+            size,
+        })
+    }
+
+    /// number of connections
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// waits for a free client, the client goes back to the pool when the lease is dropped
+    pub fn get(&self) -> Result<Lease<T>, Error> {
+        let mut state = self.shared.state.lock().map_err(|_| Error::Lock)?;
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.push_back(ticket);
+
+        loop {
+            if state.waiting.front() == Some(&ticket) {
+                if let Some(client) = state.idle.pop() {
+                    state.waiting.pop_front();
+                    // the next one in line may get another idle client
+                    self.shared.returned.notify_all();
+
+                    return Ok(Lease {
+                        client: Some(client),
+                        shared: self.shared.clone(),
+                    });
+                }
+
+                // a discarded client is replaced, without holding up the others
+                if state.open < self.size {
+                    state.waiting.pop_front();
+                    state.open += 1;
+                    self.shared.returned.notify_all();
+                    drop(state);
+
+                    return match self.connect() {
+                        Ok(client) => Ok(Lease {
+                            client: Some(client),
+                            shared: self.shared.clone(),
+                        }),
+                        Err(e) => {
+                            self.shared.close();
+                            Err(e)
+                        }
+                    };
+                }
+            }
+
+            state = self.shared.returned.wait(state).map_err(|_| Error::Lock)?;
+        }
+    }
+
+    fn connect(&self) -> Result<Client<T>, Error> {
+        let mut connect = self.shared.connect.lock().map_err(|_| Error::Lock)?;
+        Client::new(connect()?)
+    }
+}
+
+impl<T: Transport> Shared<T> {
+    // frees the place of a client that is gone
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.open -= 1;
+            self.returned.notify_all();
+        }
+    }
+}
+
+/// a client leased from the pool
+pub struct Lease<T: Transport> {
+    // only taken when dropped or discarded
+    client: Option<Client<T>>,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Transport> Lease<T> {
+    /// drops the client instead of returning it to the pool, ie after an io error,
+    /// a new one is connected for a later `Pool::get`
+    pub fn discard(mut self) {
+        self.client = None;
+        self.shared.close();
+    }
+}
+
+impl<T: Transport> Deref for Lease<T> {
+    type Target = Client<T>;
+
+    fn deref(&self) -> &Client<T> {
+        self.client.as_ref().expect("client taken before drop")
+    }
+}
+
+impl<T: Transport> DerefMut for Lease<T> {
+    fn deref_mut(&mut self) -> &mut Client<T> {
+        self.client.as_mut().expect("client taken before drop")
+    }
+}
+
+impl<T: Transport> Drop for Lease<T> {
+    fn drop(&mut self) {
+        if let (Some(client), Ok(mut state)) = (self.client.take(), self.shared.state.lock()) {
+            state.idle.push(client);
+            self.shared.returned.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+use super::transport::Connection;
+
+#[cfg(test)]
+struct TestTransport {
+    max_connections: u16,
+}
+
+#[cfg(test)]
+impl Transport for TestTransport {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        // cp_info
+        if request[8] == 7 && self.max_connections > 0 {
+            let mut data = [0u8; 12];
+            data[4..6].copy_from_slice(&self.max_connections.to_be_bytes());
+            return Ok(super::telegram::test_szl_response(&data));
+        }
+        Err(Error::Send)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        240
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        Connection::Basic
+    }
+}
+
+#[test]
+fn test_pool_size() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connected = connections.clone();
+    let pool = Pool::new(8, move || {
+        connected.fetch_add(1, Ordering::SeqCst);
+        Ok(TestTransport { max_connections: 3 })
+    })
+    .unwrap();
+
+    assert_eq!(3, pool.size());
+    assert_eq!(3, connections.load(Ordering::SeqCst));
+
+    // no answer to cp_info
+    let pool = Pool::new(2, || Ok(TestTransport { max_connections: 0 })).unwrap();
+    assert_eq!(2, pool.size());
+
+    assert!(Pool::new(0, || Ok(TestTransport { max_connections: 3 })).is_err());
+}
+
+#[test]
+fn test_pool_leases() {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    let pool = Pool::new(2, || Ok(TestTransport { max_connections: 2 })).unwrap();
+
+    let first = pool.get().unwrap();
+    let second = pool.get().unwrap();
+
+    // waits for a lease to be dropped
+    let (tx, rx) = mpsc::channel();
+    let waiting = {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            let lease = pool.get().unwrap();
+            tx.send(()).unwrap();
+            drop(lease);
+        })
+    };
+
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    drop(first);
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiting.join().unwrap();
+
+    drop(second);
+    let mut leases = vec![pool.get().unwrap(), pool.get().unwrap()];
+    assert!(leases[0].mb_read(0, 1, &mut [0]).is_err());
+    leases.clear();
+    assert!(pool.get().is_ok());
+}
+
+#[test]
+fn test_pool_discard() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let unreachable = Arc::new(AtomicBool::new(false));
+
+    let pool = {
+        let connections = connections.clone();
+        let unreachable = unreachable.clone();
+        Pool::new(1, move || {
+            if unreachable.load(Ordering::SeqCst) {
+                return Err(Error::Connect("unreachable".to_string()));
+            }
+            connections.fetch_add(1, Ordering::SeqCst);
+            Ok(TestTransport { max_connections: 1 })
+        })
+        .unwrap()
+    };
+
+    // the connection of the client died
+    let mut lease = pool.get().unwrap();
+    assert!(lease.mb_read(0, 1, &mut [0]).is_err());
+    lease.discard();
+
+    unreachable.store(true, Ordering::SeqCst);
+    assert!(pool.get().is_err());
+
+    // the failed connection doesn't take the place of the client
+    unreachable.store(false, Ordering::SeqCst);
+    let lease = pool.get().unwrap();
+    assert_eq!(2, connections.load(Ordering::SeqCst));
+
+    drop(lease);
+    assert!(pool.get().is_ok());
+    assert_eq!(2, connections.load(Ordering::SeqCst));
+}
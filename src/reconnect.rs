@@ -0,0 +1,439 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Transport that reconnects when the connection is lost
+//!
+//! It wraps any transport and a function that connects a new one.
+//! After an io error the connection is dropped and the next request connects again
+//! and re-runs `negotiate`, waiting between the attempts with a backoff.
+
+use super::error::Error;
+use super::telegram;
+use super::transport::{Connection, SessionInfo, Transport as PackTrait};
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+/// the state of the connection, reported to the callback set with `on_state_change`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// connecting, `attempt` starts at 1
+    Connecting {
+        attempt: u32,
+    },
+    Connected,
+    /// the connection was lost or all the attempts failed,
+    /// the next request connects again
+    Disconnected,
+}
+
+/// exponential backoff between the connection attempts
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// wait after the first failed attempt
+    pub initial: Duration,
+    /// the wait is doubled after every failed attempt up to `max`
+    pub max: Duration,
+    /// `None` tries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl Backoff {
+    /// wait after the failed `attempt`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// a set of options for the reconnecting transport
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub backoff: Backoff,
+    /// reconnects and sends read requests again when the connection is lost while reading,
+    /// writes and control requests are never sent twice
+    pub retry_reads: bool,
+}
+
+type Connect<T> = Box<dyn FnMut() -> Result<T, Error> + Send>;
+type Callback = Box<dyn FnMut(State) + Send>;
+
+/// # Examples
+///
+/// ```no_run
+/// use std::net::{Ipv4Addr, IpAddr};
+/// use std::time::Duration;
+/// use s7::{client, reconnect, tcp, transport};
+///
+/// let addr = Ipv4Addr::new(127, 0, 0, 1);
+/// let mut opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+/// opts.read_timeout = Duration::from_secs(2);
+/// opts.write_timeout = Duration::from_secs(2);
+///
+/// let mut options = reconnect::Options::default();
+/// options.retry_reads = true;
+///
+/// let mut t = reconnect::Transport::connect(options, move || {
+///     tcp::Transport::connect(opts.clone())
+/// })
+/// .unwrap();
+/// t.on_state_change(|state| println!("{:?}", state));
+///
+/// let mut cl = client::Client::new(t).unwrap();
+/// let buffer = &mut vec![0u8; 4];
+///
+/// // keeps working after the PLC restarts
+/// cl.ag_read(888, 8, 4, buffer).unwrap();
+/// ```
+pub struct Transport<T: PackTrait> {
+    options: Options,
+    connect: Connect<T>,
+    inner: Option<T>,
+    state: State,
+    on_state_change: Option<Callback>,
+    // the last negotiated values, kept while disconnected
+    session: SessionInfo,
+    conn_type: Connection,
+}
+
+impl<T: PackTrait> fmt::Debug for Transport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Transport")
+            .field("options", &self.options)
+            .field("state", &self.state)
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl<T: PackTrait> Transport<T> {
+    /// connects with `connect`, retrying with the backoff,
+    /// `negotiate` is left to the client like with the other transports
+    pub fn connect<F>(options: Options, connect: F) -> Result<Transport<T>, Error>
+    where
+        F: FnMut() -> Result<T, Error> + Send + 'static,
+    {
+        let mut transport = Transport {
+            options,
+            connect: Box::new(connect),
+            inner: None,
+            state: State::Disconnected,
+            on_state_change: None,
+            session: SessionInfo::default(),
+            conn_type: Connection::PG,
+        };
+
+        let inner = transport.retry(|connect| connect())?;
+        transport.conn_type = inner.connection_type();
+        transport.inner = Some(inner);
+        Ok(transport)
+    }
+
+    /// `callback` is called on every state change, from the thread sending the request
+    pub fn on_state_change<F>(&mut self, callback: F)
+    where
+        F: FnMut(State) + Send + 'static,
+    {
+        self.on_state_change = Some(Box::new(callback));
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    fn set_state(&mut self, state: State) {
+        if self.state == state {
+            return;
+        }
+
+        self.state = state;
+        if let Some(callback) = self.on_state_change.as_mut() {
+            callback(state);
+        }
+    }
+
+    // runs `attempt` until it succeeds or the attempts run out
+    fn retry<F>(&mut self, mut attempt: F) -> Result<T, Error>
+    where
+        F: FnMut(&mut Connect<T>) -> Result<T, Error>,
+    {
+        let mut n = 1;
+
+        loop {
+            self.set_state(State::Connecting { attempt: n });
+
+            match attempt(&mut self.connect) {
+                Ok(inner) => {
+                    self.set_state(State::Connected);
+                    return Ok(inner);
+                }
+                Err(e) => {
+                    if let Some(max_attempts) = self.options.backoff.max_attempts {
+                        if n >= max_attempts {
+                            self.set_state(State::Disconnected);
+                            return Err(e);
+                        }
+                    }
+                    thread::sleep(self.options.backoff.delay(n));
+                    n += 1;
+                }
+            }
+        }
+    }
+
+    // connects a new transport and negotiates again
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.inner = None;
+
+        let inner = self.retry(|connect| {
+            let mut inner = connect()?;
+            inner.negotiate()?;
+            Ok(inner)
+        })?;
+
+        self.negotiated(inner);
+        Ok(())
+    }
+
+    fn negotiated(&mut self, inner: T) {
+        self.session = inner.session_info();
+        self.conn_type = inner.connection_type();
+        self.inner = Some(inner);
+    }
+
+    fn disconnect(&mut self) {
+        self.inner = None;
+        self.set_state(State::Disconnected);
+    }
+
+    // sends with the current connection, reconnects first if it was lost.
+    // a request is sent again on a new connection only if `retry` is set
+    fn call<R, F>(&mut self, retry: bool, mut send: F) -> Result<R, Error>
+    where
+        F: FnMut(&mut T) -> Result<R, Error>,
+    {
+        let attempts = if retry { 2 } else { 1 };
+        let mut result = Err(Error::Send);
+
+        for _ in 0..attempts {
+            if self.inner.is_none() {
+                self.reconnect()?;
+            }
+
+            result = match self.inner.as_mut() {
+                Some(inner) => send(inner),
+                None => return Err(Error::Send),
+            };
+
+            match &result {
+                Err(e) if is_connection_error(e) => self.disconnect(),
+                _ => return result,
+            }
+        }
+        result
+    }
+}
+
+// errors after which the connection can't be used anymore
+fn is_connection_error(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::IOError(_) | Error::Frame(_) | Error::Connect(_) | Error::Lock
+    )
+}
+
+impl<T: PackTrait> PackTrait for Transport<T> {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let retry = self.options.retry_reads && telegram::is_read(request);
+        self.call(retry, |inner| inner.send(request))
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.session.pdu_length() as i32
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        let result = match self.inner.as_mut() {
+            Some(inner) => inner.negotiate(),
+            None => return self.reconnect(),
+        };
+
+        match result {
+            Ok(()) => {
+                if let Some(inner) = self.inner.take() {
+                    self.negotiated(inner);
+                }
+                Ok(())
+            }
+            Err(e) if is_connection_error(&e) => self.reconnect(),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.session
+    }
+
+    fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let retry = self.options.retry_reads && requests.iter().all(|r| telegram::is_read(r));
+        self.call(retry, |inner| inner.send_all(requests))
+    }
+}
+
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+// fails the sends that are set to fail, the flags are shared by all connections
+#[cfg(test)]
+struct TestTransport {
+    fail: Arc<Mutex<Vec<bool>>>,
+}
+
+#[cfg(test)]
+impl PackTrait for TestTransport {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut fail = self.fail.lock().unwrap();
+
+        if !fail.is_empty() && fail.remove(0) {
+            return Err(Error::IOError(std::io::ErrorKind::ConnectionReset));
+        }
+        Ok(request.to_vec())
+    }
+
+    fn pdu_length(&self) -> i32 {
+        240
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        Connection::OP
+    }
+}
+
+#[cfg(test)]
+fn test_transport(
+    options: Options,
+    refused: usize,
+    fail: Vec<bool>,
+) -> (Transport<TestTransport>, Arc<Mutex<Vec<State>>>) {
+    let fail = Arc::new(Mutex::new(fail));
+    let mut connections = 0;
+
+    let mut transport = Transport::connect(options, move || {
+        connections += 1;
+        if connections <= refused {
+            return Err(Error::Connect("refused".to_string()));
+        }
+        Ok(TestTransport { fail: fail.clone() })
+    })
+    .unwrap();
+
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let recorded = states.clone();
+    transport.on_state_change(move |state| recorded.lock().unwrap().push(state));
+
+    (transport, states)
+}
+
+#[cfg(test)]
+fn test_options(retry_reads: bool) -> Options {
+    Options {
+        backoff: Backoff {
+            initial: Duration::from_millis(0),
+            max: Duration::from_millis(0),
+            max_attempts: Some(3),
+        },
+        retry_reads,
+    }
+}
+
+#[test]
+fn test_reconnect() {
+    use crate::constant::{self, Area};
+
+    let chunks = telegram::read_chunks(Area::Merker, 0, 1, constant::WL_BYTE, 240).unwrap();
+    let read = telegram::read_request(Area::Merker, 0, &chunks[0]);
+    let mut write = read.clone();
+    write[17] = 0x05;
+
+    // refused twice before connecting
+    let (mut t, states) = test_transport(test_options(true), 2, vec![true, false, true]);
+    assert_eq!(State::Connected, t.state());
+    t.negotiate().unwrap();
+    assert_eq!(240, t.pdu_length());
+    assert_eq!(Connection::OP, t.connection_type());
+
+    // the read is sent again on a new connection
+    assert_eq!(read, t.send(&read).unwrap());
+    assert_eq!(
+        vec![
+            State::Disconnected,
+            State::Connecting { attempt: 1 },
+            State::Connected
+        ],
+        *states.lock().unwrap()
+    );
+
+    // the write isn't, the next request connects again
+    assert!(t.send(&write).is_err());
+    assert_eq!(State::Disconnected, t.state());
+    assert_eq!(write, t.send(&write).unwrap());
+    assert_eq!(State::Connected, t.state());
+}
+
+#[test]
+fn test_reconnect_attempts() {
+    let (mut t, states) = test_transport(test_options(false), 0, vec![true]);
+    assert!(t.send(&[0; 20]).is_err());
+
+    // the connection is lost, the next connections are refused
+    let mut connections = 0;
+    t.connect = Box::new(move || {
+        connections += 1;
+        Err(Error::Connect(format!("refused {}", connections)))
+    });
+
+    match t.send(&[0; 20]) {
+        Err(Error::Connect(e)) => assert_eq!("refused 3", e),
+        result => panic!("{:?}", result),
+    }
+    assert_eq!(
+        vec![
+            State::Disconnected,
+            State::Connecting { attempt: 1 },
+            State::Connecting { attempt: 2 },
+            State::Connecting { attempt: 3 },
+            State::Disconnected
+        ],
+        *states.lock().unwrap()
+    );
+
+    let backoff = Backoff::default();
+    assert_eq!(Duration::from_millis(100), backoff.delay(1));
+    assert_eq!(Duration::from_millis(400), backoff.delay(3));
+    assert_eq!(Duration::from_secs(10), backoff.delay(40));
+}
@@ -0,0 +1,524 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Captures the conversation with a PLC and replays it, to turn field captures into tests
+//!
+//! `RecordingTransport` wraps a transport and writes every request and response
+//! to a capture, `ReplayTransport` answers the same requests with the captured responses.
+//! The capture is a text file with a line per telegram, time in microseconds since the epoch:
+//!
+//! ```text
+//! session 480 1 1 1
+//! 1571238000000000 > 0300001f02f080320100000000000e00000401120a10020004...
+//! 1571238000001200 < 0300001d02f080320300000000000200080000040...
+//! 1571238000002000 ! io TimedOut
+//! ```
+//!
+//! The session line has the PDU length, the parallel jobs and the connection type.
+
+use super::error::Error;
+use super::telegram;
+use super::transport::{Connection, SessionInfo, Transport};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "tokio")]
+use super::transport::AsyncTransport;
+
+// bytes of the PDU reference, they differ between runs
+const REFERENCE: std::ops::Range<usize> = 11..13;
+
+/// tees every request and response of the transport to a capture
+///
+/// pipelined requests go through `send_all` of the transport, up to the negotiated AmQ
+/// like without recording, and are captured once all the responses are read.
+/// A failed `send_all` returns no responses, so it's captured as its first request failing,
+/// which is what the client sees and what the replay answers.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::{Ipv4Addr, IpAddr};
+/// use s7::{client, replay, tcp, transport};
+///
+/// let addr = Ipv4Addr::new(127, 0, 0, 1);
+/// let opts = tcp::Options::new(IpAddr::from(addr), 102, 5, 5, transport::Connection::PG);
+/// let t = tcp::Transport::connect(opts).unwrap();
+///
+/// let t = replay::RecordingTransport::create(t, "plc.capture").unwrap();
+/// let mut cl = client::Client::new(t).unwrap();
+///
+/// let buffer = &mut vec![0u8; 4];
+/// cl.ag_read(888, 8, 4, buffer).unwrap();
+/// ```
+pub struct RecordingTransport<T: Transport, W: Write = BufWriter<File>> {
+    inner: T,
+    capture: W,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// records into a new file at `path`, an existing one is truncated
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> Result<RecordingTransport<T>, Error> {
+        let file = File::create(path)?;
+        Ok(RecordingTransport::new(inner, BufWriter::new(file)))
+    }
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, capture: W) -> RecordingTransport<T, W> {
+        RecordingTransport { inner, capture }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// the transport and the capture, flushed
+    pub fn into_inner(mut self) -> Result<(T, W), Error> {
+        self.capture.flush()?;
+        Ok((self.inner, self.capture))
+    }
+
+    // `sent` is the time the request was sent, the response is timed when it's recorded
+    fn record(
+        &mut self,
+        sent: u128,
+        request: &[u8],
+        response: Result<&[u8], &Error>,
+    ) -> Result<(), Error> {
+        writeln!(self.capture, "{} > {}", sent, hex(request))?;
+
+        match response {
+            Ok(response) => writeln!(self.capture, "{} < {}", now(), hex(response))?,
+            Err(Error::IOError(kind)) => writeln!(self.capture, "{} ! io {:?}", now(), kind)?,
+            Err(e) => writeln!(self.capture, "{} ! {}", now(), e)?,
+        }
+
+        // a capture of a crashing program keeps all the exchanges
+        self.capture.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Transport, W: Write> Transport for RecordingTransport<T, W> {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let sent = now();
+        let response = self.inner.send(request);
+        self.record(sent, request, response.as_deref())?;
+        response
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.inner.pdu_length()
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        self.inner.negotiate()?;
+
+        let session = self.inner.session_info();
+        writeln!(
+            self.capture,
+            "session {} {} {} {}",
+            session.pdu_length(),
+            session.amq_calling(),
+            session.amq_called(),
+            self.inner.connection_type() as u8
+        )?;
+        self.capture.flush()?;
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.inner.connection_type()
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.inner.max_jobs()
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.inner.session_info()
+    }
+
+    fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let sent = now();
+        let result = self.inner.send_all(requests);
+
+        match &result {
+            Ok(responses) => {
+                for (request, response) in requests.iter().zip(responses.iter()) {
+                    self.record(sent, request, Ok(response))?;
+                }
+            }
+            Err(e) => {
+                if let Some(request) = requests.first() {
+                    self.record(sent, request, Err(e))?;
+                }
+            }
+        }
+        result
+    }
+}
+
+// a captured request and its response or error
+#[derive(Debug)]
+struct Exchange {
+    request: Vec<u8>,
+    response: Result<Vec<u8>, String>,
+}
+
+/// answers the requests with the responses of a capture, in order
+///
+/// a request that differs from the captured one, other than by its PDU reference,
+/// fails with `Error::Replay`
+///
+/// # Examples
+///
+/// ```no_run
+/// use s7::{client, replay};
+///
+/// let t = replay::ReplayTransport::open("plc.capture").unwrap();
+/// let mut cl = client::Client::new(t).unwrap();
+///
+/// let buffer = &mut vec![0u8; 4];
+/// cl.ag_read(888, 8, 4, buffer).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ReplayTransport {
+    session: SessionInfo,
+    conn_type: Connection,
+    exchanges: Vec<Exchange>,
+    next: usize,
+}
+
+impl ReplayTransport {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ReplayTransport, Error> {
+        ReplayTransport::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<ReplayTransport, Error> {
+        let mut replay = ReplayTransport {
+            session: SessionInfo::default(),
+            conn_type: Connection::PG,
+            exchanges: Vec::new(),
+            next: 0,
+        };
+
+        for (n, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let invalid = || Error::Replay(format!("invalid capture line {}: {}", n + 1, line));
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                [] => {}
+                ["session", pdu_length, amq_calling, amq_called, conn_type] => {
+                    let number = |s: &str| s.parse::<u16>().map_err(|_| invalid());
+
+                    replay.session = SessionInfo::new(
+                        number(pdu_length)?,
+                        number(amq_calling)?,
+                        number(amq_called)?,
+                    );
+                    replay.conn_type = match number(conn_type)? {
+                        1 => Connection::PG,
+                        2 => Connection::OP,
+                        3 => Connection::Basic,
+                        _ => return Err(invalid()),
+                    };
+                }
+                [_, ">", request] => replay.exchanges.push(Exchange {
+                    request: unhex(request).ok_or_else(invalid)?,
+                    response: Err("no response captured".to_string()),
+                }),
+                [_, "<", response] => {
+                    let exchange = replay.exchanges.last_mut().ok_or_else(invalid)?;
+                    exchange.response = Ok(unhex(response).ok_or_else(invalid)?);
+                }
+                [_, "!", error @ ..] => {
+                    let exchange = replay.exchanges.last_mut().ok_or_else(invalid)?;
+                    exchange.response = Err(error.join(" "));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// number of captured requests not sent yet
+    pub fn remaining(&self) -> usize {
+        self.exchanges.len() - self.next
+    }
+
+    fn replay(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let exchange = match self.exchanges.get(self.next) {
+            Some(exchange) => exchange,
+            None => {
+                return Err(Error::Replay(format!(
+                    "request {} is not in the capture",
+                    self.next + 1
+                )))
+            }
+        };
+
+        if !same_request(&exchange.request, request) {
+            return Err(Error::Replay(format!(
+                "request {} differs from the capture, sent {} captured {}",
+                self.next + 1,
+                hex(request),
+                hex(&exchange.request)
+            )));
+        }
+        self.next += 1;
+
+        match &exchange.response {
+            Ok(response) => {
+                let mut response = response.clone();
+
+                if let (Ok(reference), true) = (
+                    telegram::reference(request),
+                    response.len() >= REFERENCE.end,
+                ) {
+                    telegram::set_reference(&mut response, reference);
+                }
+                Ok(response)
+            }
+            Err(error) => Err(captured_error(error)),
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.replay(request)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.session.pdu_length() as i32
+    }
+
+    fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.session
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncTransport for ReplayTransport {
+    async fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.replay(request)
+    }
+
+    fn pdu_length(&self) -> i32 {
+        self.session.pdu_length() as i32
+    }
+
+    async fn negotiate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn connection_type(&self) -> Connection {
+        self.conn_type
+    }
+
+    fn max_jobs(&self) -> u16 {
+        self.session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.session
+    }
+}
+
+// the requests are the same but for the PDU reference
+fn same_request(captured: &[u8], request: &[u8]) -> bool {
+    if captured.len() != request.len() {
+        return false;
+    }
+
+    captured
+        .iter()
+        .zip(request.iter())
+        .enumerate()
+        .all(|(i, (a, b))| REFERENCE.contains(&i) || a == b)
+}
+
+// the io errors are replayed as they were, the others only as text
+fn captured_error(error: &str) -> Error {
+    let kind = match error.strip_prefix("io ") {
+        Some(kind) => kind,
+        None => return Error::Replay(format!("captured error: {}", error)),
+    };
+
+    Error::IOError(match kind {
+        "TimedOut" => ErrorKind::TimedOut,
+        "WouldBlock" => ErrorKind::WouldBlock,
+        "UnexpectedEof" => ErrorKind::UnexpectedEof,
+        "ConnectionReset" => ErrorKind::ConnectionReset,
+        "ConnectionAborted" => ErrorKind::ConnectionAborted,
+        "ConnectionRefused" => ErrorKind::ConnectionRefused,
+        "BrokenPipe" => ErrorKind::BrokenPipe,
+        "NotConnected" => ErrorKind::NotConnected,
+        _ => ErrorKind::Other,
+    })
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_micros())
+        .unwrap_or(0)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 == 1 || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[test]
+fn test_record_replay() {
+    use super::client::Client;
+    use super::mock::{Expectation, MockTransport};
+
+    let mock = MockTransport::new().with_session(SessionInfo::new(240, 2, 2));
+    mock.expect(Expectation::read().returns_data(&[1; 222]));
+    mock.expect(Expectation::read().returns_data(&[2; 78]));
+    mock.expect(Expectation::write());
+    mock.expect(Expectation::read().returns_with(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        Err(Error::IOError(ErrorKind::TimedOut))
+    }));
+
+    let path = std::env::temp_dir().join(format!("s7-{}.capture", std::process::id()));
+    let t = RecordingTransport::create(mock, &path).unwrap();
+    let mut cl = Client::new(t).unwrap();
+    let mut buffer = vec![0u8; 300];
+    cl.ag_read(1, 0, 300, &mut buffer).unwrap();
+    cl.mb_write(0, 1, &mut [1]).unwrap();
+    assert!(cl.mb_read(0, 1, &mut [0]).is_err());
+
+    drop(cl);
+    let capture = std::fs::read_to_string(&path).unwrap();
+    assert!(capture.starts_with("session 240 2 2 1\n"));
+    assert_eq!(9, capture.lines().count());
+
+    // the request is timed when it's sent
+    let times: Vec<u128> = capture
+        .lines()
+        .skip(7)
+        .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+        .collect();
+    assert!(times[1] - times[0] >= 20_000);
+
+    let t = ReplayTransport::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(4, t.remaining());
+    let mut cl = Client::new(t).unwrap();
+    assert_eq!(SessionInfo::new(240, 2, 2), cl.session_info());
+
+    let mut replayed = vec![0u8; 300];
+    cl.ag_read(1, 0, 300, &mut replayed).unwrap();
+    assert_eq!(buffer, replayed);
+
+    match cl.mb_write(0, 1, &mut [2]) {
+        Err(Error::Replay(_)) => {}
+        result => panic!("unexpected {:?}", result),
+    }
+    cl.mb_write(0, 1, &mut [1]).unwrap();
+
+    match cl.mb_read(0, 1, &mut [0]) {
+        Err(Error::IOError(ErrorKind::TimedOut)) => {}
+        result => panic!("unexpected {:?}", result),
+    }
+    // the capture is over
+    assert!(cl.mb_read(0, 1, &mut [0]).is_err());
+
+    assert!(ReplayTransport::from_reader("1 < 00".as_bytes()).is_err());
+    assert!(ReplayTransport::from_reader("1 > 0".as_bytes()).is_err());
+}
+
+#[test]
+fn test_record_send_all() {
+    // pipelines the batches and echoes the requests, fails the batches of 2
+    struct Pipelined {
+        batches: Vec<usize>,
+    }
+
+    impl Transport for Pipelined {
+        fn send(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+            Ok(request.to_vec())
+        }
+
+        fn pdu_length(&self) -> i32 {
+            240
+        }
+
+        fn negotiate(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn connection_type(&self) -> Connection {
+            Connection::PG
+        }
+
+        fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+            self.batches.push(requests.len());
+
+            match requests.len() {
+                2 => Err(Error::IOError(ErrorKind::TimedOut)),
+                _ => Ok(requests.to_vec()),
+            }
+        }
+    }
+
+    let requests = vec![
+        telegram::negotiate_request(240, 3, 3),
+        telegram::negotiate_request(480, 3, 3),
+        telegram::negotiate_request(960, 3, 3),
+    ];
+    let mut t = RecordingTransport::new(Pipelined { batches: vec![] }, Vec::new());
+    assert_eq!(requests, t.send_all(&requests).unwrap());
+    assert!(t.send_all(&requests[..2]).is_err());
+    assert_eq!(vec![3, 2], t.inner().batches);
+
+    // every exchange of the batch, then the failed batch as its first request
+    let (_, capture) = t.into_inner().unwrap();
+    let capture = String::from_utf8(capture).unwrap();
+    let lines: Vec<&str> = capture.lines().collect();
+    assert_eq!(8, lines.len());
+    assert!(lines[5].ends_with(&format!("< {}", hex(&requests[2]))));
+    assert!(lines[6].ends_with(&format!("> {}", hex(&requests[0]))));
+    assert!(lines[7].ends_with("! io TimedOut"));
+
+    let mut replay = ReplayTransport::from_reader(capture.as_bytes()).unwrap();
+    assert_eq!(
+        requests,
+        Transport::send_all(&mut replay, &requests).unwrap()
+    );
+    assert!(Transport::send_all(&mut replay, &requests[..2]).is_err());
+    assert_eq!(0, replay.remaining());
+}
@@ -0,0 +1,1205 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! An in-process S7 server simulating a PLC, to test without hardware
+//!
+//! It accepts ISO on TCP connections and answers the telegrams of the client:
+//! the COTP connection, the PDU negotiation, reads and writes of all the areas,
+//! the SZL reads of `cpu_info`, `cp_info` and `plc_status`, and start/stop.
+//! The areas are kept in a `Memory` shared by all the connections.
+//!
+//! `Server::inject` scripts a `Fault` for the next matching request,
+//! to test the error handling and retries of the client.
+
+use super::codec::{self, Decoder, COTP_CR, COTP_DT, COTP_EOT, ISO_HEADER_SIZE, TPKT_HEADER_SIZE};
+use super::constant::{self, Area, CpuStatus};
+use super::error::{self, Error};
+use super::telegram::{self, ack, lock};
+use super::transport;
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// TPKT header + COTP connection request without parameters
+const COTP_CR_MIN_LENGTH: usize = 11;
+// S7 userdata header + SZL response parameters
+const USERDATA_HEADER_SIZE: usize = 22;
+// SZL ids answered by the server
+const SZL_CPU_INFO: u16 = 0x001C;
+const SZL_CP_INFO: u16 = 0x0131;
+const SZL_CPU_STATUS: u16 = 0x0424;
+
+/// the areas of the simulated PLC
+///
+/// inputs, outputs and merkers are sized in bytes, counters and timers
+/// in elements of 2 bytes, data blocks have to be added
+#[derive(Debug, Clone)]
+pub struct Memory {
+    inputs: Vec<u8>,
+    outputs: Vec<u8>,
+    merkers: Vec<u8>,
+    counters: Vec<u8>,
+    timers: Vec<u8>,
+    data_blocks: BTreeMap<u16, Vec<u8>>,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Memory {
+    /// 256 bytes of inputs, outputs and merkers, 256 counters and timers, no data blocks
+    pub fn new() -> Memory {
+        Memory {
+            inputs: vec![0; 256],
+            outputs: vec![0; 256],
+            merkers: vec![0; 256],
+            counters: vec![0; 256 * 2],
+            timers: vec![0; 256 * 2],
+            data_blocks: BTreeMap::new(),
+        }
+    }
+
+    /// adds a data block of `size` bytes, replacing the one with the same number
+    pub fn add_db(&mut self, number: u16, size: usize) {
+        self.data_blocks.insert(number, vec![0; size]);
+    }
+
+    /// resizes an area, `size` is the number of counters and timers, bytes for the others
+    pub fn resize(&mut self, area: Area, size: usize) -> Result<(), Error> {
+        let size = match area {
+            Area::Counter | Area::Timer => size * 2,
+            _ => size,
+        };
+
+        match self.area_vec(area, 0) {
+            Some(bytes) if area != Area::DataBausteine => {
+                bytes.resize(size, 0);
+                Ok(())
+            }
+            _ => Err(Error::InvalidInput {
+                input: format!("can't resize {:?}, data blocks are added with add_db", area),
+            }),
+        }
+    }
+
+    /// bytes of an area, `db_number` is only used for data blocks
+    pub fn area(&self, area: Area, db_number: u16) -> Option<&[u8]> {
+        match area {
+            Area::ProcessInput => Some(&self.inputs),
+            Area::ProcessOutput => Some(&self.outputs),
+            Area::Merker => Some(&self.merkers),
+            Area::Counter => Some(&self.counters),
+            Area::Timer => Some(&self.timers),
+            Area::DataBausteine => self.data_blocks.get(&db_number).map(|db| db.as_slice()),
+            Area::Unknown => None,
+        }
+    }
+
+    pub fn area_mut(&mut self, area: Area, db_number: u16) -> Option<&mut [u8]> {
+        self.area_vec(area, db_number)
+            .map(|bytes| bytes.as_mut_slice())
+    }
+
+    fn area_vec(&mut self, area: Area, db_number: u16) -> Option<&mut Vec<u8>> {
+        match area {
+            Area::ProcessInput => Some(&mut self.inputs),
+            Area::ProcessOutput => Some(&mut self.outputs),
+            Area::Merker => Some(&mut self.merkers),
+            Area::Counter => Some(&mut self.counters),
+            Area::Timer => Some(&mut self.timers),
+            Area::DataBausteine => self.data_blocks.get_mut(&db_number),
+            Area::Unknown => None,
+        }
+    }
+
+    // the data of an item, or its return code
+    fn read(&self, item: &Item) -> Result<Vec<u8>, u8> {
+        let bytes = self
+            .area(item.area, item.db_number)
+            .ok_or(error::CODE_7_RES_ITEM_NOT_AVAILABLE as u8)?;
+        let (start, size) = item.range(bytes.len())?;
+
+        match item.bit {
+            Some(bit) => Ok(vec![(bytes[start] >> bit) & 1]),
+            None => Ok(bytes[start..start + size].to_vec()),
+        }
+    }
+
+    fn write(&mut self, item: &Item, data: &[u8]) -> Result<(), u8> {
+        let bytes = self
+            .area_mut(item.area, item.db_number)
+            .ok_or(error::CODE_7_RES_ITEM_NOT_AVAILABLE as u8)?;
+        let (start, size) = item.range(bytes.len())?;
+
+        if data.len() != size {
+            return Err(error::CODE_7_WRITE_DATA_SIZE_MISMATCH as u8);
+        }
+
+        match item.bit {
+            Some(bit) if data[0] & 1 == 1 => bytes[start] |= 1 << bit,
+            Some(bit) => bytes[start] &= !(1 << bit),
+            None => bytes[start..start + size].copy_from_slice(data),
+        }
+        Ok(())
+    }
+}
+
+// the item spec of a read or write request
+#[derive(Debug)]
+struct Item {
+    word_len: i32,
+    amount: usize,
+    db_number: u16,
+    area: Area,
+    address: usize,
+    bit: Option<u8>,
+}
+
+impl Item {
+    fn parse(spec: &[u8]) -> Item {
+        let word_len = spec[3] as i32;
+        let address = (spec[9] as usize) << 16 | (spec[10] as usize) << 8 | spec[11] as usize;
+
+        Item {
+            word_len,
+            amount: BigEndian::read_u16(&spec[4..]) as usize,
+            db_number: BigEndian::read_u16(&spec[6..]),
+            area: Area::from_u8(spec[8]),
+            address,
+            bit: match word_len {
+                constant::WL_BIT => Some((address % 8) as u8),
+                _ => None,
+            },
+        }
+    }
+
+    // first byte and size of the item in an area of `len` bytes
+    fn range(&self, len: usize) -> Result<(usize, usize), u8> {
+        let invalid_size = error::CODE_7_INVALID_TRANSPORT_SIZE as u8;
+
+        let (start, size) = match (self.word_len, self.area) {
+            (constant::WL_BIT, _) if self.amount == 1 => (self.address / 8, 1),
+            (constant::WL_BIT, _) => return Err(invalid_size),
+            (constant::WL_COUNTER, Area::Counter) | (constant::WL_TIMER, Area::Timer) => {
+                (self.address * 2, self.amount * 2)
+            }
+            (_, Area::Counter) | (_, Area::Timer) => return Err(invalid_size),
+            (word_len, _) => match constant::data_size_byte(word_len) {
+                0 => return Err(invalid_size),
+                word_size => (self.address >> 3, self.amount * word_size as usize),
+            },
+        };
+
+        if start + size > len {
+            return Err(error::CODE_7_ADDRESS_OUT_OF_RANGE as u8);
+        }
+        Ok((start, size))
+    }
+}
+
+/// the requests a fault is injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// the next request of any kind
+    Any,
+    /// COTP connection request
+    Connect,
+    /// PDU negotiation
+    Negotiate,
+    Read,
+    Write,
+    /// start and stop
+    Control,
+    /// SZL reads, also made by `plc_status`
+    Szl,
+}
+
+impl Request {
+    fn of(request: &[u8]) -> Request {
+        if request.len() < 19 {
+            return Request::Any;
+        }
+
+        match (request[8], request[17]) {
+            (transport::PDU_JOB, transport::PDU_NEGOTIATE) => Request::Negotiate,
+            (transport::PDU_JOB, transport::PDU_READ) => Request::Read,
+            (transport::PDU_JOB, transport::PDU_WRITE) => Request::Write,
+            (transport::PDU_JOB, transport::PDU_START)
+            | (transport::PDU_JOB, transport::PDU_STOP) => Request::Control,
+            (transport::PDU_USERDATA, _) => Request::Szl,
+            _ => Request::Any,
+        }
+    }
+}
+
+/// what the server does instead of the normal response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// rejects the job with an error class and code like `error::CODE_7_NEED_PASSWORD`,
+    /// a connection request is rejected with a COTP disconnect
+    Error(i32),
+    /// every item of a read or write gets the return code like `error::CODE_7_ADDRESS_OUT_OF_RANGE`
+    ItemError(u8),
+    /// answers after the delay
+    Delay(Duration),
+    /// closes the connection without answering
+    Drop,
+    /// sends the first bytes of the response and closes the connection
+    Truncate(usize),
+    /// sends a TPKT frame longer than the negotiated PDU
+    Oversize,
+    /// sends the response in TCP segments of the size
+    Segments(usize),
+    /// splits the S7 PDU into COTP data units of the size
+    Fragments(usize),
+}
+
+/// the parameters of the simulated PLC
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// largest PDU accepted during the negotiation
+    pub pdu_length: u16,
+    /// parallel jobs accepted during the negotiation
+    pub amq: u16,
+    /// connections accepted at once, reported by `cp_info`
+    pub max_connections: u16,
+    /// status when the server starts
+    pub status: CpuStatus,
+    /// texts reported by `cpu_info`, at most 24 or 32 characters
+    pub as_name: String,
+    pub module_name: String,
+    pub module_type_name: String,
+    pub serial_number: String,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            pdu_length: 480,
+            amq: 3,
+            max_connections: 8,
+            status: CpuStatus::Run,
+            as_name: "S7 SERVER".to_string(),
+            module_name: "CPU 315-2 PN/DP".to_string(),
+            module_type_name: "CPU 315-2 PN/DP".to_string(),
+            serial_number: "S C-X0000000".to_string(),
+        }
+    }
+}
+
+struct Shared {
+    options: Options,
+    memory: Mutex<Memory>,
+    status: Mutex<CpuStatus>,
+    stopped: AtomicBool,
+    // clones of the connected streams to shut them down with the server
+    connections: Mutex<Vec<(u64, TcpStream)>>,
+    faults: Mutex<VecDeque<(Request, Fault)>>,
+}
+
+impl Shared {
+    // the first fault injected for the request
+    fn take_fault(&self, request: Request) -> Option<Fault> {
+        let mut faults = lock(&self.faults);
+        let i = faults
+            .iter()
+            .position(|(r, _)| *r == Request::Any || *r == request)?;
+        faults.remove(i).map(|(_, fault)| fault)
+    }
+}
+
+/// a simulated PLC listening on a local port, stopped when dropped
+///
+/// # Examples
+///
+/// ```
+/// use s7::{client::Client, constant::Area, server, tcp};
+///
+/// let mut memory = server::Memory::new();
+/// memory.add_db(888, 64);
+///
+/// let plc = server::Server::start("127.0.0.1:0", server::Options::default(), memory).unwrap();
+/// let addr = plc.local_addr();
+///
+/// let opts = tcp::Options::builder(addr.ip()).port(addr.port()).build().unwrap();
+/// let mut cl = Client::new(tcp::Transport::connect(opts).unwrap()).unwrap();
+///
+/// cl.ag_write(888, 8, 2, &mut [1, 2]).unwrap();
+/// assert_eq!([1, 2], plc.memory().area(Area::DataBausteine, 888).unwrap()[8..10]);
+/// ```
+pub struct Server {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// listens on `address`, a port of 0 picks a free one
+    pub fn start<A: ToSocketAddrs>(
+        address: A,
+        options: Options,
+        memory: Memory,
+    ) -> Result<Server, Error> {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+
+        let shared = Arc::new(Shared {
+            status: Mutex::new(options.status),
+            options,
+            memory: Mutex::new(memory),
+            stopped: AtomicBool::new(false),
+            connections: Mutex::new(Vec::new()),
+            faults: Mutex::new(VecDeque::new()),
+        });
+
+        let accept = {
+            let shared = shared.clone();
+            thread::spawn(move || accept(shared, listener))
+        };
+
+        Ok(Server {
+            shared,
+            local_addr,
+            accept: Some(accept),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// the areas, locked until the guard is dropped
+    pub fn memory(&self) -> MutexGuard<'_, Memory> {
+        lock(&self.shared.memory)
+    }
+
+    pub fn status(&self) -> CpuStatus {
+        *lock(&self.shared.status)
+    }
+
+    pub fn set_status(&self, status: CpuStatus) {
+        *lock(&self.shared.status) = status;
+    }
+
+    /// number of connected clients
+    pub fn connections(&self) -> usize {
+        lock(&self.shared.connections).len()
+    }
+
+    /// the next request matching `request`, on any connection, gets the fault.
+    /// the faults are used once, in the order they were injected
+    pub fn inject(&self, request: Request, fault: Fault) {
+        lock(&self.shared.faults).push_back((request, fault));
+    }
+
+    /// number of injected faults no request has matched yet
+    pub fn pending_faults(&self) -> usize {
+        lock(&self.shared.faults).len()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+
+        // wakes up the accept loop
+        let _ = TcpStream::connect(self.local_addr);
+
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+
+        for (_, stream) in lock(&self.shared.connections).iter() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn accept(shared: Arc<Shared>, listener: TcpListener) {
+    let mut next_id = 0;
+
+    for stream in listener.incoming() {
+        if shared.stopped.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        let id = next_id;
+        next_id += 1;
+
+        {
+            let mut connections = lock(&shared.connections);
+
+            let clone = match stream.try_clone() {
+                Ok(clone) => clone,
+                Err(_) => continue,
+            };
+
+            // like a PLC out of connection resources
+            if connections.len() >= shared.options.max_connections as usize {
+                let _ = stream.shutdown(Shutdown::Both);
+                continue;
+            }
+            connections.push((id, clone));
+        }
+
+        let registered = Registered {
+            shared: shared.clone(),
+            id,
+        };
+        thread::spawn(move || {
+            // the connection ends with the first io or frame error
+            let _ = serve(&registered.shared, stream);
+        });
+    }
+}
+
+// frees the connection slot, even if the connection thread panics
+struct Registered {
+    shared: Arc<Shared>,
+    id: u64,
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        lock(&self.shared.connections).retain(|(i, _)| *i != self.id);
+    }
+}
+
+fn serve(shared: &Shared, mut stream: TcpStream) -> Result<(), Error> {
+    let mut session = Session::new(shared);
+    let mut decoder = Decoder::new(shared.options.pdu_length as usize);
+
+    loop {
+        let mut frame = decoder.frame_buffer();
+        stream.read_exact(&mut frame)?;
+
+        let length = decoder.frame_length(&frame)?;
+        frame.resize(length, 0);
+        stream.read_exact(&mut frame[TPKT_HEADER_SIZE..])?;
+
+        // the decoder only knows the COTP PDUs sent by a PLC
+        let (response, fault) = if frame[TPKT_HEADER_SIZE + 1] == COTP_CR {
+            // no references and class
+            if frame.len() < COTP_CR_MIN_LENGTH {
+                stream.write_all(&disconnect(&frame))?;
+                let _ = stream.shutdown(Shutdown::Both);
+                return Ok(());
+            }
+
+            match shared.take_fault(Request::Connect) {
+                Some(Fault::Error(_)) => (disconnect(&frame), None),
+                fault => (connection_confirm(&frame), fault),
+            }
+        } else {
+            match decoder.decode(&frame)? {
+                Some(request) => {
+                    let fault = shared.take_fault(Request::of(&request));
+                    let response = session.respond(&request, fault.as_ref());
+                    decoder.set_pdu_length(session.pdu_length);
+                    (response, fault)
+                }
+                None => continue,
+            }
+        };
+
+        if !send(&mut stream, response, fault, session.pdu_length)? {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Ok(());
+        }
+    }
+}
+
+// sends the response with the fault, false if the connection has to be closed
+fn send(
+    stream: &mut TcpStream,
+    mut response: Vec<u8>,
+    fault: Option<Fault>,
+    pdu_length: usize,
+) -> Result<bool, Error> {
+    match fault {
+        Some(Fault::Delay(delay)) => {
+            thread::sleep(delay);
+            stream.write_all(&response)?;
+        }
+        Some(Fault::Drop) => return Ok(false),
+        Some(Fault::Truncate(size)) => {
+            stream.write_all(&response[..size.min(response.len())])?;
+            return Ok(false);
+        }
+        Some(Fault::Oversize) => {
+            let length = pdu_length + ISO_HEADER_SIZE + 1;
+            response.resize(length.max(response.len()), 0);
+            let length = response.len() as u16;
+            BigEndian::write_u16(&mut response[2..], length);
+            stream.write_all(&response)?;
+        }
+        Some(Fault::Segments(size)) => {
+            stream.set_nodelay(true)?;
+
+            for segment in response.chunks(size.max(1)) {
+                stream.write_all(segment)?;
+                stream.flush()?;
+                // gives the client a chance to read every segment on its own
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        Some(Fault::Fragments(size)) if response[TPKT_HEADER_SIZE + 1] == COTP_DT => {
+            let fragments: Vec<&[u8]> = response[ISO_HEADER_SIZE..].chunks(size.max(1)).collect();
+
+            for (i, fragment) in fragments.iter().enumerate() {
+                let mut frame = codec::encode(fragment);
+                // the last data unit has the EOT bit
+                if i + 1 < fragments.len() {
+                    frame[TPKT_HEADER_SIZE + 2] &= !COTP_EOT;
+                }
+                stream.write_all(&frame)?;
+            }
+        }
+        _ => stream.write_all(&response)?,
+    }
+    Ok(true)
+}
+
+// rejects a connection request
+fn disconnect(request: &[u8]) -> Vec<u8> {
+    // COTP disconnect request, references and reason
+    let mut frame = vec![codec::TPKT_VERSION, 0, 0, 11, 6, 0x80, 0, 0, 0, 1, 0];
+    if request.len() >= COTP_CR_MIN_LENGTH {
+        frame[6..8].copy_from_slice(&request[8..10]);
+    }
+    frame
+}
+
+fn connection_confirm(request: &[u8]) -> Vec<u8> {
+    let mut confirm = request.to_vec();
+    confirm[5] = transport::CONFIRM_CONNECTION;
+    // destination reference is the source reference of the request
+    confirm.copy_within(8..10, 6);
+    confirm[8..10].copy_from_slice(&[0, 1]);
+    confirm
+}
+
+// the state of a connection
+struct Session<'a> {
+    shared: &'a Shared,
+    pdu_length: usize,
+    // return code of all the items of the current request
+    item_error: Option<u8>,
+    // SZL data left for the next fragments
+    szl: Vec<u8>,
+    szl_sequence: u8,
+}
+
+impl<'a> Session<'a> {
+    fn new(shared: &'a Shared) -> Session<'a> {
+        Session {
+            shared,
+            pdu_length: shared.options.pdu_length as usize,
+            item_error: None,
+            szl: Vec::new(),
+            szl_sequence: 0,
+        }
+    }
+
+    // the response to the request with the errors of the fault
+    fn respond(&mut self, request: &[u8], fault: Option<&Fault>) -> Vec<u8> {
+        match fault {
+            Some(Fault::Error(code)) if Request::of(request) == Request::Szl => {
+                userdata(request, self.szl_sequence, *code, &[])
+            }
+            Some(Fault::Error(code)) => ack(request, *code, &[], &[]),
+            Some(Fault::ItemError(code)) => {
+                self.item_error = Some(*code);
+                let response = self.handle(request);
+                self.item_error = None;
+                response
+            }
+            _ => self.handle(request),
+        }
+    }
+
+    fn handle(&mut self, request: &[u8]) -> Vec<u8> {
+        if request.len() < 19 || request[7] != 0x32 {
+            return ack(request, error::CODE_7_FUN_NOT_AVAILABLE, &[], &[]);
+        }
+
+        match (request[8], request[17]) {
+            (transport::PDU_JOB, transport::PDU_NEGOTIATE) => self.negotiate(request),
+            (transport::PDU_JOB, transport::PDU_READ) => self.read_var(request),
+            (transport::PDU_JOB, transport::PDU_WRITE) => self.write_var(request),
+            (transport::PDU_JOB, transport::PDU_START) => self.control(request, CpuStatus::Run),
+            (transport::PDU_JOB, transport::PDU_STOP) => self.control(request, CpuStatus::Stop),
+            (transport::PDU_USERDATA, _) => self.userdata(request),
+            _ => ack(request, error::CODE_7_FUN_NOT_AVAILABLE, &[], &[]),
+        }
+    }
+
+    fn negotiate(&mut self, request: &[u8]) -> Vec<u8> {
+        if request.len() < 25 {
+            return ack(request, error::CODE_7_FUN_NOT_AVAILABLE, &[], &[]);
+        }
+
+        let options = &self.shared.options;
+        let amq_calling = BigEndian::read_u16(&request[19..]).min(options.amq).max(1);
+        let amq_called = BigEndian::read_u16(&request[21..]).min(options.amq).max(1);
+        let pdu_length = BigEndian::read_u16(&request[23..]).min(options.pdu_length);
+
+        self.pdu_length = pdu_length as usize;
+
+        let mut params = vec![transport::PDU_NEGOTIATE, 0, 0, 0, 0, 0, 0, 0];
+        BigEndian::write_u16(&mut params[2..], amq_calling);
+        BigEndian::write_u16(&mut params[4..], amq_called);
+        BigEndian::write_u16(&mut params[6..], pdu_length);
+        ack(request, 0, &params, &[])
+    }
+
+    fn read_var(&mut self, request: &[u8]) -> Vec<u8> {
+        let items = match items(request) {
+            Some(items) => items,
+            None => return ack(request, error::CODE_7_FUN_NOT_AVAILABLE, &[], &[]),
+        };
+
+        let memory = lock(&self.shared.memory);
+        let items = items
+            .iter()
+            .map(|item| {
+                let data = self.item_error.map_or_else(|| memory.read(item), Err);
+                (item.word_len, data)
+            })
+            .collect::<Vec<_>>();
+
+        let response = telegram::read_ack(request, &items);
+
+        if response.len() - ISO_HEADER_SIZE > self.pdu_length {
+            return ack(request, error::CODE_7_DATA_OVER_PDU, &[], &[]);
+        }
+        response
+    }
+
+    fn write_var(&mut self, request: &[u8]) -> Vec<u8> {
+        let items = match items(request) {
+            Some(items) => items,
+            None => return ack(request, error::CODE_7_FUN_NOT_AVAILABLE, &[], &[]),
+        };
+
+        let mut memory = lock(&self.shared.memory);
+        let mut offset = 19 + items.len() * constant::SIZE_ITEM_SPEC as usize;
+        let mut codes = Vec::with_capacity(items.len());
+
+        for item in items.iter() {
+            if request.len() < offset + 4 {
+                codes.push(error::CODE_7_WRITE_DATA_SIZE_MISMATCH as u8);
+                continue;
+            }
+
+            let size = telegram::data_size(
+                request[offset + 1],
+                BigEndian::read_u16(&request[offset + 2..]),
+            );
+            let end = (offset + 4 + size).min(request.len());
+
+            let result = match self.item_error {
+                Some(code) => Err(code),
+                None => memory.write(item, &request[offset + 4..end]),
+            };
+
+            codes.push(match result {
+                Ok(()) => constant::RES_ITEM_OK,
+                Err(code) => code,
+            });
+            offset = end + size % 2;
+        }
+
+        ack(
+            request,
+            0,
+            &[transport::PDU_WRITE, items.len() as u8],
+            &codes,
+        )
+    }
+
+    fn control(&mut self, request: &[u8], status: CpuStatus) -> Vec<u8> {
+        let mut current = lock(&self.shared.status);
+
+        let already = match status {
+            CpuStatus::Run => transport::PDU_ALREADY_STARTED,
+            _ => transport::PDU_ALREADY_STOPPED,
+        };
+
+        let result = if *current == status { already } else { 0 };
+        *current = status;
+        ack(request, 0, &[request[17], result], &[])
+    }
+
+    fn userdata(&mut self, request: &[u8]) -> Vec<u8> {
+        // CPU functions, read SZL
+        if request.len() < 25 || request[22] & 0x0F != 4 || request[23] != 1 {
+            return userdata(request, 0, error::CODE_7_FUN_NOT_AVAILABLE, &[]);
+        }
+
+        // the following fragments of the last SZL read
+        if request[21] == 0x12 {
+            return self.szl_fragment(request, Vec::new());
+        }
+
+        if request.len() < 33 {
+            return userdata(request, 0, error::CODE_7_FUN_NOT_AVAILABLE, &[]);
+        }
+
+        let id = BigEndian::read_u16(&request[29..]);
+        let index = BigEndian::read_u16(&request[31..]);
+
+        let (record_length, records) = match self.szl(id) {
+            Some(szl) => szl,
+            None => return userdata(request, 0, error::CODE_7_RES_ITEM_NOT_AVAILABLE1, &[]),
+        };
+
+        // SZL id, index, record length and count before the records
+        let mut header = vec![0u8; 8];
+        BigEndian::write_u16(&mut header[0..], id);
+        BigEndian::write_u16(&mut header[2..], index);
+        BigEndian::write_u16(&mut header[4..], record_length as u16);
+        BigEndian::write_u16(&mut header[6..], (records.len() / record_length) as u16);
+
+        self.szl = records;
+        self.szl_sequence = self.szl_sequence.wrapping_add(1).max(1);
+        self.szl_fragment(request, header)
+    }
+
+    // the next fragment of the SZL data that fits the pdu
+    fn szl_fragment(&mut self, request: &[u8], header: Vec<u8>) -> Vec<u8> {
+        let room = self
+            .pdu_length
+            .saturating_sub(USERDATA_HEADER_SIZE + 4 + header.len() + 1);
+        let size = self.szl.len().min(room.max(1));
+        let last = size == self.szl.len();
+
+        let mut data = vec![constant::RES_ITEM_OK, constant::TS_RES_OCTET as u8, 0, 0];
+        BigEndian::write_u16(&mut data[2..], (header.len() + size) as u16);
+        data.extend(header);
+        data.extend(self.szl.drain(..size));
+
+        let mut response = userdata(request, self.szl_sequence, 0, &data);
+        // more fragments follow
+        if !last {
+            response[26] = 1;
+        }
+        response
+    }
+
+    // record length and records of the SZL answered by the server
+    fn szl(&self, id: u16) -> Option<(usize, Vec<u8>)> {
+        let options = &self.shared.options;
+
+        match id {
+            SZL_CPU_STATUS => {
+                let mut record = vec![0u8; 20];
+                record[3] = *lock(&self.shared.status) as u8;
+                Some((20, record))
+            }
+            SZL_CP_INFO => {
+                let mut record = vec![0u8; 40];
+                BigEndian::write_u16(&mut record[0..], 1);
+                BigEndian::write_u16(&mut record[2..], options.pdu_length);
+                BigEndian::write_u16(&mut record[4..], options.max_connections);
+                Some((40, record))
+            }
+            SZL_CPU_INFO => {
+                let texts = [
+                    (1, options.as_name.as_str()),
+                    (2, options.module_name.as_str()),
+                    (3, ""),
+                    (4, "Original Siemens Equipment"),
+                    (5, options.serial_number.as_str()),
+                    (7, options.module_type_name.as_str()),
+                    (8, ""),
+                    (9, ""),
+                    (10, ""),
+                    (11, ""),
+                ];
+
+                let mut records = Vec::with_capacity(texts.len() * 34);
+
+                for (index, text) in texts.iter() {
+                    let mut record = vec![0u8; 34];
+                    BigEndian::write_u16(&mut record, *index);
+                    let text = &text.as_bytes()[..text.len().min(32)];
+                    record[2..2 + text.len()].copy_from_slice(text);
+                    records.extend(record);
+                }
+                Some((34, records))
+            }
+            _ => None,
+        }
+    }
+}
+
+// the item specs of a read or write request
+fn items(request: &[u8]) -> Option<Vec<Item>> {
+    let count = request[18] as usize;
+    let size = constant::SIZE_ITEM_SPEC as usize;
+
+    if count == 0 || count > constant::MAX_VARS || request.len() < 19 + count * size {
+        return None;
+    }
+
+    Some(
+        request[19..19 + count * size]
+            .chunks(size)
+            .map(Item::parse)
+            .collect(),
+    )
+}
+
+// a response to a SZL read
+fn userdata(request: &[u8], sequence: u8, error: i32, data: &[u8]) -> Vec<u8> {
+    let mut pdu = vec![
+        0x32,
+        transport::PDU_USERDATA,
+        0,
+        0,
+        0,
+        0,
+        0,
+        12,
+        0,
+        0,
+        // parameter head, length, response method, type and group, subfunction
+        0,
+        1,
+        0x12,
+        8,
+        0x12,
+        0x84,
+        1,
+        sequence,
+        // data unit reference, last data unit, error code
+        0,
+        0,
+        0,
+        0,
+    ];
+
+    pdu[4..6].copy_from_slice(&request[11..13]);
+    BigEndian::write_u16(&mut pdu[20..], error as u16);
+
+    match data.is_empty() {
+        true => pdu.extend_from_slice(&[0x0A, 0, 0, 0]),
+        false => pdu.extend_from_slice(data),
+    }
+
+    // the client reads a byte past the SZL data and at least a whole header
+    pdu.push(0);
+    while pdu.len() + 7 < transport::MIN_SZL_FIRST_TELEGRAM {
+        pdu.push(0);
+    }
+
+    let data_length = (pdu.len() - USERDATA_HEADER_SIZE) as u16;
+    BigEndian::write_u16(&mut pdu[8..], data_length);
+    codec::encode(&pdu)
+}
+
+#[cfg(test)]
+fn test_server(options: Options) -> (Server, super::client::Client<super::tcp::Transport>) {
+    use super::client::Client;
+
+    let mut memory = Memory::new();
+    memory.add_db(1, 1024);
+
+    let server = Server::start("127.0.0.1:0", options, memory).unwrap();
+    let client = Client::new(test_transport(&server).unwrap()).unwrap();
+    (server, client)
+}
+
+#[cfg(test)]
+fn test_transport(server: &Server) -> Result<super::tcp::Transport, Error> {
+    use super::tcp;
+
+    let addr = server.local_addr();
+    let options = tcp::Options::builder(addr.ip())
+        .port(addr.port())
+        .read_timeout(Duration::from_secs(2))
+        .pdu_size(960)
+        .amq(8, 8)
+        .build()?;
+    tcp::Transport::connect(options)
+}
+
+#[test]
+fn test_read_write() {
+    use super::client::DataItem;
+    use super::field::BitAddress;
+    use std::time::Duration;
+
+    let (server, mut cl) = test_server(Options::default());
+    assert_eq!(480, cl.session_info().pdu_length());
+    assert_eq!(3, cl.session_info().amq_calling());
+
+    // split into pipelined chunks
+    let mut data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    cl.ag_write(1, 10, 1000, &mut data).unwrap();
+    assert_eq!(
+        data[..],
+        server.memory().area(Area::DataBausteine, 1).unwrap()[10..1010]
+    );
+
+    let mut buffer = vec![0u8; 1000];
+    cl.ag_read(1, 10, 1000, &mut buffer).unwrap();
+    assert_eq!(data, buffer);
+
+    cl.mb_write(4, 2, &mut [1, 2]).unwrap();
+    cl.eb_write(0, 1, &mut [3]).unwrap();
+    cl.ab_write(255, 1, &mut [4]).unwrap();
+    {
+        let memory = server.memory();
+        assert_eq!([1, 2], memory.area(Area::Merker, 0).unwrap()[4..6]);
+        assert_eq!(3, memory.area(Area::ProcessInput, 0).unwrap()[0]);
+        assert_eq!(4, memory.area(Area::ProcessOutput, 0).unwrap()[255]);
+    }
+
+    cl.write_bit(Area::Merker, 0, BitAddress::new(4, 7), true)
+        .unwrap();
+    assert!(cl.read_bit(Area::Merker, 0, BitAddress::new(4, 7)).unwrap());
+    assert_eq!(0x81, server.memory().area(Area::Merker, 0).unwrap()[4]);
+    cl.write_bit(Area::Merker, 0, BitAddress::new(4, 0), false)
+        .unwrap();
+    assert_eq!(0x80, server.memory().area(Area::Merker, 0).unwrap()[4]);
+
+    cl.ct_write(3, &[123, 999]).unwrap();
+    assert_eq!(vec![123, 999], cl.ct_read(3, 2).unwrap());
+
+    let timers = [Duration::from_millis(500), Duration::from_secs(90)];
+    cl.tm_write(255, &timers[..1]).unwrap();
+    cl.tm_write(0, &timers).unwrap();
+    assert_eq!(timers.to_vec(), cl.tm_read(0, 2).unwrap());
+
+    let mut items = vec![
+        DataItem::new(Area::DataBausteine, 1, 10, constant::WL_BYTE, 3),
+        DataItem::new(Area::DataBausteine, 2, 0, constant::WL_BYTE, 1),
+        DataItem::new(Area::Merker, 0, 4, constant::WL_WORD, 1),
+        DataItem::new(Area::Counter, 0, 4, constant::WL_COUNTER, 1),
+    ];
+    cl.read_multi(&mut items).unwrap();
+    assert_eq!([0, 1, 2], items[0].data());
+    assert!(items[1].result().is_err());
+    assert_eq!([0x80, 2], items[2].data());
+    assert_eq!([9, 0x99], items[3].data());
+
+    items[0].set_data(vec![7, 8, 9]);
+    items[1].set_data(vec![1]);
+    items[2].set_data(vec![5, 6]);
+    cl.write_multi(&mut items[..3]).unwrap();
+    assert!(items[0].result().is_ok());
+    assert!(items[1].result().is_err());
+    assert_eq!(
+        [7, 8, 9],
+        server.memory().area(Area::DataBausteine, 1).unwrap()[10..13]
+    );
+
+    // out of range and missing data block
+    assert!(cl.ag_read(1, 1020, 8, &mut [0; 8]).is_err());
+    assert!(cl.ag_read(2, 0, 1, &mut [0]).is_err());
+    assert!(cl.mb_write(256, 1, &mut [0]).is_err());
+}
+
+#[test]
+fn test_control() {
+    let options = Options {
+        max_connections: 2,
+        as_name: "TEST PLC".to_string(),
+        ..Options::default()
+    };
+    let (server, mut cl) = test_server(options);
+
+    assert_eq!(CpuStatus::Run, cl.plc_status().unwrap());
+    assert!(cl.start().is_err());
+
+    cl.stop().unwrap();
+    assert_eq!(CpuStatus::Stop, server.status());
+    assert_eq!(CpuStatus::Stop, cl.plc_status().unwrap());
+    assert!(cl.stop().is_err());
+
+    cl.restart().unwrap();
+    assert_eq!(CpuStatus::Run, cl.plc_status().unwrap());
+
+    server.set_status(CpuStatus::Stop);
+    assert_eq!(CpuStatus::Stop, cl.plc_status().unwrap());
+
+    let cpu_info = cl.cpu_info().unwrap();
+    assert_eq!("TEST PLC", cpu_info.as_name().trim_end_matches('\0'));
+    assert_eq!(
+        "CPU 315-2 PN/DP",
+        cpu_info.module_type_name().trim_end_matches('\0')
+    );
+
+    let cp_info = cl.cp_info().unwrap();
+    assert_eq!(480, cp_info.max_pdu_length());
+    assert_eq!(2, cp_info.max_connections());
+    assert_eq!(1, server.connections());
+
+    // the third connection is refused
+    let addr = server.local_addr();
+    let connect = || {
+        let options = super::tcp::Options::builder(addr.ip())
+            .port(addr.port())
+            .build()
+            .unwrap();
+        super::client::Client::new(super::tcp::Transport::connect(options).unwrap())
+    };
+    let _second = connect().unwrap();
+    assert!(connect().is_err());
+}
+
+#[test]
+fn test_small_pdu() {
+    let options = Options {
+        pdu_length: 240,
+        amq: 1,
+        ..Options::default()
+    };
+    let (_server, mut cl) = test_server(options);
+    assert_eq!(transport::SessionInfo::new(240, 1, 1), cl.session_info());
+
+    // the SZL of the cpu info doesn't fit a single pdu
+    let cpu_info = cl.cpu_info().unwrap();
+    assert_eq!(
+        "S C-X0000000",
+        cpu_info.serial_number().trim_end_matches('\0')
+    );
+
+    let mut buffer = vec![0u8; 600];
+    cl.ag_read(1, 0, 600, &mut buffer).unwrap();
+}
+
+#[test]
+fn test_faults() {
+    use std::time::Instant;
+
+    let (server, mut cl) = test_server(Options::default());
+    let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    cl.ag_write(1, 0, 200, &mut data.clone()).unwrap();
+    let mut buffer = vec![0u8; 200];
+
+    // only the next read gets the error
+    server.inject(
+        Request::Read,
+        Fault::ItemError(error::CODE_7_ADDRESS_OUT_OF_RANGE as u8),
+    );
+    cl.ag_write(1, 0, 1, &mut [0]).unwrap();
+    match cl.ag_read(1, 0, 1, &mut buffer) {
+        Err(Error::CPU { code }) => assert_eq!(error::CODE_7_ADDRESS_OUT_OF_RANGE, code),
+        result => panic!("unexpected {:?}", result),
+    }
+    cl.ag_write(1, 0, 1, &mut [0]).unwrap();
+
+    server.inject(Request::Write, Fault::Error(error::CODE_7_NEED_PASSWORD));
+    match cl.ag_write(1, 0, 1, &mut [0]) {
+        Err(Error::CPU { code }) => assert_eq!(error::CODE_7_NEED_PASSWORD, code),
+        result => panic!("unexpected {:?}", result),
+    }
+    server.inject(Request::Szl, Fault::Error(error::CODE_7_FUN_NOT_AVAILABLE));
+    assert!(cl.plc_status().is_err());
+    assert_eq!(0, server.pending_faults());
+
+    server.inject(Request::Any, Fault::Delay(Duration::from_millis(100)));
+    let start = Instant::now();
+    cl.ag_read(1, 0, 200, &mut buffer).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(data, buffer);
+
+    // the client reassembles the response
+    for fault in [Fault::Segments(3), Fault::Fragments(20)] {
+        server.inject(Request::Read, fault);
+        buffer.iter_mut().for_each(|b| *b = 0);
+        cl.ag_read(1, 0, 200, &mut buffer).unwrap();
+        assert_eq!(data, buffer);
+    }
+
+    server.inject(Request::Read, Fault::Oversize);
+    match cl.ag_read(1, 0, 200, &mut buffer) {
+        Err(Error::Frame(_)) => {}
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn test_fault_connection() {
+    use super::client::Client;
+    use super::reconnect;
+
+    let (server, mut cl) = test_server(Options::default());
+    let mut buffer = [0u8; 4];
+
+    server.inject(Request::Read, Fault::Drop);
+    assert!(cl.ag_read(1, 0, 4, &mut buffer).is_err());
+
+    let mut cl = Client::new(test_transport(&server).unwrap()).unwrap();
+    server.inject(Request::Read, Fault::Truncate(10));
+    assert!(cl.ag_read(1, 0, 4, &mut buffer).is_err());
+
+    server.inject(Request::Connect, Fault::Error(0));
+    assert!(Client::new(test_transport(&server).unwrap()).is_err());
+
+    // the read is sent again on a new connection
+    let options = reconnect::Options {
+        retry_reads: true,
+        ..reconnect::Options::default()
+    };
+    let addr = server.local_addr();
+    let transport = reconnect::Transport::connect(options, move || {
+        let options = super::tcp::Options::builder(addr.ip())
+            .port(addr.port())
+            .read_timeout(Duration::from_secs(2))
+            .build()?;
+        super::tcp::Transport::connect(options)
+    })
+    .unwrap();
+    let mut cl = Client::new(transport).unwrap();
+
+    server.inject(Request::Read, Fault::Drop);
+    cl.ag_read(1, 0, 4, &mut buffer).unwrap();
+    assert_eq!(0, server.pending_faults());
+}
+
+#[test]
+fn test_short_connection_request() {
+    let options = Options {
+        max_connections: 1,
+        ..Options::default()
+    };
+    let server = Server::start("127.0.0.1:0", options, Memory::new()).unwrap();
+
+    // a connection request without references is rejected and frees the connection
+    for _ in 0..3 {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(&[3, 0, 0, 6, 1, COTP_CR]).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        assert_eq!(0x80, response[5]);
+    }
+
+    for _ in 0..100 {
+        if server.connections() == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(test_transport(&server)
+        .and_then(super::client::Client::new)
+        .is_ok());
+}
@@ -6,13 +6,14 @@
 
 extern crate byteorder;
 
-use super::error::{self, Error};
+use super::codec::Decoder;
+use super::error::Error;
+use super::telegram;
 use super::transport::{self, Transport as PackTrait};
-use crate::transport::Connection;
-use byteorder::{BigEndian, ByteOrder};
-use std::io::{Read, Write};
-use std::net::{IpAddr, SocketAddrV4};
-use std::net::TcpStream;
+use crate::transport::{Connection, Profile, SessionInfo};
+use std::convert::TryFrom;
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::Mutex;
 use std::time::Duration;
 
@@ -22,14 +23,23 @@ pub const TIMEOUT: Duration = Duration::from_secs(10);
 pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 pub const MAX_LENGTH: usize = 2084;
 //messages
-const PDU_SIZE_REQUESTED: i32 = 480;
+pub const PDU_SIZE_REQUESTED: u16 = 480;
+/// parallel jobs requested, calling and called
+pub const AMQ_REQUESTED: u16 = 1;
+/// smallest PDU every S7 CPU accepts
+pub const MIN_PDU_SIZE: u16 = 240;
+/// largest PDU of the S7-1500 and S7-400
+pub const MAX_PDU_SIZE: u16 = 960;
+pub const MAX_RACK: u16 = 7;
+pub const MAX_SLOT: u16 = 31;
 pub const ISO_TCP: i32 = 102; //default isotcp port
-const ISO_HEADER_SIZE: i32 = 7; // TPKT+COTP Header Size
-const MIN_PDU_SIZE: i32 = 16;
 
 pub struct Transport {
     options: Options,
     stream: Mutex<TcpStream>,
+    decoder: Decoder,
+    //negotiated with the PLC, all 0 until connected
+    session: SessionInfo,
 }
 
 /// a set of options for the TCP connection
@@ -38,136 +48,334 @@ pub struct Options {
     pub connection_timeout: Option<Duration>,
     pub read_timeout: Duration,
     pub write_timeout: Duration,
-    address: String,
+    //ip address or hostname, resolved when connecting
+    host: String,
+    port: u16,
     pub conn_type: transport::Connection,
     rack: u16,
     slot: u16,
-    //Transport Service Access Point
-    local_tsap: u16,
-    remote_tsap: u16,
-    local_tsap_high: u8,
-    local_tsap_low: u8,
-    remote_tsap_high: u8,
-    remote_tsap_low: u8,
-    last_pdu_type: u8,
-    //PDULength variable to store pdu length after connect
-    pdu_length: i32,
+    //Transport Service Access Point, local and remote
+    //derived from the connection type, rack and slot unless set
+    tsap: Option<(u16, u16)>,
+    //PDU length proposed to the PLC, it may negotiate a smaller one
+    pdu_size: u16,
+    //parallel jobs proposed to the PLC, calling and called
+    amq: (u16, u16),
 }
 
 impl Options {
-    pub fn new(address: IpAddr, port: i32, rack: u16, slot: u16, conn_type: Connection) -> Options {
+    /// `host` is an ip address, IPv4 or IPv6, or a hostname.
+    /// a port of 0 is the ISO on TCP port 102, a port out of the u16 range fails to connect.
+    /// use `Options::builder` for validated options
+    pub fn new<H: ToString>(
+        host: H,
+        port: i32,
+        rack: u16,
+        slot: u16,
+        conn_type: Connection,
+    ) -> Options {
+        // 0 is never connected to, the builder rejects it
         let port = match port {
-            0 => ISO_TCP,
-            _ => port
+            0 => ISO_TCP as u16,
+            _ => u16::try_from(port).unwrap_or(0),
         };
 
         Options {
             connection_timeout: None,
             read_timeout: Duration::new(0, 0),
             write_timeout: Duration::new(0, 0),
-            address: format!("{}:{}", address.to_string(), port.to_string()), //ip:102,
+            host: host.to_string(),
+            port,
             conn_type,
             rack,
             slot,
-            local_tsap: 0,
-            remote_tsap: 0,
-            local_tsap_high: 0,
-            local_tsap_low: 0,
-            remote_tsap_high: 0,
-            remote_tsap_low: 0,
-            last_pdu_type: 0,
-            pdu_length: 0,
+            tsap: None,
+            pdu_size: PDU_SIZE_REQUESTED,
+            amq: (AMQ_REQUESTED, AMQ_REQUESTED),
         }
     }
+
+    /// validated options with the default port, rack 0, slot 2 and 10s timeouts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use s7::{tcp, transport::Profile};
+    ///
+    /// let options = tcp::Options::builder("plc.local")
+    ///     .profile(Profile::S71500)
+    ///     .read_timeout(Duration::from_secs(2))
+    ///     .pdu_size(960)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(960, options.pdu_size());
+    ///
+    /// assert!(tcp::Options::builder("plc.local").slot(32).build().is_err());
+    /// ```
+    pub fn builder<H: ToString>(host: H) -> OptionsBuilder {
+        OptionsBuilder::new(host)
+    }
+
+    /// ip address or hostname, without the brackets of an IPv6 address
+    pub fn host(&self) -> &str {
+        self.host.trim_start_matches('[').trim_end_matches(']')
+    }
+
+    /// 0 if the port given to `Options::new` is out of range
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// PDU length requested when negotiating
+    pub fn pdu_size(&self) -> u16 {
+        self.pdu_size
+    }
+
+    /// parallel jobs requested when negotiating, calling and called
+    pub fn amq(&self) -> (u16, u16) {
+        self.amq
+    }
+
+    pub(crate) fn checked_port(&self) -> Result<u16, Error> {
+        match self.port {
+            0 => Err(Error::InvalidInput {
+                input: "port out of range".to_string(),
+            }),
+            port => Ok(port),
+        }
+    }
+
+    // resolves the host, a hostname may have several addresses
+    fn socket_addrs(&self) -> Result<Vec<SocketAddr>, Error> {
+        match (self.host(), self.checked_port()?).to_socket_addrs() {
+            Ok(addrs) => Ok(addrs.collect()),
+            Err(e) => Err(Error::Connect(format!("{}: {}", self.host(), e))),
+        }
+    }
+
+    /// options with the rack, slot and TSAPs of a PLC family
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::{Ipv4Addr, IpAddr};
+    /// use s7::{tcp, transport::Profile};
+    ///
+    /// let addr = IpAddr::from(Ipv4Addr::new(127, 0, 0, 1));
+    ///
+    /// let logo = tcp::Options::with_profile(addr, 102, Profile::Logo);
+    /// assert_eq!((0x0100, 0x0200), logo.tsap());
+    ///
+    /// let s7_1500 = tcp::Options::with_profile(addr, 102, Profile::S71500);
+    /// assert_eq!((0x0100, 0x0101), s7_1500.tsap());
+    /// ```
+    pub fn with_profile<H: ToString>(host: H, port: i32, profile: Profile) -> Options {
+        let (rack, slot) = profile.rack_slot();
+        let mut options = Options::new(host, port, rack, slot, Connection::PG);
+
+        if let Some((local_tsap, remote_tsap)) = profile.tsap() {
+            options.set_tsap(local_tsap, remote_tsap);
+        }
+        options
+    }
+
+    /// sets explicit local and remote TSAPs, the rack, slot and connection type
+    /// aren't used for the connection anymore
+    pub fn set_tsap(&mut self, local_tsap: u16, remote_tsap: u16) {
+        self.tsap = Some((local_tsap, remote_tsap));
+    }
+
+    /// local and remote TSAP, either set with `set_tsap`
+    /// or derived from the connection type, rack and slot
+    pub fn tsap(&self) -> (u16, u16) {
+        match self.tsap {
+            Some(tsap) => tsap,
+            None => {
+                let remote_tsap = ((self.conn_type as u16) << 8) + (self.rack * 0x20) + self.slot;
+                (0x0100, remote_tsap)
+            }
+        }
+    }
+}
+
+/// builds `Options`, the values are validated by `build`
+#[derive(Debug, Clone)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    pub fn new<H: ToString>(host: H) -> OptionsBuilder {
+        let mut options = Options::new(host, ISO_TCP, 0, 2, Connection::PG);
+        options.connection_timeout = Some(TIMEOUT);
+        options.read_timeout = TIMEOUT;
+        options.write_timeout = TIMEOUT;
+
+        OptionsBuilder { options }
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.options.port = port;
+        self
+    }
+
+    pub fn rack(mut self, rack: u16) -> Self {
+        self.options.rack = rack;
+        self
+    }
+
+    pub fn slot(mut self, slot: u16) -> Self {
+        self.options.slot = slot;
+        self
+    }
+
+    pub fn connection_type(mut self, conn_type: Connection) -> Self {
+        self.options.conn_type = conn_type;
+        self
+    }
+
+    /// explicit local and remote TSAPs instead of the ones of the rack and slot
+    pub fn tsap(mut self, local_tsap: u16, remote_tsap: u16) -> Self {
+        self.options.set_tsap(local_tsap, remote_tsap);
+        self
+    }
+
+    /// rack, slot and TSAPs of a PLC family
+    pub fn profile(mut self, profile: Profile) -> Self {
+        let (rack, slot) = profile.rack_slot();
+        self.options.rack = rack;
+        self.options.slot = slot;
+        self.options.tsap = profile.tsap();
+        self
+    }
+
+    /// `None` waits for the operating system to give up
+    pub fn connection_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.connection_timeout = timeout;
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.read_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.options.write_timeout = timeout;
+        self
+    }
+
+    /// PDU length requested when negotiating, between 240 and 960
+    pub fn pdu_size(mut self, pdu_size: u16) -> Self {
+        self.options.pdu_size = pdu_size;
+        self
+    }
+
+    /// parallel jobs requested when negotiating, the client pipelines
+    /// up to the `amq_calling` accepted by the PLC
+    pub fn amq(mut self, amq_calling: u16, amq_called: u16) -> Self {
+        self.options.amq = (amq_calling, amq_called);
+        self
+    }
+
+    pub fn build(self) -> Result<Options, Error> {
+        let options = self.options;
+
+        let invalid = |input: String| Err(Error::InvalidInput { input });
+
+        if options.host().is_empty() {
+            return invalid("empty host".to_string());
+        }
+        if options.port == 0 {
+            return invalid("port 0".to_string());
+        }
+        if options.rack > MAX_RACK {
+            return invalid(format!("rack {} over {}", options.rack, MAX_RACK));
+        }
+        if options.slot > MAX_SLOT {
+            return invalid(format!("slot {} over {}", options.slot, MAX_SLOT));
+        }
+        if options.pdu_size < MIN_PDU_SIZE || options.pdu_size > MAX_PDU_SIZE {
+            return invalid(format!(
+                "pdu size {} not between {} and {}",
+                options.pdu_size, MIN_PDU_SIZE, MAX_PDU_SIZE
+            ));
+        }
+        if options.amq.0 == 0 || options.amq.1 == 0 {
+            return invalid(format!("amq {:?} with no parallel jobs", options.amq));
+        }
+        if options.connection_timeout == Some(Duration::from_secs(0)) {
+            return invalid("zero connection timeout".to_string());
+        }
+        if options.read_timeout.as_nanos() == 0 {
+            return invalid("zero read timeout".to_string());
+        }
+        if options.write_timeout.as_nanos() == 0 {
+            return invalid("zero write timeout".to_string());
+        }
+        Ok(options)
+    }
 }
 
 impl Transport {
     pub fn connect(options: Options) -> Result<Transport, Error> {
-        let tcp_client = match options.connection_timeout {
-            Some(timeout) => {
-                // Trying connecting with timeout
-                match options.address.parse::<std::net::SocketAddr>() {
-                    Ok(socket_address) => TcpStream::connect_timeout(&socket_address, timeout)?,
-                    Err(e) => return Err(Error::Connect(e.to_string())),
+        let mut last_error = Error::Connect(format!("{}: no address found", options.host()));
+        let mut tcp_client = None;
+
+        // tries every address of the host until one connects
+        for address in options.socket_addrs()? {
+            let stream = match options.connection_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+                None => TcpStream::connect(address),
+            };
+
+            match stream {
+                Ok(stream) => {
+                    tcp_client = Some(stream);
+                    break;
                 }
-            },
-            None => {
-                // Trying connecting with no timeout defined
-                TcpStream::connect(&options.address)?
-            },
-        };
+                Err(e) => last_error = e.into(),
+            }
+        }
 
-        tcp_client.set_read_timeout(Some(options.read_timeout))?;
-        tcp_client.set_write_timeout(Some(options.write_timeout))?;
+        let tcp_client = tcp_client.ok_or(last_error)?;
+
+        // a zero timeout is no timeout, like the async transport
+        let timeout = |duration: Duration| Some(duration).filter(|d| d.as_nanos() > 0);
+
+        tcp_client.set_read_timeout(timeout(options.read_timeout))?;
+        tcp_client.set_write_timeout(timeout(options.write_timeout))?;
         Ok(Transport {
+            decoder: Decoder::new(options.pdu_size as usize),
             options,
             stream: Mutex::new(tcp_client),
+            session: SessionInfo::default(),
         })
     }
 
-    fn set_tsap(&mut self) {
-        let mut remote_tsap = ((self.connection_type() as u16) << 8) as u16
-            + (self.options.rack * 0x20)
-            + self.options.slot;
-        let local_tsap: u16 = 0x0100 & 0x0000FFFF;
-        remote_tsap = remote_tsap & 0x0000FFFF;
-
-        self.options.local_tsap = local_tsap;
-        self.options.local_tsap_high = (local_tsap >> 8) as u8;
-        self.options.local_tsap_low = (local_tsap & 0x00FF) as u8;
-
-        self.options.remote_tsap = remote_tsap;
-        self.options.remote_tsap_high = (remote_tsap >> 8) as u8;
-        self.options.remote_tsap_low = (remote_tsap as u8) & 0x00FF;
-    }
-
     fn iso_connect(&mut self) -> Result<(), Error> {
-        let mut msg = transport::ISO_CONNECTION_REQUEST_TELEGRAM.to_vec();
-
-        msg[16] = self.options.local_tsap_high;
-        msg[17] = self.options.local_tsap_low;
-        msg[20] = self.options.remote_tsap_high;
-        msg[21] = self.options.remote_tsap_low;
-
-        let r = self.send(msg.as_slice());
+        let (local_tsap, remote_tsap) = self.options.tsap();
+        let request = telegram::connection_request(local_tsap, remote_tsap);
 
-        let n = match r {
-            Ok(n) => n.len(),
+        // Sends the connection request telegram
+        let response = match self.send(request.as_slice()) {
+            Ok(response) => response,
             Err(e) => return Err(Error::Connect(e.to_string())),
         };
 
-        // Sends the connection request telegram
-        if n != msg.len() {
-            return Err(Error::PduLength(n as i32));
-        }
-
-        if self.options.last_pdu_type != transport::CONFIRM_CONNECTION {
-            return Err(Error::Iso);
-        }
-        Ok(())
+        telegram::connection_response(response.as_slice())
     }
 
     fn negotiate_pdu_length(&mut self) -> Result<(), Error> {
         // Set PDU Size Requested //lth
-        let mut pdu_size_package = transport::PDU_NEGOTIATION_TELEGRAM.to_vec();
-        BigEndian::write_u16(pdu_size_package[23..].as_mut(), PDU_SIZE_REQUESTED as u16);
+        let (amq_calling, amq_called) = self.options.amq;
+        let request = telegram::negotiate_request(self.options.pdu_size, amq_calling, amq_called);
 
-        // Sends the connection request telegram
-        let response = self.send(pdu_size_package.as_slice())?;
-        if response.len() == 27 && response[17] == 0 && response[18] == 0 {
-            // 20 = size of Negotiate Answer
-            // Get PDU Size Negotiated
-            self.options.pdu_length = BigEndian::read_u16(&response[25..]) as i32;
-            if self.options.pdu_length <= 0 {
-                return Err(Error::Response {
-                    code: error::CLI_NEGOTIATING_PDU,
-                });
-            }
-        } else {
-            return Err(Error::Response {
-                code: error::CLI_NEGOTIATING_PDU,
-            });
-        }
+        let response = self.send(request.as_slice())?;
+        self.session = telegram::negotiate_response(response.as_slice())?;
+        self.decoder
+            .set_pdu_length(self.session.pdu_length() as usize);
         Ok(())
     }
 }
@@ -179,44 +387,20 @@ impl PackTrait for Transport {
             Ok(s) => s,
             Err(_) => return Err(Error::Lock),
         };
-        stream.write(request)?;
-
-        let mut data = vec![0u8; MAX_LENGTH];
-        let mut length;
-
-        loop {
-            // Get TPKT (4 bytes)
-            stream.read(&mut data[..4])?;
+        let result = exchange(&mut self.decoder, &mut stream, request);
 
-            // Read length, ignore transaction & protocol id (4 bytes)
-            length = BigEndian::read_u16(&data[2..]);
-            let length_n = length as i32;
-
-            if length_n == ISO_HEADER_SIZE {
-                stream.read(&mut data[4..7])?;
-            } else {
-                if length_n > PDU_SIZE_REQUESTED + ISO_HEADER_SIZE || length_n < MIN_PDU_SIZE {
-                    return Err(Error::PduLength(length_n));
-                }
-                break;
-            }
+        // a late response would be read as the response of the next request
+        if result.is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
         }
-
-        // Skip remaining 3 COTP bytes
-        stream.read(&mut data[4..7])?;
-        self.options.last_pdu_type = data[5]; // Stores PDU Type, we need it for later
-
-        // Receives the S7 Payload
-        stream.read(&mut data[7..length as usize])?;
-        Ok(data[0..length as usize].to_vec())
+        result
     }
 
     fn pdu_length(&self) -> i32 {
-        self.options.pdu_length
+        self.session.pdu_length() as i32
     }
 
     fn negotiate(&mut self) -> Result<(), Error> {
-        self.set_tsap();
         self.iso_connect()?;
         self.negotiate_pdu_length()
     }
@@ -224,4 +408,289 @@ impl PackTrait for Transport {
     fn connection_type(&self) -> Connection {
         self.options.conn_type
     }
+
+    fn max_jobs(&self) -> u16 {
+        self.session.amq_calling().max(1)
+    }
+
+    fn session_info(&self) -> SessionInfo {
+        self.session
+    }
+
+    fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let max_jobs = self.max_jobs() as usize;
+        let mut stream = match self.stream.lock() {
+            Ok(s) => s,
+            Err(_) => return Err(Error::Lock),
+        };
+
+        let result = pipeline(&mut self.decoder, &mut stream, max_jobs, requests);
+
+        // the responses still in flight would be read as the responses of the next requests
+        if result.is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        result
+    }
+}
+
+fn exchange(
+    decoder: &mut Decoder,
+    stream: &mut TcpStream,
+    request: &[u8],
+) -> Result<Vec<u8>, Error> {
+    stream.write_all(request)?;
+
+    let response = decoder.read(stream)?;
+    telegram::check_reference(request, &response)?;
+    Ok(response)
+}
+
+// sends up to `max_jobs` requests before reading the responses, in any order
+fn pipeline(
+    decoder: &mut Decoder,
+    stream: &mut TcpStream,
+    max_jobs: usize,
+    requests: &[Vec<u8>],
+) -> Result<Vec<Vec<u8>>, Error> {
+    let mut pending = telegram::Pending::new(requests, max_jobs);
+
+    while !pending.is_done() {
+        while let Some(request) = pending.next_request() {
+            stream.write_all(request)?;
+            pending.sent(request)?;
+        }
+        pending.received(decoder.read(stream)?)?;
+    }
+    Ok(pending.into_responses())
+}
+
+#[test]
+fn test_tsap() {
+    let addr = "127.0.0.1";
+
+    let mut options = Options::new(addr, 102, 0, 2, Connection::PG);
+    assert_eq!((0x0100, 0x0102), options.tsap());
+
+    options.set_tsap(0x4d57, 0x4d57);
+    assert_eq!((0x4d57, 0x4d57), options.tsap());
+
+    let options = Options::new(addr, 102, 1, 3, Connection::Basic);
+    assert_eq!((0x0100, 0x0323), options.tsap());
+
+    let options = Options::with_profile(addr, 102, Profile::S7200);
+    assert_eq!((0x1000, 0x1001), options.tsap());
+
+    let options = Options::with_profile(addr, 102, Profile::S7400);
+    assert_eq!((0x0100, 0x0103), options.tsap());
+}
+
+#[test]
+fn test_port_range() {
+    for port in [70000, -1] {
+        let options = Options::new("127.0.0.1", port, 0, 2, Connection::PG);
+        assert_eq!(0, options.port());
+
+        match Transport::connect(options) {
+            Err(Error::InvalidInput { .. }) => {}
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+    }
+    assert_eq!(
+        ISO_TCP as u16,
+        Options::new("plc", 0, 0, 2, Connection::PG).port()
+    );
+}
+
+#[test]
+fn test_options_builder() {
+    let options = Options::builder("127.0.0.1").build().unwrap();
+    assert_eq!(ISO_TCP as u16, options.port());
+    assert_eq!((0x0100, 0x0102), options.tsap());
+    assert_eq!(PDU_SIZE_REQUESTED, options.pdu_size());
+    assert_eq!(Some(TIMEOUT), options.connection_timeout);
+
+    let options = Options::builder("plc")
+        .port(1102)
+        .rack(1)
+        .slot(3)
+        .connection_type(Connection::OP)
+        .pdu_size(240)
+        .build()
+        .unwrap();
+    assert_eq!(1102, options.port());
+    assert_eq!((0x0100, 0x0223), options.tsap());
+    assert_eq!(240, options.pdu_size());
+
+    let options = Options::builder("plc")
+        .profile(Profile::Logo)
+        .build()
+        .unwrap();
+    assert_eq!((0x0100, 0x0200), options.tsap());
+
+    let zero = Duration::from_secs(0);
+    let invalid = [
+        Options::builder(""),
+        Options::builder("plc").port(0),
+        Options::builder("plc").rack(8),
+        Options::builder("plc").slot(32),
+        Options::builder("plc").pdu_size(239),
+        Options::builder("plc").pdu_size(961),
+        Options::builder("plc").amq(0, 1),
+        Options::builder("plc").connection_timeout(Some(zero)),
+        Options::builder("plc").read_timeout(zero),
+        Options::builder("plc").write_timeout(zero),
+    ];
+
+    for builder in invalid.iter() {
+        assert!(builder.clone().build().is_err(), "{:?}", builder);
+    }
+}
+
+#[test]
+fn test_connect_hosts() {
+    use std::net::{IpAddr, Ipv6Addr, TcpListener};
+
+    let connect = |host: &str, port: u16| {
+        let mut options = Options::new(host, port as i32, 0, 2, Connection::PG);
+        options.connection_timeout = Some(Duration::from_secs(2));
+        options.read_timeout = Duration::from_secs(2);
+        options.write_timeout = Duration::from_secs(2);
+        Transport::connect(options)
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(connect("127.0.0.1", port).is_ok());
+    // localhost may resolve to ::1 first, which isn't listening
+    assert!(connect("localhost", port).is_ok());
+    assert!(connect("unknown.invalid", port).is_err());
+
+    // the sandbox may not have IPv6
+    if let Ok(listener) = TcpListener::bind("[::1]:0") {
+        let port = listener.local_addr().unwrap().port();
+
+        assert!(connect("::1", port).is_ok());
+        assert!(connect("[::1]", port).is_ok());
+        assert!(connect(&IpAddr::from(Ipv6Addr::LOCALHOST).to_string(), port).is_ok());
+    }
+}
+
+#[test]
+fn test_pipelined_read() {
+    use super::client::Client;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let plc = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 512];
+
+        stream.read_exact(&mut request[..22]).unwrap();
+        stream
+            .write_all(&telegram::test_connection_confirm())
+            .unwrap();
+        // asked for a pdu of 960 and 4 parallel jobs, a pdu of 240 and 3 parallel jobs are accepted
+        stream.read_exact(&mut request[..25]).unwrap();
+        assert_eq!([0, 4, 0, 4, 3, 192], request[19..25]);
+        stream
+            .write_all(&telegram::test_negotiation(240, 3))
+            .unwrap();
+
+        // 4 chunks, only 3 are sent before the first response
+        let mut requests = [[0u8; 31]; 3];
+        for request in requests.iter_mut() {
+            stream.read_exact(request).unwrap();
+        }
+        stream
+            .write_all(&telegram::test_read_response(&requests[1], &[2; 222]))
+            .unwrap();
+
+        let mut last = [0u8; 31];
+        stream.read_exact(&mut last).unwrap();
+        stream
+            .write_all(&telegram::test_read_response(&last, &[4; 34]))
+            .unwrap();
+        stream
+            .write_all(&telegram::test_read_response(&requests[2], &[3; 222]))
+            .unwrap();
+        stream
+            .write_all(&telegram::test_read_response(&requests[0], &[1; 222]))
+            .unwrap();
+    });
+
+    let options = Options::builder(addr.ip())
+        .port(addr.port())
+        .pdu_size(960)
+        .amq(4, 4)
+        .read_timeout(Duration::from_secs(2))
+        .build()
+        .unwrap();
+    let mut cl = Client::new(Transport::connect(options).unwrap()).unwrap();
+    assert_eq!(SessionInfo::new(240, 3, 3), cl.session_info());
+
+    let mut buffer = vec![0u8; 700];
+    cl.ag_read(888, 0, 700, &mut buffer).unwrap();
+
+    for (i, chunk) in buffer.chunks(222).enumerate() {
+        assert!(chunk.iter().all(|b| *b == i as u8 + 1));
+    }
+    plc.join().unwrap();
+}
+
+#[test]
+fn test_pipelined_timeout() {
+    use super::client::Client;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let plc = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 512];
+
+        stream.read_exact(&mut request[..22]).unwrap();
+        stream
+            .write_all(&telegram::test_connection_confirm())
+            .unwrap();
+        stream.read_exact(&mut request[..25]).unwrap();
+        stream
+            .write_all(&telegram::test_negotiation(240, 2))
+            .unwrap();
+
+        // the second response comes after the read timed out
+        let mut requests = [[0u8; 31]; 2];
+        for request in requests.iter_mut() {
+            stream.read_exact(request).unwrap();
+        }
+        stream
+            .write_all(&telegram::test_read_response(&requests[0], &[1; 222]))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        let _ = stream.write_all(&telegram::test_read_response(&requests[1], &[2; 78]));
+
+        // the connection was closed instead of sending the next read
+        assert_eq!(0, stream.read(&mut request).unwrap_or(0));
+    });
+
+    let options = Options::builder(addr.ip())
+        .port(addr.port())
+        .read_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let mut cl = Client::new(Transport::connect(options).unwrap()).unwrap();
+
+    let mut buffer = vec![0u8; 300];
+    assert!(cl.ag_read(888, 0, 300, &mut buffer).is_err());
+
+    // the late response isn't returned for the next read
+    std::thread::sleep(Duration::from_millis(400));
+    assert!(cl.ag_read(888, 0, 78, &mut buffer[..78]).is_err());
+    plc.join().unwrap();
 }
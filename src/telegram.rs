@@ -0,0 +1,1278 @@
+// Copyright 2019 Petar Dambovaliev. All rights reserved.
+// This software may be modified and distributed under the terms
+// of the BSD license. See the LICENSE file for details.
+
+//! Builds the request telegrams and parses the responses,
+//! shared by the blocking and the async clients so they only differ in how they send
+
+use super::address::{Address, MAX_BIT_OFFSET};
+use super::client::DataItem;
+use super::codec;
+use super::constant::{self, Area, CpuStatus};
+use super::error::{self, Error};
+use super::field::{decode_s5time, encode_s5time, BitAddress, SizedField};
+use super::transport::{self, SessionInfo};
+use byteorder::{BigEndian, ByteOrder};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+// S7 header (10) + function and items count (2)
+const MULTI_REQUEST_HEADER: i32 = 12;
+// S7 ack header (12) + function and items count (2)
+const MULTI_RESPONSE_HEADER: i32 = 14;
+// offset of the PDU reference in the S7 header
+const PDU_REFERENCE: usize = 11;
+
+pub(crate) fn connection_request(local_tsap: u16, remote_tsap: u16) -> Vec<u8> {
+    let mut request = transport::ISO_CONNECTION_REQUEST_TELEGRAM.to_vec();
+
+    BigEndian::write_u16(request[16..].as_mut(), local_tsap);
+    BigEndian::write_u16(request[20..].as_mut(), remote_tsap);
+    request
+}
+
+pub(crate) fn connection_response(response: &[u8]) -> Result<(), Error> {
+    if response.len() != transport::ISO_CONNECTION_REQUEST_TELEGRAM.len() {
+        return Err(Error::PduLength(response.len() as i32));
+    }
+
+    if response[5] != transport::CONFIRM_CONNECTION {
+        return Err(Error::Iso);
+    }
+    Ok(())
+}
+
+pub(crate) fn negotiate_request(pdu_length: u16, amq_calling: u16, amq_called: u16) -> Vec<u8> {
+    let mut request = transport::PDU_NEGOTIATION_TELEGRAM.to_vec();
+    BigEndian::write_u16(request[19..].as_mut(), amq_calling);
+    BigEndian::write_u16(request[21..].as_mut(), amq_called);
+    BigEndian::write_u16(request[23..].as_mut(), pdu_length);
+    request
+}
+
+/// the parameters accepted by the PLC during the pdu negotiation
+pub(crate) fn negotiate_response(response: &[u8]) -> Result<SessionInfo, Error> {
+    if response.len() != 27 || response[17] != 0 || response[18] != 0 {
+        return Err(Error::Response {
+            code: error::CLI_NEGOTIATING_PDU,
+        });
+    }
+
+    let pdu_length = BigEndian::read_u16(&response[25..]);
+
+    if pdu_length == 0 {
+        return Err(Error::Response {
+            code: error::CLI_NEGOTIATING_PDU,
+        });
+    }
+
+    // some PLCs answer 0, there is always at least one job
+    Ok(SessionInfo::new(
+        pdu_length,
+        BigEndian::read_u16(&response[21..]).max(1),
+        BigEndian::read_u16(&response[23..]).max(1),
+    ))
+}
+
+/// sets the PDU reference of a request, the PLC sends it back in the response
+pub(crate) fn set_reference(request: &mut [u8], reference: u16) {
+    BigEndian::write_u16(request[PDU_REFERENCE..].as_mut(), reference);
+}
+
+/// the PDU reference of a request or a response
+pub(crate) fn reference(telegram: &[u8]) -> Result<u16, Error> {
+    if telegram.len() < PDU_REFERENCE + 2 {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_PDU,
+        });
+    }
+    Ok(BigEndian::read_u16(&telegram[PDU_REFERENCE..]))
+}
+
+/// fails if the response is to another request, ie a late one to a request that timed out
+pub(crate) fn check_reference(request: &[u8], response: &[u8]) -> Result<(), Error> {
+    // the COTP connection request has no S7 header
+    if request.len() < PDU_REFERENCE + 2 || request[5] != codec::COTP_DT {
+        return Ok(());
+    }
+
+    let response_reference = reference(response)?;
+    if response_reference != reference(request)? {
+        return Err(Error::InvalidResponse {
+            reason: format!("unexpected pdu reference {}", response_reference),
+            bytes: response.to_vec(),
+        });
+    }
+    Ok(())
+}
+
+/// pipelined requests, up to `max_jobs` are sent before reading the responses
+/// which are matched to their requests by the PDU reference, in any order
+pub(crate) struct Pending<'a> {
+    requests: &'a [Vec<u8>],
+    responses: Vec<Vec<u8>>,
+    max_jobs: usize,
+    // reference and index of the requests waiting for a response
+    waiting: Vec<(u16, usize)>,
+    next: usize,
+}
+
+impl<'a> Pending<'a> {
+    pub(crate) fn new(requests: &'a [Vec<u8>], max_jobs: usize) -> Pending<'a> {
+        Pending {
+            requests,
+            responses: vec![Vec::new(); requests.len()],
+            max_jobs: max_jobs.max(1),
+            waiting: Vec::with_capacity(max_jobs),
+            next: 0,
+        }
+    }
+
+    /// the next request to send, none if all are sent or the max jobs are waiting
+    pub(crate) fn next_request(&self) -> Option<&'a [u8]> {
+        if self.waiting.len() < self.max_jobs {
+            return self.requests.get(self.next).map(|r| r.as_slice());
+        }
+        None
+    }
+
+    /// marks the request returned by `next_request` as sent
+    pub(crate) fn sent(&mut self, request: &[u8]) -> Result<(), Error> {
+        self.waiting.push((reference(request)?, self.next));
+        self.next += 1;
+        Ok(())
+    }
+
+    /// whether every request has been sent and answered
+    pub(crate) fn is_done(&self) -> bool {
+        self.next == self.requests.len() && self.waiting.is_empty()
+    }
+
+    /// matches a response to its request, returns the index of the request
+    pub(crate) fn received(&mut self, response: Vec<u8>) -> Result<usize, Error> {
+        let reference = reference(&response)?;
+
+        match self.waiting.iter().position(|(r, _)| *r == reference) {
+            Some(i) => {
+                let (_, index) = self.waiting.swap_remove(i);
+                self.responses[index] = response;
+                Ok(index)
+            }
+            None => Err(Error::InvalidResponse {
+                reason: format!("unexpected pdu reference {}", reference),
+                bytes: response,
+            }),
+        }
+    }
+
+    /// the responses in the order of the requests
+    pub(crate) fn into_responses(self) -> Vec<Vec<u8>> {
+        self.responses
+    }
+}
+
+/// a read var job, reading has no side effects so it can be sent again
+pub(crate) fn is_read(request: &[u8]) -> bool {
+    request.len() > 17 && request[8] == transport::PDU_JOB && request[17] == transport::PDU_READ
+}
+
+/// a part of a read or write that fits a single pdu
+pub(crate) struct Chunk {
+    pub start: i32,
+    pub amount: i32,
+    pub word_len: i32,
+    /// offset of the chunk data in the whole buffer
+    pub offset: usize,
+    pub size: usize,
+}
+
+pub(crate) fn read_chunks(
+    area: Area,
+    start: i32,
+    amount: i32,
+    word_len: i32,
+    pdu_length: i32,
+) -> Result<Vec<Chunk>, Error> {
+    // 18 = Reply telegram header
+    chunks(area, start, amount, word_len, pdu_length, 18)
+}
+
+pub(crate) fn write_chunks(
+    area: Area,
+    start: i32,
+    amount: i32,
+    word_len: i32,
+    pdu_length: i32,
+) -> Result<Vec<Chunk>, Error> {
+    // 35 = Request telegram header
+    chunks(area, start, amount, word_len, pdu_length, 35)
+}
+
+// splits `amount` elements of `word_len` into chunks of at most `pdu_length - header` bytes
+fn chunks(
+    area: Area,
+    mut start: i32,
+    mut amount: i32,
+    word_len: i32,
+    pdu_length: i32,
+    header: i32,
+) -> Result<Vec<Chunk>, Error> {
+    // Some adjustment
+    let mut word_len = match area {
+        Area::Counter => constant::WL_COUNTER,
+        Area::Timer => constant::WL_TIMER,
+        _ => word_len,
+    };
+
+    // Calc Word size
+    let mut word_size = constant::data_size_byte(word_len);
+
+    if word_size == 0 {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_DATA_SIZE,
+        });
+    }
+
+    if word_len == constant::WL_BIT {
+        amount = 1; // Only 1 bit can be transferred at time
+    } else if word_len != constant::WL_COUNTER && word_len != constant::WL_TIMER {
+        amount *= word_size;
+        word_size = 1;
+        word_len = constant::WL_BYTE;
+    }
+
+    let max_elements = (pdu_length - header) / word_size;
+
+    if max_elements <= 0 {
+        return Err(Error::PduLength(pdu_length));
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+
+    while amount > 0 {
+        let num_elements = amount.min(max_elements);
+        let size = (num_elements * word_size) as usize;
+
+        chunks.push(Chunk {
+            start,
+            amount: num_elements,
+            word_len,
+            offset,
+            size,
+        });
+
+        offset += size;
+        amount -= num_elements;
+        // byte offset or counter/timer number
+        start += num_elements;
+    }
+    Ok(chunks)
+}
+
+// bit offset for bits, the counter/timer number as is, byte offset in bits for everything else
+fn chunk_address(chunk: &Chunk) -> i32 {
+    match chunk.word_len {
+        constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER => chunk.start,
+        _ => chunk.start << 3,
+    }
+}
+
+pub(crate) fn read_request(area: Area, db_number: i32, chunk: &Chunk) -> Vec<u8> {
+    // Setup the telegram
+    let mut request =
+        transport::READ_WRITE_TELEGRAM[..constant::SIZE_HEADER_READ as usize].to_vec();
+
+    // Set DB Number
+    BigEndian::write_u16(request[25..].as_mut(), db_number as u16);
+    // Set Area
+    request[27] = area as u8;
+
+    if let constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER = chunk.word_len {
+        request[22] = chunk.word_len as u8;
+    }
+
+    // Num elements
+    BigEndian::write_u16(request[23..].as_mut(), chunk.amount as u16);
+
+    // Address into the PLC (only 3 bytes)
+    let address = chunk_address(chunk).to_be_bytes();
+    request[28..31].copy_from_slice(&address[1..]);
+    request
+}
+
+/// the error class and code of an ack header, a rejected job has no parameters or data
+fn ack_error(response: &[u8]) -> Result<(), Error> {
+    if response.len() < 19 {
+        return Ok(());
+    }
+
+    match BigEndian::read_u16(response[17..].as_ref()) {
+        0 => Ok(()),
+        code => Err(Error::CPU { code: code as i32 }),
+    }
+}
+
+/// the data of a read response
+pub(crate) fn read_response(response: &[u8], size: usize) -> Result<&[u8], Error> {
+    ack_error(response)?;
+
+    if response.len() < 25 {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_DATA_SIZE,
+        });
+    }
+
+    if response[21] != constant::RES_ITEM_OK {
+        return Err(Error::CPU {
+            code: response[21] as i32,
+        });
+    }
+
+    let end = 25 + size;
+
+    if response.len() < end {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_DATA_SIZE,
+        });
+    }
+    Ok(&response[25..end])
+}
+
+/// `data` is the data of the chunk
+pub(crate) fn write_request(area: Area, db_number: i32, chunk: &Chunk, data: &[u8]) -> Vec<u8> {
+    let data_size = chunk.size as i32;
+    let iso_size = constant::SIZE_HEADER_WRITE + data_size;
+
+    // Setup the telegram
+    let mut request = transport::READ_WRITE_TELEGRAM.to_vec();
+    // Whole telegram Size
+    BigEndian::write_u16(request[2..].as_mut(), iso_size as u16);
+    // Data length
+    BigEndian::write_u16(request[15..].as_mut(), (data_size + 4) as u16);
+    // Function
+    request[17] = transport::PDU_WRITE;
+    // Set Area
+    request[27] = area as u8;
+
+    if let Area::DataBausteine = area {
+        BigEndian::write_u16(request[25..].as_mut(), db_number as u16)
+    }
+
+    // Adjusts word length and the length of the data
+    let (transport_size, length) = match chunk.word_len {
+        constant::WL_BIT => {
+            request[22] = chunk.word_len as u8;
+            (constant::TS_RES_BIT, data_size)
+        }
+        constant::WL_COUNTER | constant::WL_TIMER => {
+            request[22] = chunk.word_len as u8;
+            (constant::TS_RES_OCTET, data_size)
+        }
+        _ => (constant::TS_RES_BYTE, data_size << 3),
+    };
+
+    // Num elements
+    BigEndian::write_u16(request[23..].as_mut(), chunk.amount as u16);
+    // address into the PLC
+    let address = chunk_address(chunk).to_be_bytes();
+    request[28..31].copy_from_slice(&address[1..]);
+    // Transport Size
+    request[32] = transport_size as u8;
+    // length
+    BigEndian::write_u16(request[33..].as_mut(), length as u16);
+
+    request.extend_from_slice(data);
+    request
+}
+
+pub(crate) fn write_response(response: &[u8]) -> Result<(), Error> {
+    ack_error(response)?;
+
+    if response.len() != 22 {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_PDU,
+        });
+    }
+
+    if response[21] != constant::RES_ITEM_OK {
+        return Err(Error::CPU {
+            code: response[21] as i32,
+        });
+    }
+    Ok(())
+}
+
+/// items sent with one request, or an item too big for a single pdu
+pub(crate) enum Group {
+    Items(Vec<usize>),
+    Single(usize),
+}
+
+/// groups the items into requests within the pdu length and the `constant::MAX_VARS` limit
+pub(crate) fn read_groups(items: &[DataItem], pdu_length: i32) -> Result<Vec<Group>, Error> {
+    if pdu_length == 0 {
+        return Err(Error::PduLength(pdu_length));
+    }
+
+    let mut groups = Vec::new();
+    let mut group: Vec<usize> = Vec::new();
+    let (mut request_size, mut response_size) = (MULTI_REQUEST_HEADER, MULTI_RESPONSE_HEADER);
+
+    for (i, item) in items.iter().enumerate() {
+        let size = item.size()?;
+        // return code, transport size, length + data padded to even
+        let item_response_size = 4 + size + size % 2;
+
+        if MULTI_RESPONSE_HEADER + 4 + size > pdu_length {
+            groups.push(Group::Single(i));
+            continue;
+        }
+
+        if group.len() == constant::MAX_VARS
+            || request_size + constant::SIZE_ITEM_SPEC > pdu_length
+            || response_size + item_response_size > pdu_length
+        {
+            groups.push(Group::Items(std::mem::take(&mut group)));
+            request_size = MULTI_REQUEST_HEADER;
+            response_size = MULTI_RESPONSE_HEADER;
+        }
+
+        group.push(i);
+        request_size += constant::SIZE_ITEM_SPEC;
+        response_size += item_response_size;
+    }
+
+    if !group.is_empty() {
+        groups.push(Group::Items(group));
+    }
+    Ok(groups)
+}
+
+/// reads the items at `group` indexes with a single request
+pub(crate) fn read_items_request(items: &[DataItem], group: &[usize]) -> Result<Vec<u8>, Error> {
+    let mut request = transport::READ_WRITE_TELEGRAM[..19].to_vec();
+
+    for &i in group {
+        request.extend(items[i].spec()?);
+    }
+
+    // Items count
+    request[18] = group.len() as u8;
+    // Parameters Length
+    BigEndian::write_u16(
+        request[13..].as_mut(),
+        (2 + group.len() as i32 * constant::SIZE_ITEM_SPEC) as u16,
+    );
+    // Telegram Length
+    let len = request.len() as u16;
+    BigEndian::write_u16(request[2..].as_mut(), len);
+    Ok(request)
+}
+
+pub(crate) fn read_items_response(
+    items: &mut [DataItem],
+    group: &[usize],
+    response: &[u8],
+) -> Result<(), Error> {
+    check_multi_response(response, group.len())?;
+
+    let mut offset = 21;
+
+    for &i in group {
+        if response.len() < offset + 4 {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_DATA_SIZE,
+            });
+        }
+
+        let item = &mut items[i];
+        item.return_code = response[offset];
+
+        if item.return_code != constant::RES_ITEM_OK {
+            item.data.clear();
+            offset += 4;
+            continue;
+        }
+
+        let size = data_size(
+            response[offset + 1],
+            BigEndian::read_u16(response[offset + 2..].as_ref()),
+        );
+
+        if response.len() < offset + 4 + size {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_DATA_SIZE,
+            });
+        }
+
+        item.data = response[offset + 4..offset + 4 + size].to_vec();
+        // data of all items but the last is padded to even
+        offset += 4 + size + size % 2;
+    }
+    Ok(())
+}
+
+/// sets the result of an item that was read on its own,
+/// a rejected item doesn't fail the whole job
+pub(crate) fn read_item_result(
+    item: &mut DataItem,
+    result: Result<Vec<u8>, Error>,
+) -> Result<(), Error> {
+    match result {
+        Ok(data) => {
+            item.data = data;
+            item.return_code = constant::RES_ITEM_OK;
+            Ok(())
+        }
+        Err(Error::CPU { code }) => {
+            item.data.clear();
+            item.return_code = code as u8;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// validates the data of the items and groups them like `read_groups`
+pub(crate) fn write_groups(items: &[DataItem], pdu_length: i32) -> Result<Vec<Group>, Error> {
+    if pdu_length == 0 {
+        return Err(Error::PduLength(pdu_length));
+    }
+
+    for item in items.iter() {
+        let size = item.size()?;
+
+        if item.data.len() != size as usize {
+            return Err(Error::InvalidInput {
+                input: format!(
+                    "write_multi: expected data size {} got {}",
+                    size,
+                    item.data.len()
+                ),
+            });
+        }
+    }
+
+    let mut groups = Vec::new();
+    let mut group: Vec<usize> = Vec::new();
+    let mut request_size = MULTI_REQUEST_HEADER;
+
+    for (i, item) in items.iter().enumerate() {
+        let size = item.data.len() as i32;
+        // item spec + data header + data padded to even
+        let item_request_size = constant::SIZE_ITEM_SPEC + 4 + size + size % 2;
+
+        if MULTI_REQUEST_HEADER + constant::SIZE_ITEM_SPEC + 4 + size > pdu_length {
+            groups.push(Group::Single(i));
+            continue;
+        }
+
+        if group.len() == constant::MAX_VARS || request_size + item_request_size > pdu_length {
+            groups.push(Group::Items(std::mem::take(&mut group)));
+            request_size = MULTI_REQUEST_HEADER;
+        }
+
+        group.push(i);
+        request_size += item_request_size;
+    }
+
+    if !group.is_empty() {
+        groups.push(Group::Items(group));
+    }
+    Ok(groups)
+}
+
+/// writes the items at `group` indexes with a single request
+pub(crate) fn write_items_request(items: &[DataItem], group: &[usize]) -> Result<Vec<u8>, Error> {
+    let mut request = transport::READ_WRITE_TELEGRAM[..19].to_vec();
+    let mut data = Vec::new();
+
+    for (n, &i) in group.iter().enumerate() {
+        request.extend(items[i].spec()?);
+        data.extend(items[i].data_header()?);
+        data.extend_from_slice(items[i].data.as_slice());
+
+        // data of all items but the last is padded to even
+        if items[i].data.len() % 2 == 1 && n + 1 < group.len() {
+            data.push(0);
+        }
+    }
+
+    // Function
+    request[17] = transport::PDU_WRITE;
+    // Items count
+    request[18] = group.len() as u8;
+    // Parameters Length
+    BigEndian::write_u16(
+        request[13..].as_mut(),
+        (2 + group.len() as i32 * constant::SIZE_ITEM_SPEC) as u16,
+    );
+    // Data Length
+    BigEndian::write_u16(request[15..].as_mut(), data.len() as u16);
+    request.extend(data);
+    // Telegram Length
+    let len = request.len() as u16;
+    BigEndian::write_u16(request[2..].as_mut(), len);
+    Ok(request)
+}
+
+pub(crate) fn write_items_response(
+    items: &mut [DataItem],
+    group: &[usize],
+    response: &[u8],
+) -> Result<(), Error> {
+    check_multi_response(response, group.len())?;
+
+    if response.len() < 21 + group.len() {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_DATA_SIZE,
+        });
+    }
+
+    for (n, &i) in group.iter().enumerate() {
+        items[i].return_code = response[21 + n];
+    }
+    Ok(())
+}
+
+/// sets the result of an item that was written on its own
+pub(crate) fn write_item_result(
+    item: &mut DataItem,
+    result: Result<(), Error>,
+) -> Result<(), Error> {
+    match result {
+        Ok(()) => {
+            item.return_code = constant::RES_ITEM_OK;
+            Ok(())
+        }
+        Err(Error::CPU { code }) => {
+            item.return_code = code as u8;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// validates the header of a multi read/write response
+fn check_multi_response(response: &[u8], items_count: usize) -> Result<(), Error> {
+    ack_error(response)?;
+
+    if response.len() < 21 {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_PDU,
+        });
+    }
+
+    if response[20] as usize != items_count {
+        return Err(Error::Response {
+            code: error::CLI_INVALID_PLC_ANSWER,
+        });
+    }
+    Ok(())
+}
+
+// size in bytes of the item data in a response
+pub(crate) fn data_size(transport_size: u8, length: u16) -> usize {
+    let length = length as usize;
+
+    match transport_size as i32 {
+        constant::TS_RES_BIT => length.div_ceil(8),
+        constant::TS_RES_BYTE | constant::TS_RES_INT => length / 8,
+        // TS_RES_REAL, TS_RES_OCTET
+        _ => length,
+    }
+}
+
+// transport size and length of the item data in a response
+fn data_header(word_len: i32, size: usize) -> (u8, u16) {
+    match word_len {
+        constant::WL_BIT => (constant::TS_RES_BIT as u8, 1),
+        constant::WL_COUNTER | constant::WL_TIMER => (constant::TS_RES_OCTET as u8, size as u16),
+        _ => (constant::TS_RES_BYTE as u8, (size * 8) as u16),
+    }
+}
+
+/// the items of a read or write request, with the data of a write
+pub(crate) fn decode_items(request: &[u8]) -> Option<Vec<DataItem>> {
+    if request.len() < 19 {
+        return None;
+    }
+
+    let count = request[18] as usize;
+    let mut offset = 19 + count * constant::SIZE_ITEM_SPEC as usize;
+
+    if request.len() < offset {
+        return None;
+    }
+
+    let mut items: Vec<DataItem> = request[19..offset]
+        .chunks(constant::SIZE_ITEM_SPEC as usize)
+        .map(|spec| {
+            let word_len = spec[3] as i32;
+            let address = (spec[9] as i32) << 16 | (spec[10] as i32) << 8 | spec[11] as i32;
+
+            DataItem::new(
+                Area::from_u8(spec[8]),
+                BigEndian::read_u16(&spec[6..]) as i32,
+                match word_len {
+                    constant::WL_BIT | constant::WL_COUNTER | constant::WL_TIMER => address,
+                    _ => address >> 3,
+                },
+                word_len,
+                BigEndian::read_u16(&spec[4..]) as i32,
+            )
+        })
+        .collect();
+
+    if request[17] != transport::PDU_WRITE {
+        return Some(items);
+    }
+
+    for item in items.iter_mut() {
+        if request.len() < offset + 4 {
+            return None;
+        }
+
+        let size = data_size(
+            request[offset + 1],
+            BigEndian::read_u16(&request[offset + 2..]),
+        );
+        let end = (offset + 4 + size).min(request.len());
+        item.set_data(request[offset + 4..end].to_vec());
+        // the data of all items but the last is padded to even
+        offset = end + size % 2;
+    }
+    Some(items)
+}
+
+/// word length and amount to transfer `size` bytes of an area,
+/// counters and timers are always read as 2 byte elements
+pub(crate) fn field_amount(area: Area, size: i32) -> Result<(i32, i32), Error> {
+    let word_len = match area {
+        Area::Counter => constant::WL_COUNTER,
+        Area::Timer => constant::WL_TIMER,
+        _ => return Ok((constant::WL_BYTE, size)),
+    };
+
+    let word_size = constant::data_size_byte(word_len);
+
+    if size % word_size != 0 {
+        return Err(Error::InvalidInput {
+            input: format!(
+                "field of {} bytes is not a multiple of {} byte {:?} elements",
+                size, word_size, area
+            ),
+        });
+    }
+    Ok((word_len, size / word_size))
+}
+
+pub(crate) fn field_size<F: SizedField>() -> i32 {
+    F::bit_size().div_ceil(8) as i32
+}
+
+pub(crate) fn decode_field<F: SizedField>(address: &Address, bytes: Vec<u8>) -> Result<F, Error> {
+    let mut field = F::decode(
+        address.db_number() as i32,
+        BitAddress::new(address.byte(), address.bit()),
+        bytes,
+    )?;
+    field.set_area(address.area());
+    Ok(field)
+}
+
+/// bit offset of a single bit, counters and timers have no bits
+pub(crate) fn bit_start(area: Area, address: BitAddress) -> Result<i32, Error> {
+    match area {
+        Area::Counter | Area::Timer | Area::Unknown => {
+            return Err(Error::InvalidInput {
+                input: format!("{:?} has no bits", area),
+            })
+        }
+        _ => {}
+    }
+
+    if address.bit > 7 {
+        return Err(Error::InvalidInput {
+            input: format!("bit offset must be 0..7 got {}", address.bit),
+        });
+    }
+    if address.byte > MAX_BIT_OFFSET >> 3 {
+        return Err(Error::InvalidInput {
+            input: format!(
+                "byte offset must be 0..{} got {}",
+                MAX_BIT_OFFSET >> 3,
+                address.byte
+            ),
+        });
+    }
+
+    address
+        .byte
+        .checked_mul(8)
+        .and_then(|bits| bits.checked_add(address.bit as u32))
+        .map(|bits| bits as i32)
+        .ok_or_else(|| Error::InvalidInput {
+            input: format!("bit offset out of range at byte {}", address.byte),
+        })
+}
+
+// counters hold 3 BCD digits in the low 12 bits
+fn decode_counter(raw: u16) -> Option<u16> {
+    let digits = [(raw >> 8) & 0x0F, (raw >> 4) & 0x0F, raw & 0x0F];
+
+    if digits.iter().any(|d| *d > 9) {
+        return None;
+    }
+    Some(digits[0] * 100 + digits[1] * 10 + digits[2])
+}
+
+fn encode_counter(v: u16) -> Result<u16, Error> {
+    if v > 999 {
+        return Err(Error::InvalidInput {
+            input: format!("ct_write: counter value {} is over 999", v),
+        });
+    }
+    Ok(((v / 100) << 8) | ((v / 10 % 10) << 4) | (v % 10))
+}
+
+/// a buffer for `amount` counters or timers from `start`, 2 bytes each
+pub(crate) fn element_buffer(start: i32, amount: i32) -> Result<Vec<u8>, Error> {
+    if start < 0 || amount <= 0 {
+        return Err(Error::InvalidInput {
+            input: format!("invalid start {} and amount {}", start, amount),
+        });
+    }
+    Ok(vec![0u8; amount as usize * 2])
+}
+
+pub(crate) fn decode_counters(bytes: &[u8]) -> Result<Vec<u16>, Error> {
+    bytes
+        .chunks(2)
+        .map(|word| {
+            decode_counter(BigEndian::read_u16(word)).ok_or_else(|| {
+                Error::TryFrom(word.to_vec(), "ct_read: invalid BCD counter".to_string())
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn encode_counters(start: i32, values: &[u16]) -> Result<Vec<u8>, Error> {
+    let mut bytes = element_buffer(start, values.len() as i32)?;
+
+    for (word, v) in bytes.chunks_mut(2).zip(values.iter()) {
+        BigEndian::write_u16(word, encode_counter(*v)?);
+    }
+    Ok(bytes)
+}
+
+pub(crate) fn decode_timers(bytes: &[u8]) -> Result<Vec<Duration>, Error> {
+    bytes
+        .chunks(2)
+        .map(|word| {
+            decode_s5time(BigEndian::read_u16(word)).ok_or_else(|| {
+                Error::TryFrom(word.to_vec(), "tm_read: invalid S5TIME timer".to_string())
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn encode_timers(start: i32, values: &[Duration]) -> Result<Vec<u8>, Error> {
+    let mut bytes = element_buffer(start, values.len() as i32)?;
+
+    for (word, v) in bytes.chunks_mut(2).zip(values.iter()) {
+        BigEndian::write_u16(word, encode_s5time(*v)?);
+    }
+    Ok(bytes)
+}
+
+/// cold start, warm start and stop of the CPU
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Control {
+    ColdStart,
+    WarmStart,
+    Stop,
+}
+
+impl Control {
+    pub fn request(self) -> &'static [u8] {
+        match self {
+            Control::ColdStart => transport::COLD_START_TELEGRAM.as_ref(),
+            Control::WarmStart => transport::WARM_START_TELEGRAM.as_ref(),
+            Control::Stop => transport::STOP_TELEGRAM.as_ref(),
+        }
+    }
+
+    pub fn response(self, response: &[u8]) -> Result<(), Error> {
+        let (start_cmp, start, already_cmp, already) = match self {
+            Control::ColdStart | Control::WarmStart => (
+                transport::PDU_START,
+                error::CLI_CANNOT_START_PLC,
+                transport::PDU_ALREADY_STARTED,
+                error::CLI_ALREADY_RUN,
+            ),
+            Control::Stop => (
+                transport::PDU_STOP,
+                error::CLI_CANNOT_STOP_PLC,
+                transport::PDU_ALREADY_STOPPED,
+                error::CLI_ALREADY_STOP,
+            ),
+        };
+
+        ack_error(response)?;
+
+        if response.len() < transport::TELEGRAM_MIN_RESPONSE {
+            return Err(Error::Response {
+                code: error::ISO_INVALID_PDU,
+            });
+        }
+
+        // function and its result after the ack header
+        if response[19] != start_cmp {
+            return Err(Error::Response { code: start });
+        }
+        if response[20] == already_cmp {
+            return Err(Error::Response { code: already });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn plc_status_response(response: &[u8]) -> Result<CpuStatus, Error> {
+    if response.len() < transport::PLC_STATUS_MIN_RESPONSE {
+        return Err(Error::Response {
+            code: error::ISO_INVALID_PDU,
+        });
+    }
+
+    let result = BigEndian::read_u16(response[27..29].as_ref());
+
+    if result != 0 {
+        return Err(Error::CPU {
+            code: result as i32,
+        });
+    }
+
+    CpuStatus::from_u8(response[44])
+}
+
+/// reads a system status list, which may come in several fragments
+///
+/// `request` gives the next telegram to send until the last fragment has been passed to `response`
+pub(crate) struct SzlRead {
+    id: u16,
+    index: u16,
+    seq_out: u16,
+    seq_in: u8,
+    done: bool,
+    szl: Option<transport::S7SZL>,
+}
+
+impl SzlRead {
+    pub fn new(id: u16, index: u16) -> SzlRead {
+        SzlRead {
+            id,
+            index,
+            seq_out: 0,
+            seq_in: 0,
+            done: false,
+            szl: None,
+        }
+    }
+
+    pub fn request(&mut self) -> Option<Vec<u8>> {
+        if self.done {
+            return None;
+        }
+
+        self.seq_out += 1;
+
+        if self.szl.is_none() {
+            let mut request = transport::SZL_FIRST_TELEGRAM.to_vec();
+            BigEndian::write_u16(request[11..].as_mut(), self.seq_out);
+            BigEndian::write_u16(request[29..].as_mut(), self.id);
+            BigEndian::write_u16(request[31..].as_mut(), self.index);
+            return Some(request);
+        }
+
+        let mut request = transport::SZL_NEXT_TELEGRAM.to_vec();
+        BigEndian::write_u16(request[11..].as_mut(), self.seq_out);
+        request[24] = self.seq_in;
+        Some(request)
+    }
+
+    pub fn response(&mut self, res: &[u8]) -> Result<(), Error> {
+        let validate = |res: &[u8], size: usize| -> Result<(), Error> {
+            if res.len() < transport::MIN_SZL_FIRST_TELEGRAM + size {
+                return Err(Error::Response {
+                    code: error::ISO_INVALID_PDU,
+                });
+            }
+
+            if BigEndian::read_u16(res[27..].as_ref()) != 0 && res[29] != 0xFF {
+                return Err(Error::CPU {
+                    code: error::CLI_INVALID_PLC_ANSWER,
+                });
+            }
+            Ok(())
+        };
+
+        validate(res, 0)?;
+
+        match self.szl.as_mut() {
+            None => {
+                // Skips extra params (ID, Index ...)
+                let data_szl = BigEndian::read_u16(res[31..].as_ref()).saturating_sub(8);
+
+                validate(res, data_szl as usize)?;
+
+                let header = transport::SZLHeader {
+                    length_header: BigEndian::read_u16(res[37..].as_ref()) * 2,
+                    number_of_data_record: BigEndian::read_u16(res[39..].as_ref()),
+                };
+
+                let data = res[41..41 + data_szl as usize].to_vec();
+                self.szl = Some(transport::S7SZL { header, data });
+            }
+            Some(szl) => {
+                // Following fragments carry only the data records
+                let data_szl = BigEndian::read_u16(res[31..].as_ref());
+
+                if res.len() < 33 + data_szl as usize {
+                    return Err(Error::Response {
+                        code: error::ISO_INVALID_PDU,
+                    });
+                }
+
+                szl.data
+                    .extend_from_slice(res[33..33 + data_szl as usize].as_ref());
+            }
+        }
+
+        self.done = res[26] == 0x00;
+        // Slice sequence
+        self.seq_in = res[24];
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<transport::S7SZL, Error> {
+        self.szl.ok_or(Error::Response {
+            code: error::ISO_INVALID_PDU,
+        })
+    }
+}
+
+// a response to a job
+pub(crate) fn ack(request: &[u8], error: i32, params: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut pdu = vec![0x32, transport::PDU_ACK_DATA, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    if request.len() >= 13 {
+        pdu[4..6].copy_from_slice(&request[11..13]);
+    }
+    BigEndian::write_u16(&mut pdu[6..], params.len() as u16);
+    BigEndian::write_u16(&mut pdu[8..], data.len() as u16);
+    BigEndian::write_u16(&mut pdu[10..], error as u16);
+
+    pdu.extend_from_slice(params);
+    pdu.extend_from_slice(data);
+    codec::encode(&pdu)
+}
+
+/// a read var response, every item has its word length and its data or its return code
+pub(crate) fn read_ack(request: &[u8], items: &[(i32, Result<Vec<u8>, u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    for (n, (word_len, item)) in items.iter().enumerate() {
+        match item {
+            Ok(bytes) => {
+                let (transport_size, length) = data_header(*word_len, bytes.len());
+                data.extend_from_slice(&[constant::RES_ITEM_OK, transport_size]);
+                data.extend_from_slice(&length.to_be_bytes());
+                data.extend_from_slice(bytes);
+            }
+            Err(code) => data.extend_from_slice(&[*code, 0, 0, 0]),
+        }
+
+        // data of all items but the last is padded to even
+        if n + 1 < items.len() && data.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    ack(request, 0, &[transport::PDU_READ, items.len() as u8], &data)
+}
+
+// the simulator and the mock lock their state with it,
+// the lock is only poisoned by a panic in a test or a closure, the data is still usable
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// a connection confirm for the test servers
+#[cfg(test)]
+pub(crate) fn test_connection_confirm() -> Vec<u8> {
+    let mut confirm = transport::ISO_CONNECTION_REQUEST_TELEGRAM.to_vec();
+    confirm[5] = transport::CONFIRM_CONNECTION;
+    confirm
+}
+
+// a negotiation response for the test servers
+#[cfg(test)]
+pub(crate) fn test_negotiation(pdu_length: u16, amq: u16) -> Vec<u8> {
+    let mut response = vec![
+        3, 0, 0, 27, 2, 240, 128, 50, 3, 0, 0, 4, 0, 0, 8, 0, 0, 0, 0, 240, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    BigEndian::write_u16(response[21..].as_mut(), amq);
+    BigEndian::write_u16(response[23..].as_mut(), amq);
+    BigEndian::write_u16(response[25..].as_mut(), pdu_length);
+    response
+}
+
+// a single SZL response with `data` for the test servers
+#[cfg(test)]
+pub(crate) fn test_szl_response(data: &[u8]) -> Vec<u8> {
+    let mut response = vec![0u8; 41];
+    response.extend_from_slice(data);
+    response.push(0);
+
+    BigEndian::write_u16(response[31..].as_mut(), data.len() as u16 + 8);
+    response
+}
+
+// a read response to `request` for the test servers
+#[cfg(test)]
+pub(crate) fn test_read_response(request: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut response = vec![
+        3, 0, 0, 0, 2, 240, 128, 50, 3, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 4, 1, 0xFF, 4, 0, 0,
+    ];
+    response.extend_from_slice(data);
+
+    let len = response.len() as u16;
+    BigEndian::write_u16(response[2..].as_mut(), len);
+    response[11..13].copy_from_slice(&request[11..13]);
+    BigEndian::write_u16(response[15..].as_mut(), data.len() as u16 + 4);
+    BigEndian::write_u16(response[23..].as_mut(), data.len() as u16 * 8);
+    response
+}
+
+#[test]
+fn test_element_buffer() {
+    assert_eq!(4, element_buffer(0, 2).unwrap().len());
+    assert!(element_buffer(0, 0).is_err());
+    assert!(element_buffer(0, -1).is_err());
+    assert!(element_buffer(-1, 1).is_err());
+    assert!(encode_counters(-1, &[1]).is_err());
+    assert!(encode_counters(0, &[]).is_err());
+    assert!(encode_timers(-1, &[Duration::from_secs(1)]).is_err());
+    assert!(encode_timers(0, &[]).is_err());
+}
+
+#[test]
+fn test_check_reference() {
+    let chunks = read_chunks(Area::Merker, 0, 1, constant::WL_BYTE, 240).unwrap();
+    let mut request = read_request(Area::Merker, 0, &chunks[0]);
+    set_reference(&mut request, 7);
+
+    let response = test_read_response(&request, &[0]);
+    assert!(check_reference(&request, &response).is_ok());
+
+    set_reference(&mut request, 8);
+    assert!(check_reference(&request, &response).is_err());
+    // no S7 header in the connection request
+    assert!(check_reference(&transport::ISO_CONNECTION_REQUEST_TELEGRAM, &[3, 0, 0, 7]).is_ok());
+}
+
+#[test]
+fn test_read_ack() {
+    let chunks = read_chunks(Area::Merker, 0, 1, constant::WL_BYTE, 240).unwrap();
+    let request = read_request(Area::Merker, 0, &chunks[0]);
+    let items = [
+        (constant::WL_BYTE, Ok(vec![1])),
+        (constant::WL_BIT, Err(0x05)),
+        (constant::WL_COUNTER, Ok(vec![0, 42])),
+    ];
+
+    let response = read_ack(&request, &items);
+    assert_eq!(3, response[20]);
+    // the odd byte is padded, the error has no data, the counter length is in bytes
+    assert_eq!(
+        vec![0xFF, 4, 0, 8, 1, 0, 5, 0, 0, 0, 0xFF, 9, 0, 2, 0, 42],
+        response[21..].to_vec()
+    );
+}
+
+#[test]
+fn test_pending() {
+    let chunks = read_chunks(Area::Merker, 0, 1, constant::WL_BYTE, 240).unwrap();
+    let requests = (1..=3)
+        .map(|i| {
+            let mut request = read_request(Area::Merker, 0, &chunks[0]);
+            set_reference(&mut request, i);
+            request
+        })
+        .collect::<Vec<Vec<u8>>>();
+    let mut pending = Pending::new(&requests, 2);
+
+    for request in requests[..2].iter() {
+        assert_eq!(Some(request.as_slice()), pending.next_request());
+        pending.sent(request).unwrap();
+    }
+    // both jobs are waiting
+    assert_eq!(None, pending.next_request());
+    assert!(pending
+        .received(test_read_response(&requests[2], &[3]))
+        .is_err());
+
+    assert_eq!(
+        1,
+        pending
+            .received(test_read_response(&requests[1], &[2]))
+            .unwrap()
+    );
+    pending.sent(pending.next_request().unwrap()).unwrap();
+    assert_eq!(None, pending.next_request());
+    assert!(!pending.is_done());
+
+    pending
+        .received(test_read_response(&requests[2], &[3]))
+        .unwrap();
+    pending
+        .received(test_read_response(&requests[0], &[1]))
+        .unwrap();
+    assert!(pending.is_done());
+
+    let responses = pending.into_responses();
+    for (i, response) in responses.iter().enumerate() {
+        assert_eq!(i as u8 + 1, response[25]);
+    }
+}
+
+#[test]
+fn test_negotiate_response() {
+    assert_eq!(
+        SessionInfo::new(960, 3, 3),
+        negotiate_response(&test_negotiation(960, 3)).unwrap()
+    );
+
+    // no parallel jobs is read as one
+    assert_eq!(
+        SessionInfo::new(240, 1, 1),
+        negotiate_response(&test_negotiation(240, 0)).unwrap()
+    );
+
+    let request = negotiate_request(960, 8, 4);
+    assert_eq!([0, 8, 0, 4, 3, 192], request[19..]);
+
+    let mut request = read_request(
+        Area::Merker,
+        0,
+        &read_chunks(Area::Merker, 0, 1, constant::WL_BYTE, 240).unwrap()[0],
+    );
+    set_reference(&mut request, 0x1234);
+    assert_eq!([0x12, 0x34], request[11..13]);
+    assert_eq!(
+        0x1234,
+        reference(&test_read_response(&request, &[0])).unwrap()
+    );
+    assert!(reference(&request[..12]).is_err());
+}
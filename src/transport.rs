@@ -6,13 +6,15 @@
 
 use super::constant;
 use super::error::Error;
+#[cfg(feature = "tokio")]
+use std::future::Future;
 
 /// Client Connection Type
 /// 16 possible connections limited by the hardware
 /// The types are defined from the highest to lowest priority
 /// The basic connections are the first which would be closed
 /// if there aren't enough resources
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Connection {
     /// Connect to the PLC programming console (Programmiergeräte). German for programming device.
     PG = 1,
@@ -23,6 +25,79 @@ pub enum Connection {
     Basic = 3,
 }
 
+/// connection defaults of the PLC families
+///
+/// the S7-300/400/1200/1500 connect to the CPU at its rack and slot,
+/// the S7-200 (through a CP243-1) and LOGO! only accept fixed TSAPs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Profile {
+    /// S7-200 through a CP243-1, local TSAP 10.00 and remote TSAP 10.01
+    S7200,
+    /// LOGO! 0BA7 and 0BA8, local TSAP 01.00 and remote TSAP 02.00
+    Logo,
+    /// S7-300, CPU in rack 0 slot 2
+    S7300,
+    /// S7-400, CPU in rack 0 slot 3, check the hardware configuration for other slots
+    S7400,
+    /// S7-1200, rack 0 slot 1, needs PUT/GET access enabled and non-optimized data blocks
+    S71200,
+    /// S7-1500, rack 0 slot 1, needs PUT/GET access enabled and non-optimized data blocks
+    S71500,
+}
+
+impl Profile {
+    pub fn rack_slot(&self) -> (u16, u16) {
+        match self {
+            Profile::S7200 | Profile::Logo => (0, 1),
+            Profile::S7300 => (0, 2),
+            Profile::S7400 => (0, 3),
+            Profile::S71200 | Profile::S71500 => (0, 1),
+        }
+    }
+
+    /// local and remote TSAP of the families that don't use the rack and slot
+    pub fn tsap(&self) -> Option<(u16, u16)> {
+        match self {
+            Profile::S7200 => Some((0x1000, 0x1001)),
+            Profile::Logo => Some((0x0100, 0x0200)),
+            _ => None,
+        }
+    }
+}
+
+/// the parameters negotiated with the PLC when connecting
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct SessionInfo {
+    pdu_length: u16,
+    amq_calling: u16,
+    amq_called: u16,
+}
+
+impl SessionInfo {
+    pub fn new(pdu_length: u16, amq_calling: u16, amq_called: u16) -> SessionInfo {
+        SessionInfo {
+            pdu_length,
+            amq_calling,
+            amq_called,
+        }
+    }
+
+    /// max length of a S7 PDU, requests and responses
+    pub fn pdu_length(&self) -> u16 {
+        self.pdu_length
+    }
+
+    /// max parallel jobs the client can have outstanding
+    pub fn amq_calling(&self) -> u16 {
+        self.amq_calling
+    }
+
+    /// max parallel jobs the PLC can have outstanding
+    pub fn amq_called(&self) -> u16 {
+        self.amq_called
+    }
+}
+
 /// an abstract communication used by the client to send requests
 /// ## How can I implement `Transport`?
 ///
@@ -38,6 +113,64 @@ pub trait Transport {
     fn negotiate(&mut self) -> Result<(), Error>;
 
     fn connection_type(&self) -> Connection;
+
+    /// max parallel jobs negotiated with the PLC (max AmQ calling),
+    /// the default of 1 sends one request at a time
+    fn max_jobs(&self) -> u16 {
+        1
+    }
+
+    /// the negotiated parameters, the default is made of `pdu_length` and `max_jobs`
+    fn session_info(&self) -> SessionInfo {
+        SessionInfo::new(self.pdu_length() as u16, self.max_jobs(), self.max_jobs())
+    }
+
+    /// sends all the requests with up to `max_jobs` of them outstanding at once,
+    /// returns the responses in the order of the requests.
+    /// the PDU references of the requests must be distinct, they are used to match the responses
+    fn send_all(&mut self, requests: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        requests.iter().map(|request| self.send(request)).collect()
+    }
+}
+
+/// the non blocking counterpart of [`Transport`] used by the `AsyncClient`
+///
+/// the telegrams are the same, only the io is async
+#[cfg(feature = "tokio")]
+pub trait AsyncTransport: Send {
+    /// send request to the plc.
+    /// returns a response and an error, if there was any.
+    fn send(&mut self, request: &[u8]) -> impl Future<Output = Result<Vec<u8>, Error>> + Send;
+    /// pdu length needs to be set by the implementor, during the connection phase.
+    fn pdu_length(&self) -> i32;
+    /// negotiate is called by the client and should only be defined by the implementor
+    fn negotiate(&mut self) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn connection_type(&self) -> Connection;
+
+    /// see `Transport::max_jobs`
+    fn max_jobs(&self) -> u16 {
+        1
+    }
+
+    /// see `Transport::session_info`
+    fn session_info(&self) -> SessionInfo {
+        SessionInfo::new(self.pdu_length() as u16, self.max_jobs(), self.max_jobs())
+    }
+
+    /// see `Transport::send_all`
+    fn send_all(
+        &mut self,
+        requests: &[Vec<u8>],
+    ) -> impl Future<Output = Result<Vec<Vec<u8>>, Error>> + Send {
+        async move {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests.iter() {
+                responses.push(self.send(request).await?);
+            }
+            Ok(responses)
+        }
+    }
 }
 
 /// response from the plc that the connection has been confirmed
@@ -158,21 +291,31 @@ pub(crate) const SZL_NEXT_TELEGRAM: [u8; 33] = [
 
 pub(crate) const PLC_STATUS_MIN_RESPONSE: usize = 45;
 
-pub(crate) const TELEGRAM_MIN_RESPONSE: usize = 19;
+pub(crate) const TELEGRAM_MIN_RESPONSE: usize = 21;
 
 pub(crate) const SZL_MIN_RESPONSE: usize = 205;
 
+pub(crate) const PDU_JOB: u8 = 0x01; // request of a job
+pub(crate) const PDU_ACK_DATA: u8 = 0x03; // response of a job
+pub(crate) const PDU_USERDATA: u8 = 0x07; // SZL and other CPU functions
+
+pub(crate) const PDU_READ: u8 = 0x04; // read var
+pub(crate) const PDU_WRITE: u8 = 0x05; // write var
+pub(crate) const PDU_NEGOTIATE: u8 = 0xF0; // setup communication
+
 pub(crate) const PDU_START: u8 = 0x28; // CPU start
 pub(crate) const PDU_STOP: u8 = 0x29; // CPU stop
 
 pub(crate) const PDU_ALREADY_STARTED: u8 = 0x02; // CPU already in run mode
 pub(crate) const PDU_ALREADY_STOPPED: u8 = 0x07; // CPU already in stop mode
 
+#[allow(dead_code)]
 pub(crate) struct SZLHeader {
     pub length_header: u16,
     pub number_of_data_record: u16,
 }
 
+#[allow(dead_code)]
 pub(crate) struct S7SZL {
     pub header: SZLHeader,
     pub data: Vec<u8>,